@@ -0,0 +1,2 @@
+ALTER TABLE `authors` DROP COLUMN `key_id`;
+ALTER TABLE `authors` DROP COLUMN `guild_id`;
@@ -0,0 +1,9 @@
+ALTER TABLE `authors` ADD `guild_id` text;
+ALTER TABLE `authors` ADD `key_id` text;
+
+-- Unkeyed hashes that nothing points at can simply be dropped. The rest are
+-- re-keyed lazily, since the user IDs behind them are not known here.
+DELETE FROM `authors`
+WHERE `id` NOT IN (SELECT `author` FROM `confession`)
+    AND `id` NOT IN (SELECT `author` FROM `replies`)
+    AND `id` NOT IN (SELECT `author_id` FROM `delete_votes`);
@@ -0,0 +1 @@
+ALTER TABLE `confession` DROP COLUMN `author_escrow`;
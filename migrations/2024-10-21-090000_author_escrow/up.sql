@@ -0,0 +1 @@
+ALTER TABLE `confession` ADD `author_escrow` text;
@@ -0,0 +1,3 @@
+DROP TABLE `reveal_approvals`;
+DROP TABLE `reveal_shares`;
+ALTER TABLE `confession` DROP COLUMN `reveal_threshold`;
@@ -0,0 +1,25 @@
+ALTER TABLE `confession` ADD `reveal_threshold` integer;
+
+-- Each share is handed to its moderator, and only a hash is kept to check it
+-- when it is given back.
+CREATE TABLE `reveal_shares` (
+    `id` integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    `confession_id` integer NOT NULL,
+    `moderator_id` text NOT NULL,
+    `share_index` integer NOT NULL,
+    `share_hash` text NOT NULL,
+    FOREIGN KEY (`confession_id`) REFERENCES `confession` (`id`) ON UPDATE no action ON DELETE no action,
+    UNIQUE (`confession_id`, `moderator_id`)
+);
+
+-- A share given back with an approval, kept only until enough have arrived to
+-- reveal the author. Fewer than the threshold say nothing about the key.
+CREATE TABLE `reveal_approvals` (
+    `id` integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    `confession_id` integer NOT NULL,
+    `moderator_id` text NOT NULL,
+    `share` text,
+    `timestamp` timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (`confession_id`) REFERENCES `confession` (`id`) ON UPDATE no action ON DELETE no action,
+    UNIQUE (`confession_id`, `moderator_id`)
+);
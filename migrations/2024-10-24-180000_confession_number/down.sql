@@ -0,0 +1,2 @@
+DROP INDEX `confession_guild_number`;
+ALTER TABLE `confession` DROP COLUMN `number`;
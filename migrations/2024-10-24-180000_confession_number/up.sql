@@ -0,0 +1,9 @@
+ALTER TABLE `confession` ADD `number` integer;
+
+UPDATE `confession` SET `number` = (
+    SELECT COUNT(*) FROM `confession` AS `earlier`
+    WHERE `earlier`.`guild_id` = `confession`.`guild_id`
+        AND `earlier`.`id` <= `confession`.`id`
+);
+
+CREATE UNIQUE INDEX `confession_guild_number` ON `confession` (`guild_id`, `number`);
@@ -0,0 +1 @@
+DROP INDEX `delete_votes_unique_voter`;
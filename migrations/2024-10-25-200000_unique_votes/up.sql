@@ -0,0 +1,9 @@
+-- Keep the earliest of any duplicated votes before enforcing uniqueness
+DELETE FROM `delete_votes`
+WHERE `id` NOT IN (
+    SELECT MIN(`id`) FROM `delete_votes`
+    GROUP BY `confession_id`, `author_id`, `vote_type`
+);
+
+CREATE UNIQUE INDEX `delete_votes_unique_voter`
+    ON `delete_votes` (`confession_id`, `author_id`, `vote_type`);
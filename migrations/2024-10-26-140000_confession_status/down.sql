@@ -0,0 +1,6 @@
+ALTER TABLE `confession` ADD `deleted` integer DEFAULT 0 NOT NULL;
+
+UPDATE `confession` SET `deleted` = 1 WHERE `status` != 'active';
+
+ALTER TABLE `confession` DROP COLUMN `status_changed_at`;
+ALTER TABLE `confession` DROP COLUMN `status`;
@@ -0,0 +1,6 @@
+ALTER TABLE `confession` ADD `status` text CHECK (`status` IN ('active', 'deleted', 'exposed', 'removed')) NOT NULL DEFAULT 'active';
+ALTER TABLE `confession` ADD `status_changed_at` timestamp;
+
+UPDATE `confession` SET `status` = 'deleted' WHERE `deleted` = 1;
+
+ALTER TABLE `confession` DROP COLUMN `deleted`;
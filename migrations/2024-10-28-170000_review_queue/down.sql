@@ -0,0 +1,9 @@
+ALTER TABLE `confession` DROP COLUMN `review_notice`;
+
+ALTER TABLE `confession` ADD `status_old` text CHECK (`status_old` IN ('active', 'deleted', 'exposed', 'removed')) NOT NULL DEFAULT 'active';
+UPDATE `confession` SET `status_old` = CASE
+    WHEN `status` IN ('pending', 'rejected') THEN 'removed'
+    ELSE `status`
+END;
+ALTER TABLE `confession` DROP COLUMN `status`;
+ALTER TABLE `confession` RENAME COLUMN `status_old` TO `status`;
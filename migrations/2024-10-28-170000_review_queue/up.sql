@@ -0,0 +1,10 @@
+-- SQLite cannot change a CHECK constraint in place, so the status column is
+-- rebuilt with the review states
+ALTER TABLE `confession` ADD `status_new` text CHECK (`status_new` IN ('pending', 'active', 'rejected', 'deleted', 'exposed', 'removed')) NOT NULL DEFAULT 'active';
+UPDATE `confession` SET `status_new` = `status`;
+ALTER TABLE `confession` DROP COLUMN `status`;
+ALTER TABLE `confession` RENAME COLUMN `status_new` TO `status`;
+
+-- The author's user ID sealed for the review result notice. Cleared once the
+-- confession has been reviewed.
+ALTER TABLE `confession` ADD `review_notice` text;
@@ -0,0 +1,14 @@
+CREATE TABLE `filter_hits` (
+    `id` integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    `guild_id` text NOT NULL,
+    `rule_id` integer NOT NULL,
+    `author_id` integer NOT NULL,
+    `source` text CHECK (`source` IN ('confession', 'reply')) NOT NULL,
+    `action` text CHECK (`action` IN ('mask', 'review', 'reject')) NOT NULL,
+    `matched` text NOT NULL,
+    `timestamp` timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (`guild_id`) REFERENCES `guild` (`guild_id`) ON UPDATE no action ON DELETE no action,
+    FOREIGN KEY (`author_id`) REFERENCES `authors` (`id`) ON UPDATE no action ON DELETE no action
+);
+
+CREATE INDEX `filter_hits_guild` ON `filter_hits` (`guild_id`, `timestamp`);
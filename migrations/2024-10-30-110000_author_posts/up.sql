@@ -0,0 +1,12 @@
+-- Recent posts by each author pseudonym, for cooldowns and rate limits. Rows
+-- older than the guild's limits are pruned as new posts come in.
+CREATE TABLE `author_posts` (
+    `id` integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    `guild_id` text NOT NULL,
+    `author_id` integer NOT NULL,
+    `source` text CHECK (`source` IN ('confession', 'reply')) NOT NULL,
+    `timestamp` timestamp NOT NULL,
+    FOREIGN KEY (`author_id`) REFERENCES `authors` (`id`) ON UPDATE no action ON DELETE no action
+);
+
+CREATE INDEX `author_posts_author` ON `author_posts` (`author_id`, `source`, `timestamp`);
@@ -0,0 +1,13 @@
+-- Bans are against the author pseudonym, so moderators never learn who they
+-- banned. A NULL expiry is a permanent ban.
+CREATE TABLE `author_bans` (
+    `id` integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    `guild_id` text NOT NULL,
+    `author_id` integer NOT NULL,
+    `moderator_id` text NOT NULL,
+    `expires_at` timestamp,
+    `timestamp` timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (`guild_id`) REFERENCES `guild` (`guild_id`) ON UPDATE no action ON DELETE no action,
+    FOREIGN KEY (`author_id`) REFERENCES `authors` (`id`) ON UPDATE no action ON DELETE no action,
+    UNIQUE (`guild_id`, `author_id`)
+);
@@ -0,0 +1,9 @@
+DROP TABLE `outbox`;
+
+ALTER TABLE `confession` ADD `status_old` text CHECK (`status_old` IN ('pending', 'active', 'rejected', 'deleted', 'exposed', 'removed')) NOT NULL DEFAULT 'active';
+UPDATE `confession` SET `status_old` = CASE
+    WHEN `status` = 'queued' THEN 'removed'
+    ELSE `status`
+END;
+ALTER TABLE `confession` DROP COLUMN `status`;
+ALTER TABLE `confession` RENAME COLUMN `status_old` TO `status`;
@@ -0,0 +1,24 @@
+-- Confessions waiting out the guild's post delay. The row is removed once the
+-- confession has been posted, so anything left over survives a restart.
+-- Failed posts are retried at `release_at` with backoff. Once out of attempts
+-- the entry is kept as a dead letter, along with the last error.
+CREATE TABLE `outbox` (
+    `id` integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    `confession_id` integer NOT NULL,
+    `guild_id` text NOT NULL,
+    `release_at` timestamp NOT NULL,
+    `attempts` integer NOT NULL DEFAULT 0,
+    `last_error` text,
+    `dead` boolean NOT NULL DEFAULT 0,
+    FOREIGN KEY (`confession_id`) REFERENCES `confession` (`id`) ON UPDATE no action ON DELETE no action,
+    FOREIGN KEY (`guild_id`) REFERENCES `guild` (`guild_id`) ON UPDATE no action ON DELETE no action,
+    UNIQUE (`confession_id`)
+);
+CREATE INDEX `outbox_release_at` ON `outbox` (`release_at`);
+
+-- SQLite cannot change a CHECK constraint in place, so the status column is
+-- rebuilt with the queued state
+ALTER TABLE `confession` ADD `status_new` text CHECK (`status_new` IN ('pending', 'queued', 'active', 'rejected', 'deleted', 'exposed', 'removed')) NOT NULL DEFAULT 'active';
+UPDATE `confession` SET `status_new` = `status`;
+ALTER TABLE `confession` DROP COLUMN `status`;
+ALTER TABLE `confession` RENAME COLUMN `status_new` TO `status`;
@@ -0,0 +1,19 @@
+-- Attachments after their metadata has been stripped. Each belongs to either a
+-- confession or a reply, and is kept so it can be posted after review or from
+-- the outbox.
+CREATE TABLE `attachments` (
+    `id` integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    `guild_id` text NOT NULL,
+    `confession_id` integer,
+    `reply_id` integer,
+    `filename` text NOT NULL,
+    `content_type` text NOT NULL,
+    `data` blob NOT NULL,
+    `timestamp` timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (`guild_id`) REFERENCES `guild` (`guild_id`) ON UPDATE no action ON DELETE no action,
+    FOREIGN KEY (`confession_id`) REFERENCES `confession` (`id`) ON UPDATE no action ON DELETE no action,
+    FOREIGN KEY (`reply_id`) REFERENCES `replies` (`id`) ON UPDATE no action ON DELETE no action,
+    CHECK ((`confession_id` IS NULL) <> (`reply_id` IS NULL))
+);
+CREATE INDEX `attachments_confession` ON `attachments` (`confession_id`);
+CREATE INDEX `attachments_reply` ON `attachments` (`reply_id`);
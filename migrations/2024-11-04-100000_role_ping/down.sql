@@ -0,0 +1,2 @@
+ALTER TABLE `guild` DROP COLUMN `last_role_ping`;
+ALTER TABLE `confession` DROP COLUMN `skip_role_ping`;
@@ -0,0 +1,4 @@
+-- Authors can post a confession without pinging the guild's confession role
+ALTER TABLE `confession` ADD `skip_role_ping` boolean NOT NULL DEFAULT 0;
+-- When the confession role was last pinged, to space pings out
+ALTER TABLE `guild` ADD `last_role_ping` timestamp;
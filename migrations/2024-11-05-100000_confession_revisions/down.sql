@@ -0,0 +1,3 @@
+ALTER TABLE `confession` DROP COLUMN `channel_id`;
+
+DROP TABLE `confession_revisions`;
@@ -0,0 +1,8 @@
+-- Failed jobs are retried at `run_at` with backoff. Once out of attempts they
+-- are kept as dead letters, along with the last error.
+ALTER TABLE `schedule` ADD `run_at` integer NOT NULL DEFAULT 0;
+UPDATE `schedule` SET `run_at` = `start_at`;
+ALTER TABLE `schedule` ADD `status` text CHECK (`status` IN ('pending', 'dead')) NOT NULL DEFAULT 'pending';
+ALTER TABLE `schedule` ADD `attempts` integer NOT NULL DEFAULT 0;
+ALTER TABLE `schedule` ADD `last_error` text;
+CREATE INDEX `schedule_due` ON `schedule` (`status`, `run_at`);
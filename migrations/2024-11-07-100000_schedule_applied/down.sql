@@ -0,0 +1,8 @@
+DROP INDEX `schedule_guild`;
+DROP INDEX `schedule_due`;
+DELETE FROM `schedule` WHERE `status` = 'applied';
+ALTER TABLE `schedule` ADD `status_old` text CHECK (`status_old` IN ('pending', 'dead')) NOT NULL DEFAULT 'pending';
+UPDATE `schedule` SET `status_old` = `status`;
+ALTER TABLE `schedule` DROP COLUMN `status`;
+ALTER TABLE `schedule` RENAME COLUMN `status_old` TO `status`;
+CREATE INDEX `schedule_due` ON `schedule` (`status`, `run_at`);
@@ -0,0 +1,10 @@
+-- Jobs are kept once applied, so a running timeout can be found and lifted.
+-- SQLite cannot change a CHECK constraint in place, so the status column is
+-- rebuilt with the applied state
+DROP INDEX `schedule_due`;
+ALTER TABLE `schedule` ADD `status_new` text CHECK (`status_new` IN ('pending', 'applied', 'dead')) NOT NULL DEFAULT 'pending';
+UPDATE `schedule` SET `status_new` = `status`;
+ALTER TABLE `schedule` DROP COLUMN `status`;
+ALTER TABLE `schedule` RENAME COLUMN `status_new` TO `status`;
+CREATE INDEX `schedule_due` ON `schedule` (`status`, `run_at`);
+CREATE INDEX `schedule_guild` ON `schedule` (`guild_id`, `start_at`);
@@ -0,0 +1,6 @@
+-- An enforced job reapplies its timeout until `ends_at` if it is lifted early.
+ALTER TABLE `schedule` ADD `enforce` boolean NOT NULL DEFAULT 0;
+-- How often an enforced timeout has been put back after being lifted early,
+-- and when it last was, so moderators can see it in `/schedule list`.
+ALTER TABLE `schedule` ADD `reapplied` integer NOT NULL DEFAULT 0;
+ALTER TABLE `schedule` ADD `reapplied_at` bigint;
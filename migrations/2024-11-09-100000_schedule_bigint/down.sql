@@ -0,0 +1,16 @@
+DROP INDEX `schedule_guild`;
+DROP INDEX `schedule_due`;
+ALTER TABLE `schedule` ADD `start_at_old` integer NOT NULL DEFAULT 0;
+UPDATE `schedule` SET `start_at_old` = `start_at`;
+ALTER TABLE `schedule` DROP COLUMN `start_at`;
+ALTER TABLE `schedule` RENAME COLUMN `start_at_old` TO `start_at`;
+ALTER TABLE `schedule` ADD `ends_at_old` integer NOT NULL DEFAULT 0;
+UPDATE `schedule` SET `ends_at_old` = `ends_at`;
+ALTER TABLE `schedule` DROP COLUMN `ends_at`;
+ALTER TABLE `schedule` RENAME COLUMN `ends_at_old` TO `ends_at`;
+ALTER TABLE `schedule` ADD `run_at_old` integer NOT NULL DEFAULT 0;
+UPDATE `schedule` SET `run_at_old` = `run_at`;
+ALTER TABLE `schedule` DROP COLUMN `run_at`;
+ALTER TABLE `schedule` RENAME COLUMN `run_at_old` TO `run_at`;
+CREATE INDEX `schedule_due` ON `schedule` (`status`, `run_at`);
+CREATE INDEX `schedule_guild` ON `schedule` (`guild_id`, `start_at`);
@@ -0,0 +1,19 @@
+-- Schedule times are Unix timestamps, which outgrow 32 bits in 2038.
+-- SQLite integers are already 64-bit, but the columns are rebuilt as bigint so
+-- their declared type matches
+DROP INDEX `schedule_guild`;
+DROP INDEX `schedule_due`;
+ALTER TABLE `schedule` ADD `start_at_new` bigint NOT NULL DEFAULT 0;
+UPDATE `schedule` SET `start_at_new` = `start_at`;
+ALTER TABLE `schedule` DROP COLUMN `start_at`;
+ALTER TABLE `schedule` RENAME COLUMN `start_at_new` TO `start_at`;
+ALTER TABLE `schedule` ADD `ends_at_new` bigint NOT NULL DEFAULT 0;
+UPDATE `schedule` SET `ends_at_new` = `ends_at`;
+ALTER TABLE `schedule` DROP COLUMN `ends_at`;
+ALTER TABLE `schedule` RENAME COLUMN `ends_at_new` TO `ends_at`;
+ALTER TABLE `schedule` ADD `run_at_new` bigint NOT NULL DEFAULT 0;
+UPDATE `schedule` SET `run_at_new` = `run_at`;
+ALTER TABLE `schedule` DROP COLUMN `run_at`;
+ALTER TABLE `schedule` RENAME COLUMN `run_at_new` TO `run_at`;
+CREATE INDEX `schedule_due` ON `schedule` (`status`, `run_at`);
+CREATE INDEX `schedule_guild` ON `schedule` (`guild_id`, `start_at`);
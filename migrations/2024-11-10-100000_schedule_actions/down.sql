@@ -0,0 +1,10 @@
+DELETE FROM `schedule` WHERE `victim_id` IS NULL OR `ends_at` IS NULL;
+ALTER TABLE `schedule` ADD `victim_id_old` text NOT NULL DEFAULT '';
+UPDATE `schedule` SET `victim_id_old` = `victim_id`;
+ALTER TABLE `schedule` DROP COLUMN `victim_id`;
+ALTER TABLE `schedule` RENAME COLUMN `victim_id_old` TO `victim_id`;
+ALTER TABLE `schedule` ADD `ends_at_old` bigint NOT NULL DEFAULT 0;
+UPDATE `schedule` SET `ends_at_old` = `ends_at`;
+ALTER TABLE `schedule` DROP COLUMN `ends_at`;
+ALTER TABLE `schedule` RENAME COLUMN `ends_at_old` TO `ends_at`;
+ALTER TABLE `schedule` DROP COLUMN `action`;
@@ -0,0 +1,12 @@
+-- Jobs carry a JSON action payload. Existing jobs are all timeouts, which are
+-- the only action with a member and an end, so those columns become optional.
+-- SQLite cannot drop NOT NULL in place, so both columns are rebuilt
+ALTER TABLE `schedule` ADD `action` text NOT NULL DEFAULT '{"type":"timeout"}';
+ALTER TABLE `schedule` ADD `victim_id_new` text;
+UPDATE `schedule` SET `victim_id_new` = `victim_id`;
+ALTER TABLE `schedule` DROP COLUMN `victim_id`;
+ALTER TABLE `schedule` RENAME COLUMN `victim_id_new` TO `victim_id`;
+ALTER TABLE `schedule` ADD `ends_at_new` bigint;
+UPDATE `schedule` SET `ends_at_new` = `ends_at`;
+ALTER TABLE `schedule` DROP COLUMN `ends_at`;
+ALTER TABLE `schedule` RENAME COLUMN `ends_at_new` TO `ends_at`;
@@ -0,0 +1,8 @@
+DROP TABLE "reveal_approvals";
+DROP TABLE "reveal_shares";
+DROP TABLE "schedule";
+DROP TABLE "replies";
+DROP TABLE "delete_votes";
+DROP TABLE "confession";
+DROP TABLE "guild";
+DROP TABLE "authors";
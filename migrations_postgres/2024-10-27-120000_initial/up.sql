@@ -0,0 +1,78 @@
+-- The whole schema as of the SQLite migrations up to confession_status. New
+-- schema changes get a migration in both directories.
+CREATE TABLE "authors" (
+    "id" serial PRIMARY KEY,
+    "hash" text UNIQUE NOT NULL,
+    "guild_id" text,
+    "key_id" text
+);
+
+CREATE TABLE "guild" (
+    "guild_id" text PRIMARY KEY,
+    "confession_channel_id" text,
+    "config" text NOT NULL,
+    "timestamp" timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE "confession" (
+    "id" serial PRIMARY KEY,
+    "guild_id" text NOT NULL REFERENCES "guild" ("guild_id"),
+    "message_id" text NOT NULL,
+    "content" text NOT NULL,
+    "author" integer NOT NULL REFERENCES "authors" ("id"),
+    "timestamp" timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    "author_escrow" text,
+    "reveal_threshold" integer,
+    "number" integer,
+    "status" text CHECK ("status" IN ('active', 'deleted', 'exposed', 'removed')) NOT NULL DEFAULT 'active',
+    "status_changed_at" timestamp
+);
+
+CREATE UNIQUE INDEX "confession_guild_number" ON "confession" ("guild_id", "number");
+
+CREATE TABLE "delete_votes" (
+    "id" serial PRIMARY KEY,
+    "confession_id" integer NOT NULL REFERENCES "confession" ("id"),
+    "author_id" integer NOT NULL REFERENCES "authors" ("id"),
+    "vote_type" text CHECK ("vote_type" IN ('delete_vote', 'expose_vote')) NOT NULL DEFAULT 'delete_vote',
+    "timestamp" timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE UNIQUE INDEX "delete_votes_unique_voter"
+    ON "delete_votes" ("confession_id", "author_id", "vote_type");
+
+CREATE TABLE "replies" (
+    "id" serial PRIMARY KEY,
+    "guild_id" text NOT NULL REFERENCES "guild" ("guild_id"),
+    "original_confession_id" integer NOT NULL REFERENCES "confession" ("id"),
+    "message_id" text NOT NULL,
+    "content" text NOT NULL,
+    "author" integer NOT NULL REFERENCES "authors" ("id"),
+    "timestamp" timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE "schedule" (
+    "id" serial PRIMARY KEY,
+    "guild_id" text NOT NULL,
+    "victim_id" text NOT NULL,
+    "ends_at" integer NOT NULL,
+    "start_at" integer NOT NULL
+);
+
+CREATE TABLE "reveal_shares" (
+    "id" serial PRIMARY KEY,
+    "confession_id" integer NOT NULL REFERENCES "confession" ("id"),
+    "moderator_id" text NOT NULL,
+    "share_index" integer NOT NULL,
+    "share_hash" text NOT NULL,
+    UNIQUE ("confession_id", "moderator_id")
+);
+
+CREATE TABLE "reveal_approvals" (
+    "id" serial PRIMARY KEY,
+    "confession_id" integer NOT NULL REFERENCES "confession" ("id"),
+    "moderator_id" text NOT NULL,
+    "share" text,
+    "timestamp" timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE ("confession_id", "moderator_id")
+);
@@ -0,0 +1,6 @@
+ALTER TABLE "confession" DROP COLUMN "review_notice";
+
+UPDATE "confession" SET "status" = 'removed' WHERE "status" IN ('pending', 'rejected');
+ALTER TABLE "confession" DROP CONSTRAINT "confession_status_check";
+ALTER TABLE "confession" ADD CONSTRAINT "confession_status_check"
+    CHECK ("status" IN ('active', 'deleted', 'exposed', 'removed'));
@@ -0,0 +1,7 @@
+ALTER TABLE "confession" DROP CONSTRAINT "confession_status_check";
+ALTER TABLE "confession" ADD CONSTRAINT "confession_status_check"
+    CHECK ("status" IN ('pending', 'active', 'rejected', 'deleted', 'exposed', 'removed'));
+
+-- The author's user ID sealed for the review result notice. Cleared once the
+-- confession has been reviewed.
+ALTER TABLE "confession" ADD "review_notice" text;
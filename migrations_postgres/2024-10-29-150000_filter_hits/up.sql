@@ -0,0 +1,12 @@
+CREATE TABLE "filter_hits" (
+    "id" serial PRIMARY KEY,
+    "guild_id" text NOT NULL REFERENCES "guild" ("guild_id"),
+    "rule_id" integer NOT NULL,
+    "author_id" integer NOT NULL REFERENCES "authors" ("id"),
+    "source" text CHECK ("source" IN ('confession', 'reply')) NOT NULL,
+    "action" text CHECK ("action" IN ('mask', 'review', 'reject')) NOT NULL,
+    "matched" text NOT NULL,
+    "timestamp" timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX "filter_hits_guild" ON "filter_hits" ("guild_id", "timestamp");
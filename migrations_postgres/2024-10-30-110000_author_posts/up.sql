@@ -0,0 +1,11 @@
+-- Recent posts by each author pseudonym, for cooldowns and rate limits. Rows
+-- older than the guild's limits are pruned as new posts come in.
+CREATE TABLE "author_posts" (
+    "id" serial PRIMARY KEY,
+    "guild_id" text NOT NULL,
+    "author_id" integer NOT NULL REFERENCES "authors" ("id"),
+    "source" text CHECK ("source" IN ('confession', 'reply')) NOT NULL,
+    "timestamp" timestamp NOT NULL
+);
+
+CREATE INDEX "author_posts_author" ON "author_posts" ("author_id", "source", "timestamp");
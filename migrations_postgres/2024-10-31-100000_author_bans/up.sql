@@ -0,0 +1,11 @@
+-- Bans are against the author pseudonym, so moderators never learn who they
+-- banned. A NULL expiry is a permanent ban.
+CREATE TABLE "author_bans" (
+    "id" serial PRIMARY KEY,
+    "guild_id" text NOT NULL REFERENCES "guild" ("guild_id"),
+    "author_id" integer NOT NULL REFERENCES "authors" ("id"),
+    "moderator_id" text NOT NULL,
+    "expires_at" timestamp,
+    "timestamp" timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE ("guild_id", "author_id")
+);
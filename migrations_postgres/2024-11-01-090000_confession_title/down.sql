@@ -0,0 +1,2 @@
+ALTER TABLE "confession" DROP COLUMN "content_warning";
+ALTER TABLE "confession" DROP COLUMN "title";
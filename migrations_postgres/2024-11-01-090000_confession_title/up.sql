@@ -0,0 +1,2 @@
+ALTER TABLE "confession" ADD "title" text;
+ALTER TABLE "confession" ADD "content_warning" text;
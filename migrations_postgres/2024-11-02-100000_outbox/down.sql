@@ -0,0 +1,6 @@
+DROP TABLE "outbox";
+
+UPDATE "confession" SET "status" = 'removed' WHERE "status" = 'queued';
+ALTER TABLE "confession" DROP CONSTRAINT "confession_status_check";
+ALTER TABLE "confession" ADD CONSTRAINT "confession_status_check"
+    CHECK ("status" IN ('pending', 'active', 'rejected', 'deleted', 'exposed', 'removed'));
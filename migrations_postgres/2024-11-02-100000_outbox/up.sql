@@ -0,0 +1,18 @@
+-- Confessions waiting out the guild's post delay. The row is removed once the
+-- confession has been posted, so anything left over survives a restart.
+-- Failed posts are retried at "release_at" with backoff. Once out of attempts
+-- the entry is kept as a dead letter, along with the last error.
+CREATE TABLE "outbox" (
+    "id" serial PRIMARY KEY,
+    "confession_id" integer NOT NULL REFERENCES "confession" ("id") UNIQUE,
+    "guild_id" text NOT NULL REFERENCES "guild" ("guild_id"),
+    "release_at" timestamp NOT NULL,
+    "attempts" integer NOT NULL DEFAULT 0,
+    "last_error" text,
+    "dead" boolean NOT NULL DEFAULT false
+);
+CREATE INDEX "outbox_release_at" ON "outbox" ("release_at");
+
+ALTER TABLE "confession" DROP CONSTRAINT "confession_status_check";
+ALTER TABLE "confession" ADD CONSTRAINT "confession_status_check"
+    CHECK ("status" IN ('pending', 'queued', 'active', 'rejected', 'deleted', 'exposed', 'removed'));
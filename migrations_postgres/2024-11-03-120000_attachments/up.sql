@@ -0,0 +1,16 @@
+-- Attachments after their metadata has been stripped. Each belongs to either a
+-- confession or a reply, and is kept so it can be posted after review or from
+-- the outbox.
+CREATE TABLE "attachments" (
+    "id" serial PRIMARY KEY,
+    "guild_id" text NOT NULL REFERENCES "guild" ("guild_id"),
+    "confession_id" integer REFERENCES "confession" ("id"),
+    "reply_id" integer REFERENCES "replies" ("id"),
+    "filename" text NOT NULL,
+    "content_type" text NOT NULL,
+    "data" bytea NOT NULL,
+    "timestamp" timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CHECK (("confession_id" IS NULL) <> ("reply_id" IS NULL))
+);
+CREATE INDEX "attachments_confession" ON "attachments" ("confession_id");
+CREATE INDEX "attachments_reply" ON "attachments" ("reply_id");
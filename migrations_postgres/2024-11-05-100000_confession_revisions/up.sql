@@ -0,0 +1,14 @@
+-- The text a confession had before each edit by its author, oldest first.
+CREATE TABLE "confession_revisions" (
+    "id" serial PRIMARY KEY,
+    "confession_id" integer NOT NULL REFERENCES "confession" ("id"),
+    "title" text,
+    "content_warning" text,
+    "content" text NOT NULL,
+    "timestamp" timestamp DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX "confession_revisions_confession_id" ON "confession_revisions" ("confession_id");
+
+-- The channel a confession was posted in, as the guild's confession channel
+-- may since have changed. Older confessions are left NULL.
+ALTER TABLE "confession" ADD "channel_id" text;
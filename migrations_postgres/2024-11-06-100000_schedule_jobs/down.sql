@@ -0,0 +1,6 @@
+DROP INDEX "schedule_due";
+DELETE FROM "schedule" WHERE "status" = 'dead';
+ALTER TABLE "schedule" DROP COLUMN "last_error";
+ALTER TABLE "schedule" DROP COLUMN "attempts";
+ALTER TABLE "schedule" DROP COLUMN "status";
+ALTER TABLE "schedule" DROP COLUMN "run_at";
@@ -0,0 +1,5 @@
+DROP INDEX "schedule_guild";
+DELETE FROM "schedule" WHERE "status" = 'applied';
+ALTER TABLE "schedule" DROP CONSTRAINT "schedule_status_check";
+ALTER TABLE "schedule" ADD CONSTRAINT "schedule_status_check"
+    CHECK ("status" IN ('pending', 'dead'));
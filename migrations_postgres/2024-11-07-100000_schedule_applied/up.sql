@@ -0,0 +1,5 @@
+-- Jobs are kept once applied, so a running timeout can be found and lifted
+ALTER TABLE "schedule" DROP CONSTRAINT "schedule_status_check";
+ALTER TABLE "schedule" ADD CONSTRAINT "schedule_status_check"
+    CHECK ("status" IN ('pending', 'applied', 'dead'));
+CREATE INDEX "schedule_guild" ON "schedule" ("guild_id", "start_at");
@@ -0,0 +1,3 @@
+ALTER TABLE "schedule" DROP COLUMN "reapplied_at";
+ALTER TABLE "schedule" DROP COLUMN "reapplied";
+ALTER TABLE "schedule" DROP COLUMN "enforce";
@@ -0,0 +1,4 @@
+ALTER TABLE "schedule"
+    ALTER COLUMN "start_at" TYPE integer,
+    ALTER COLUMN "ends_at" TYPE integer,
+    ALTER COLUMN "run_at" TYPE integer;
@@ -0,0 +1,5 @@
+-- Schedule times are Unix timestamps, which outgrow 32 bits in 2038
+ALTER TABLE "schedule"
+    ALTER COLUMN "start_at" TYPE bigint,
+    ALTER COLUMN "ends_at" TYPE bigint,
+    ALTER COLUMN "run_at" TYPE bigint;
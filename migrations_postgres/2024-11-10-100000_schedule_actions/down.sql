@@ -0,0 +1,4 @@
+DELETE FROM "schedule" WHERE "victim_id" IS NULL OR "ends_at" IS NULL;
+ALTER TABLE "schedule" ALTER COLUMN "victim_id" SET NOT NULL;
+ALTER TABLE "schedule" ALTER COLUMN "ends_at" SET NOT NULL;
+ALTER TABLE "schedule" DROP COLUMN "action";
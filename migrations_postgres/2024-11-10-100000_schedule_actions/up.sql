@@ -0,0 +1,5 @@
+-- Jobs carry a JSON action payload. Existing jobs are all timeouts, which are
+-- the only action with a member and an end, so those columns become optional
+ALTER TABLE "schedule" ADD "action" text NOT NULL DEFAULT '{"type":"timeout"}';
+ALTER TABLE "schedule" ALTER COLUMN "victim_id" DROP NOT NULL;
+ALTER TABLE "schedule" ALTER COLUMN "ends_at" DROP NOT NULL;
@@ -0,0 +1,112 @@
+use std::io::Cursor;
+
+use image::{
+    codecs::{
+        gif::{GifDecoder, GifEncoder, Repeat},
+        jpeg::JpegEncoder,
+    },
+    AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits,
+};
+
+use crate::models::{AttachmentKind, NewAttachment};
+
+const JPEG_QUALITY: u8 = 90;
+/// Larger images are refused rather than decoded.
+const MAX_DIMENSION: u32 = 8192;
+
+/// What kind of file an upload is, from its content type, or its extension if
+/// Discord did not give one. The content itself is checked by [`strip`].
+pub fn kind_of(content_type: Option<&str>, filename: &str) -> Option<AttachmentKind> {
+    let content_type = content_type.map(|c| {
+        c.split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+    });
+    let extension = filename.rsplit_once('.').map(|(_, e)| e.to_lowercase());
+    match (content_type.as_deref(), extension.as_deref()) {
+        (Some("image/png" | "image/jpeg" | "image/gif" | "image/webp"), _) => {
+            Some(AttachmentKind::IMAGE)
+        }
+        (Some("text/plain"), _) => Some(AttachmentKind::TEXT),
+        (None, Some("png" | "jpg" | "jpeg" | "gif" | "webp")) => Some(AttachmentKind::IMAGE),
+        (None, Some("txt")) => Some(AttachmentKind::TEXT),
+        _ => None,
+    }
+}
+
+/// Rebuild an upload under a generic name and without any metadata.
+///
+/// Images are decoded and encoded again, which drops EXIF (including location),
+/// colour profiles and comments. EXIF rotation is applied first so they still
+/// appear the right way up.
+pub fn strip(kind: AttachmentKind, data: &[u8]) -> Result<NewAttachment, String> {
+    match kind {
+        AttachmentKind::IMAGE => strip_image(data),
+        AttachmentKind::TEXT => {
+            let text = std::str::from_utf8(data)
+                .map_err(|_| "Text attachments must be UTF-8.".to_string())?;
+            Ok(NewAttachment {
+                filename: "attachment.txt".to_string(),
+                content_type: "text/plain; charset=utf-8".to_string(),
+                data: text.as_bytes().to_vec(),
+            })
+        }
+    }
+}
+
+fn strip_image(data: &[u8]) -> Result<NewAttachment, String> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    reader.limits(limits.clone());
+
+    let mut stripped = vec![];
+    let (filename, content_type) = match reader.format() {
+        // Re-encoded frame by frame so animations are kept
+        Some(ImageFormat::Gif) => {
+            let mut decoder = GifDecoder::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+            decoder.set_limits(limits).map_err(|e| e.to_string())?;
+            let frames = decoder
+                .into_frames()
+                .collect_frames()
+                .map_err(|e| e.to_string())?;
+            let mut encoder = GifEncoder::new(&mut stripped);
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(|e| e.to_string())?;
+            encoder.encode_frames(frames).map_err(|e| e.to_string())?;
+            drop(encoder);
+            ("attachment.gif", "image/gif")
+        }
+        Some(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)) => {
+            let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+            let orientation = decoder.orientation().map_err(|e| e.to_string())?;
+            let mut image = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+            image.apply_orientation(orientation);
+            if format == ImageFormat::Jpeg {
+                JpegEncoder::new_with_quality(&mut stripped, JPEG_QUALITY)
+                    .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))
+                    .map_err(|e| e.to_string())?;
+                ("attachment.jpg", "image/jpeg")
+            } else {
+                // WebP is only encoded losslessly, which PNG does as well and
+                // more widely
+                image
+                    .write_to(Cursor::new(&mut stripped), ImageFormat::Png)
+                    .map_err(|e| e.to_string())?;
+                ("attachment.png", "image/png")
+            }
+        }
+        _ => return Err("Only PNG, JPEG, GIF and WebP images are supported.".to_string()),
+    };
+    Ok(NewAttachment {
+        filename: filename.to_string(),
+        content_type: content_type.to_string(),
+        data: stripped,
+    })
+}
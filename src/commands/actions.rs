@@ -0,0 +1,178 @@
+use poise::serenity_prelude::{
+    ChannelId, CreateAllowedMentions, CreateMessage, EditThread, GuildId, Http,
+    PermissionOverwrite, PermissionOverwriteType, Permissions, RoleId, Timestamp, UserId,
+};
+use tracing::info;
+
+use crate::{
+    commands::{Data, Error},
+    db_impl::GuildStore,
+    models::{Schedule, ScheduleAction},
+};
+
+/// Shown in the audit log for changes made by scheduled jobs.
+const AUDIT_REASON: &str = "Scheduled job";
+
+/// Run the action a job carries.
+pub async fn run_action(http: &Http, data: &Data, job: &Schedule) -> Result<(), Error> {
+    let guild_id = GuildId::new(job.guild_id.parse()?);
+    match job.action()? {
+        ScheduleAction::TIMEOUT => apply_timeout(http, job).await,
+        ScheduleAction::GRANT { user_id, role_id } => {
+            http.add_member_role(
+                guild_id,
+                UserId::new(user_id.parse()?),
+                RoleId::new(role_id.parse()?),
+                Some(AUDIT_REASON),
+            )
+            .await?;
+            info!("Gave role {} to member {}", role_id, user_id);
+            Ok(())
+        }
+        ScheduleAction::REVOKE { user_id, role_id } => {
+            http.remove_member_role(
+                guild_id,
+                UserId::new(user_id.parse()?),
+                RoleId::new(role_id.parse()?),
+                Some(AUDIT_REASON),
+            )
+            .await?;
+            info!("Took role {} from member {}", role_id, user_id);
+            Ok(())
+        }
+        ScheduleAction::LOCK { channel_id } => set_locked(http, guild_id, &channel_id, true).await,
+        ScheduleAction::UNLOCK { channel_id } => {
+            set_locked(http, guild_id, &channel_id, false).await
+        }
+        ScheduleAction::POST {
+            channel_id,
+            content,
+        } => {
+            ChannelId::new(channel_id.parse()?)
+                .send_message(
+                    http,
+                    CreateMessage::new()
+                        .content(content)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                )
+                .await?;
+            info!("Posted a scheduled message in channel {}", channel_id);
+            Ok(())
+        }
+        ScheduleAction::CLOSE {
+            confession_number,
+            thread_id,
+        } => {
+            ChannelId::new(thread_id.parse()?)
+                .edit_thread(http, EditThread::new().locked(true).archived(true))
+                .await?;
+            info!("Closed the replies to confession #{}", confession_number);
+            Ok(())
+        }
+        ScheduleAction::BLACKOUT { enabled } => set_blackout(data, &job.guild_id, enabled).await,
+    }
+}
+
+/// Describe what a job does, for `/schedule list`.
+pub fn describe_action(job: &Schedule) -> String {
+    match job.action() {
+        Ok(ScheduleAction::TIMEOUT) => format!(
+            "Timeout <@{}> until <t:{}:R>",
+            job.victim_id.as_deref().unwrap_or_default(),
+            job.ends_at.unwrap_or_default()
+        ),
+        Ok(ScheduleAction::GRANT { user_id, role_id }) => {
+            format!("Give <@&{}> to <@{}>", role_id, user_id)
+        }
+        Ok(ScheduleAction::REVOKE { user_id, role_id }) => {
+            format!("Take <@&{}> from <@{}>", role_id, user_id)
+        }
+        Ok(ScheduleAction::LOCK { channel_id }) => format!("Lock <#{}>", channel_id),
+        Ok(ScheduleAction::UNLOCK { channel_id }) => format!("Unlock <#{}>", channel_id),
+        Ok(ScheduleAction::POST { channel_id, .. }) => {
+            format!("Post a message in <#{}>", channel_id)
+        }
+        Ok(ScheduleAction::CLOSE {
+            confession_number, ..
+        }) => format!("Close the replies to Confession #{}", confession_number),
+        Ok(ScheduleAction::BLACKOUT { enabled: true }) => "Start a confession blackout".to_owned(),
+        Ok(ScheduleAction::BLACKOUT { enabled: false }) => "End the confession blackout".to_owned(),
+        Err(_) => "Unknown action".to_owned(),
+    }
+}
+
+/// Time out the member a job was scheduled for.
+pub async fn apply_timeout(http: &Http, job: &Schedule) -> Result<(), Error> {
+    let guild_id = GuildId::new(job.guild_id.parse()?);
+    let victim_id = UserId::new(
+        job.victim_id
+            .as_deref()
+            .ok_or("No member to time out")?
+            .parse()?,
+    );
+    let ends_at = Timestamp::from_unix_timestamp(job.ends_at.ok_or("No end to the timeout")?)?;
+
+    let mut member = http.get_member(guild_id, victim_id).await?;
+    member.disable_communication_until(http, ends_at).await?;
+    info!("Disabled communication for member with ID: {}", victim_id);
+    Ok(())
+}
+
+/// Deny or restore everyone's permission to send messages in a channel,
+/// leaving the rest of its overwrite for everyone alone.
+async fn set_locked(
+    http: &Http,
+    guild_id: GuildId,
+    channel_id: &String,
+    locked: bool,
+) -> Result<(), Error> {
+    let channel_id = ChannelId::new(channel_id.parse()?);
+    let channel = http
+        .get_channel(channel_id)
+        .await?
+        .guild()
+        .ok_or("Only server channels can be locked")?;
+
+    let everyone = PermissionOverwriteType::Role(guild_id.everyone_role());
+    let (mut allow, mut deny) = channel
+        .permission_overwrites
+        .iter()
+        .find(|overwrite| overwrite.kind == everyone)
+        .map(|overwrite| (overwrite.allow, overwrite.deny))
+        .unwrap_or((Permissions::empty(), Permissions::empty()));
+    let sending = Permissions::SEND_MESSAGES | Permissions::SEND_MESSAGES_IN_THREADS;
+    if locked {
+        allow.remove(sending);
+        deny.insert(sending);
+    } else {
+        deny.remove(sending);
+    }
+
+    channel_id
+        .create_permission(
+            http,
+            PermissionOverwrite {
+                allow,
+                deny,
+                kind: everyone,
+            },
+            Some(AUDIT_REASON),
+        )
+        .await?;
+    info!(
+        "{} channel {}",
+        if locked { "Locked" } else { "Unlocked" },
+        channel_id
+    );
+    Ok(())
+}
+
+async fn set_blackout(data: &Data, guild_id: &String, enabled: bool) -> Result<(), Error> {
+    data.db.set_blackout(guild_id, enabled).await?;
+    info!(
+        "{} the confession blackout in guild {}",
+        if enabled { "Started" } else { "Ended" },
+        guild_id
+    );
+    Ok(())
+}
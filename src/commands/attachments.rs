@@ -0,0 +1,110 @@
+use poise::serenity_prelude::{Attachment, CreateAttachment, CreateEmbed, CreateMessage};
+
+use crate::{
+    attachments::{kind_of, strip},
+    commands::Error,
+    models::{AttachmentKind, AttachmentRules, NewAttachment},
+};
+
+/// Check an upload against the guild's rules. This is cheap, so it can be done
+/// before the interaction is responded to.
+pub fn check_attachment(
+    rules: &AttachmentRules,
+    upload: &Attachment,
+) -> Result<AttachmentKind, Error> {
+    if rules.kinds.is_empty() {
+        return Err(Box::from("Attachments are not allowed in this server."));
+    }
+    let kind = match kind_of(upload.content_type.as_deref(), &upload.filename) {
+        Some(kind) if rules.kinds.contains(&kind) => kind,
+        _ => {
+            return Err(Box::from(format!(
+                "Only {} attachments are allowed in this server.",
+                describe_kinds(&rules.kinds)
+            )))
+        }
+    };
+    if upload.size > rules.max_size {
+        return Err(Box::from(format!(
+            "Attachments can be at most {}.",
+            describe_size(rules.max_size)
+        )));
+    }
+    Ok(kind)
+}
+
+/// Check an upload, then download it and strip its name and metadata.
+pub async fn accept_attachment(
+    rules: &AttachmentRules,
+    upload: &Attachment,
+) -> Result<NewAttachment, Error> {
+    let kind = check_attachment(rules, upload)?;
+    let data = upload.download().await?;
+    let attachment = tokio::task::spawn_blocking(move || strip(kind, &data))
+        .await?
+        .map_err(|e| format!("Could not read the attachment. Reason: {}", e))?;
+    // Re-encoding can make an image larger
+    if attachment.data.len() > rules.max_size as usize {
+        return Err(Box::from(format!(
+            "Attachments can be at most {}.",
+            describe_size(rules.max_size)
+        )));
+    }
+    Ok(attachment)
+}
+
+/// The text of an attachment, so it can be run through the content filter.
+pub fn attachment_text(attachment: &NewAttachment) -> Option<String> {
+    if attachment.is_image() {
+        return None;
+    }
+    String::from_utf8(attachment.data.clone()).ok()
+}
+
+/// Add an attachment to a message, showing it in the embed if it is an image.
+pub fn attach<'a>(
+    message: CreateMessage<'a>,
+    embed: CreateEmbed<'a>,
+    attachment: Option<&NewAttachment>,
+) -> CreateMessage<'a> {
+    match attachment {
+        Some(attachment) => {
+            let embed = match attachment.is_image() {
+                true => embed.image(format!("attachment://{}", attachment.filename)),
+                false => embed,
+            };
+            message.embed(embed).add_file(CreateAttachment::bytes(
+                attachment.data.clone(),
+                attachment.filename.clone(),
+            ))
+        }
+        None => message.embed(embed),
+    }
+}
+
+/// Describe a guild's attachment rules for the config embed.
+pub fn describe_attachment_rules(rules: &AttachmentRules) -> String {
+    if rules.kinds.is_empty() {
+        return "Unset".to_owned();
+    }
+    format!(
+        "{}, up to {}",
+        describe_kinds(&rules.kinds),
+        describe_size(rules.max_size)
+    )
+}
+
+fn describe_kinds(kinds: &[AttachmentKind]) -> String {
+    kinds
+        .iter()
+        .map(|kind| match kind {
+            AttachmentKind::IMAGE => "image",
+            AttachmentKind::TEXT => "text",
+        })
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+fn describe_size(bytes: u32) -> String {
+    format!("{} MB", bytes / (1024 * 1024))
+}
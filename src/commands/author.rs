@@ -0,0 +1,355 @@
+use chrono::{TimeDelta, Utc};
+use confession_bot_rs::CONFESSION_SOURCE_STR;
+use poise::{
+    serenity_prelude::{
+        ChannelId, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+        EditMessage, Http, Message, MessageId,
+    },
+    CreateReply,
+};
+use tracing::{info, warn};
+
+use crate::{
+    commands::{
+        confess::{
+            await_composer, composer, confession_embed, read_composer, too_long_embed,
+            BLACKOUT_MESSAGE,
+        },
+        filter::{blocked_embed, screen, Screened},
+        moderation::{is_banned, BANNED_MESSAGE},
+        Context, Data, Error,
+    },
+    db_impl::{AttachmentStore, AuthorStore, ConfessionStore, GuildStore},
+    models::{Confession, ConfessionDraft, GuildConfig},
+    sanitise::sanitise,
+};
+
+/// Delete or edit a confession you posted.
+#[poise::command(
+    slash_command,
+    rename = "confession",
+    subcommands("delete", "edit"),
+    subcommand_required
+)]
+pub async fn own_confession(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Delete a confession you posted.
+#[poise::command(slash_command, ephemeral)]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "The number of your confession"] id: u32,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let guild_config = data.db.get_guild_config(&guild_id).await?;
+    let confession = own_confession_by_id(ctx, &guild_config, id).await?;
+
+    if !data.db.retract_confession(confession.id).await? {
+        return Err(Box::from("This confession can no longer be deleted."));
+    }
+    data.db.delete_attachments(confession.id).await?;
+    info!("Author deleted confession {}", confession.id);
+
+    // The confession is gone either way, so a missing message is not an error
+    let taken_down = match posted_message(ctx.http(), data, &confession).await {
+        Ok(mut message) => message
+            .edit(
+                ctx.http(),
+                EditMessage::new()
+                    .embed(
+                        CreateEmbed::new()
+                            .title(format!("Confession #{}", id))
+                            .description("Deleted by its author")
+                            .color(0xFF0000),
+                    )
+                    .components(vec![])
+                    .remove_all_attachments(),
+            )
+            .await
+            .map_err(Error::from),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = taken_down {
+        warn!(
+            "Could not take down the message of confession {}: {}",
+            confession.id, e
+        );
+    }
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Confession Deleted")
+                .color(0x00FF00)
+                .description(format!("Confession #{} has been deleted.", id)),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Edit a confession you posted. Its earlier text is kept.
+#[poise::command(slash_command)]
+async fn edit(
+    ctx: Context<'_>,
+    #[description = "The number of your confession"] id: u32,
+) -> Result<(), Error> {
+    let app_ctx = match ctx {
+        Context::Application(app_ctx) => app_ctx,
+        Context::Prefix(_) => return Err(Box::from("`/confession edit` is only a slash command.")),
+    };
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let user_id = ctx.author().id;
+    let http = ctx.http();
+    let nonce = ctx.id();
+
+    let guild_config = data.db.get_guild_config(&guild_id).await?;
+    let confession = own_confession_by_id(ctx, &guild_config, id).await?;
+    // Deleting is still allowed, as a blackout only refuses new content
+    if guild_config.blackout {
+        return Err(Box::from(BLACKOUT_MESSAGE));
+    }
+    {
+        let config = data.config.read().await;
+        if is_banned(
+            &data.db,
+            &config.author_keys,
+            &guild_id,
+            &user_id.to_string(),
+        )
+        .await?
+        {
+            return Err(Box::from(BANNED_MESSAGE));
+        }
+    }
+
+    let draft = ConfessionDraft {
+        title: confession.title.clone(),
+        content_warning: confession.content_warning.clone(),
+        content: confession.content.clone(),
+        ..Default::default()
+    };
+    app_ctx
+        .interaction
+        .create_response(
+            http,
+            CreateInteractionResponse::Modal(composer(nonce, &draft)),
+        )
+        .await?;
+    let modal = match await_composer(ctx, user_id, nonce).await {
+        Some(modal) => modal,
+        None => return Ok(()),
+    };
+    let mut edited = draft.clone();
+    read_composer(&modal, &mut edited);
+
+    let outcome = match save_edit(ctx, &guild_config, &confession, &edited).await {
+        Ok(outcome) => outcome,
+        Err(e) => CreateEmbed::default()
+            .color(0xFF0000)
+            .title("Error")
+            .description(e.to_string()),
+    };
+    modal
+        .create_response(
+            http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .embed(outcome),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Filter and store an edit, then update the posted message. Returns what the
+/// author should be told.
+async fn save_edit(
+    ctx: Context<'_>,
+    guild_config: &GuildConfig,
+    confession: &Confession,
+    draft: &ConfessionDraft,
+) -> Result<CreateEmbed<'static>, Error> {
+    let data = ctx.data();
+    let guild_id = confession.guild_id.clone();
+    let user_id = ctx.author().id.to_string();
+
+    // A blackout may have started while the edit was being written
+    if data.db.get_guild_config(&guild_id).await?.blackout {
+        return Err(Box::from(BLACKOUT_MESSAGE));
+    }
+
+    // Edits are shown straight away, so they cannot be held for review
+    let mut screened = vec![];
+    for field in [
+        &draft.title,
+        &draft.content_warning,
+        &Some(draft.content.clone()),
+    ] {
+        let field = match field {
+            Some(field) => field,
+            None => {
+                screened.push(None);
+                continue;
+            }
+        };
+        match screen(
+            data,
+            &guild_id,
+            &user_id,
+            guild_config,
+            field,
+            CONFESSION_SOURCE_STR,
+        )
+        .await?
+        {
+            Screened::Allowed(field) => {
+                screened.push(Some(sanitise(&field, guild_config.sanitise_level)))
+            }
+            Screened::Held(..) | Screened::Blocked => {
+                return Ok(blocked_embed(CONFESSION_SOURCE_STR))
+            }
+        }
+    }
+    let mut screened = screened.into_iter();
+    let draft = ConfessionDraft {
+        title: screened.next().flatten(),
+        content_warning: screened.next().flatten(),
+        content: screened.next().flatten().unwrap_or_default(),
+        ..Default::default()
+    };
+    if let Some(refusal) = too_long_embed(&draft) {
+        return Ok(refusal);
+    }
+
+    // Stored first, which checks the confession is still active. If the
+    // message cannot be updated after all, the edit is undone
+    let edited = match data.db.edit_confession(confession.id, &draft).await? {
+        Some(edited) => edited,
+        None => return Err(Box::from("This confession can no longer be edited.")),
+    };
+    let number = confession.number.unwrap_or_default();
+    let message = match show_edit(ctx.http(), data, confession, &draft).await {
+        Ok(message) => message,
+        Err(e) => {
+            if let Err(e) = data.db.undo_confession_edit(&edited).await {
+                warn!(
+                    "Could not undo the edit of confession {}: {}",
+                    confession.id, e
+                );
+            }
+            return Err(e);
+        }
+    };
+    info!("Author edited confession {}", edited.id);
+
+    Ok(CreateEmbed::default()
+        .title("Confession Edited")
+        .color(0x00FF00)
+        .description(format!(
+            "Your changes to Confession #{} have been posted: {}",
+            number,
+            message.link()
+        )))
+}
+
+/// Show an edit on the posted message, keeping the colour and any image of
+/// the posted embed.
+async fn show_edit(
+    http: &Http,
+    data: &Data,
+    confession: &Confession,
+    draft: &ConfessionDraft,
+) -> Result<Message, Error> {
+    let mut message = posted_message(http, data, confession).await?;
+    let mut embed = confession_embed(
+        format!(
+            "Confession #{} (edited)",
+            confession.number.unwrap_or_default()
+        ),
+        draft.title.as_ref(),
+        draft.content_warning.as_ref(),
+        &draft.content,
+    );
+    if let Some(posted) = message.embeds.first() {
+        if let Some(colour) = posted.colour {
+            embed = embed.colour(colour);
+        }
+        if let Some(image) = &posted.image {
+            embed = embed.image(image.url.to_string());
+        }
+    }
+    message.edit(http, EditMessage::new().embed(embed)).await?;
+    Ok(message)
+}
+
+/// Look up a confession and check the caller wrote it, recently enough to
+/// change it.
+async fn own_confession_by_id(
+    ctx: Context<'_>,
+    guild_config: &GuildConfig,
+    id: u32,
+) -> Result<Confession, Error> {
+    let data = ctx.data();
+    let guild_id = &ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    if guild_config.author_edit_window == 0 {
+        return Err(Box::from(
+            "This server does not allow authors to delete or edit their confessions.",
+        ));
+    }
+    let confession = data
+        .db
+        .get_confession_by_id_guild(id, guild_id)
+        .await
+        .map_err(|_| format!("Could not find confession with ID `{}` in the Guild.", id))?;
+
+    let config = data.config.read().await;
+    let author_id = data
+        .db
+        .insert_author(&config.author_keys, guild_id, &ctx.author().id.to_string())
+        .await?;
+    if confession.author != author_id {
+        return Err(Box::from("You can only change your own confessions."));
+    }
+
+    // Counted from when it was posted, which may be after it was written
+    let posted_at = confession
+        .status_changed_at
+        .unwrap_or(confession.timestamp)
+        .and_utc();
+    if Utc::now() > posted_at + TimeDelta::seconds(guild_config.author_edit_window) {
+        return Err(Box::from(format!(
+            "Confessions can only be changed within {} min of being posted.",
+            guild_config.author_edit_window / 60
+        )));
+    }
+    Ok(confession)
+}
+
+/// The message a confession was posted as. Confessions posted before their
+/// channel was recorded are looked for in the guild's confession channel.
+async fn posted_message(
+    http: &Http,
+    data: &Data,
+    confession: &Confession,
+) -> Result<Message, Error> {
+    let channel_id = match &confession.channel_id {
+        Some(channel_id) => channel_id.clone(),
+        None => data
+            .db
+            .get_guild(&confession.guild_id)
+            .await?
+            .and_then(|guild| guild.confession_channel_id)
+            .ok_or("No confession channel has been set")?,
+    };
+    Ok(http
+        .get_message(
+            ChannelId::new(channel_id.parse()?),
+            MessageId::new(confession.message_id.parse()?),
+        )
+        .await?)
+}
@@ -1,45 +1,230 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
-use confession_bot_rs::{DELETE_VOTE_STR, EXPOSE_VOTE_STR};
-use poise::{
-    serenity_prelude::{
-        ButtonStyle, Channel, ChannelId, CreateActionRow, CreateButton, CreateEmbed, CreateMessage,
-        ReactionType,
-    },
-    CreateReply,
+use chrono::Utc;
+use confession_bot_rs::{
+    COMPOSE_CANCEL_STR, COMPOSE_EDIT_STR, COMPOSE_MODAL_STR, COMPOSE_POST_STR,
+    CONFESSION_SOURCE_STR, DELETE_VOTE_STR, EXPOSE_VOTE_STR,
+};
+use poise::serenity_prelude::{
+    ActionRowComponent, Attachment, ButtonStyle, Channel, ChannelId, ComponentInteractionCollector,
+    CreateActionRow, CreateAllowedMentions, CreateButton, CreateEmbed, CreateEmbedFooter,
+    CreateInputText, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
+    CreateModal, EditInteractionResponse, GuildChannel, Http, InputTextStyle, Message,
+    ModalInteraction, ModalInteractionCollector, ReactionType, RoleId, UserId,
 };
 use rand::random;
 use tracing::{error, warn};
 
-use crate::db_impl::guilds;
 use crate::{
-    commands::{Context, Error},
-    db_impl::confessions::{self, insert_confession},
-    models::GuildConfig,
+    commands::{
+        attachments::{accept_attachment, attach, attachment_text, check_attachment},
+        filter::{blocked_embed, screen, Screened},
+        limits::check_post_limits,
+        moderation::{is_banned, BANNED_MESSAGE},
+        outbox::release_time,
+        reveal::deal_shares,
+        review::send_for_review,
+        Context, Data, Error,
+    },
+    db_impl::{escrow_author, AttachmentStore, ConfessionStore, Db, GuildStore},
+    models::{Confession, ConfessionDraft, GuildConfig, NewAttachment},
+    sanitise::sanitise,
 };
 
-/// Post a confession into the confession channel
-#[poise::command(slash_command, ephemeral, rename = "confess")]
+/// What authors are told while the guild has paused confessions.
+pub const BLACKOUT_MESSAGE: &str =
+    "Confessions are paused in this server for now. Please try again later.";
+/// How long the composer waits on the author before giving up.
+const COMPOSER_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+/// Discord's limit on the length of an embed's description.
+const MAX_DESCRIPTION_LENGTH: usize = 4096;
+const CONTENT_WARNING_MAX_LENGTH: u16 = 100;
+/// Leaves room in the description for a content warning and its markup.
+const CONTENT_MAX_LENGTH: u16 = 3900;
+
+/// Write a confession and preview it before it is posted
+#[poise::command(slash_command, rename = "confess")]
 pub async fn confession(
     ctx: Context<'_>,
-    #[description = "The confession text content"] content: String,
+    #[description = "An image or text file to attach"] attachment: Option<Attachment>,
+    #[description = "Ping the server's confession role (on by default)"] ping: Option<bool>,
 ) -> Result<(), Error> {
+    let app_ctx = match ctx {
+        Context::Application(app_ctx) => app_ctx,
+        Context::Prefix(_) => return Err(Box::from("`/confess` is only a slash command.")),
+    };
     let data = ctx.data();
-    let config = data.config.read().await;
     let guild_id = ctx.guild_id().ok_or("Not in a guild")?;
+    let user_id = ctx.author().id;
+    let http = ctx.http();
+    let nonce = ctx.id();
 
-    let guild = match guilds::get_guild(&config.db_url, &guild_id.to_string()).await? {
+    let guild_config = data.db.get_guild_config(&guild_id.to_string()).await?;
+    let refusal = match banned_embed(data, &guild_id.to_string(), &user_id.to_string()).await? {
+        Some(refusal) => Some(refusal),
+        None => guild_config.blackout.then(blackout_embed),
+    };
+    if let Some(refusal) = refusal {
+        app_ctx
+            .interaction
+            .create_response(
+                http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .embed(refusal),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+    // Only checked here, as downloading it could outlast the interaction. It
+    // is fetched and stripped once the author decides to post
+    if let Some(upload) = &attachment {
+        check_attachment(&guild_config.attachments, upload)?;
+    }
+
+    let mut draft = ConfessionDraft {
+        skip_role_ping: ping == Some(false),
+        ..Default::default()
+    };
+    app_ctx
+        .interaction
+        .create_response(
+            http,
+            CreateInteractionResponse::Modal(composer(nonce, &draft)),
+        )
+        .await?;
+    let modal = match await_composer(ctx, user_id, nonce).await {
+        Some(modal) => modal,
+        None => return Ok(()),
+    };
+    read_composer(&modal, &mut draft);
+    modal
+        .create_response(
+            http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .embed(preview(&draft, attachment.as_ref()))
+                    .components(preview_buttons(nonce)?),
+            ),
+        )
+        .await?;
+
+    loop {
+        let suffix = format!(":{}", nonce);
+        let press = match ComponentInteractionCollector::new(ctx.serenity_context().shard.clone())
+            .author_id(user_id)
+            .filter(move |press| press.data.custom_id.ends_with(&suffix))
+            .timeout(COMPOSER_TIMEOUT)
+            .await
+        {
+            Some(press) => press,
+            None => {
+                // Take the buttons away so it cannot be posted late. The
+                // preview may already be gone, which is fine
+                let _ = modal
+                    .edit_response(
+                        http,
+                        EditInteractionResponse::new()
+                            .content("This draft has expired. Use `/confess` to start again.")
+                            .components(vec![]),
+                    )
+                    .await;
+                return Ok(());
+            }
+        };
+
+        match press.data.custom_id.split(':').next().unwrap_or_default() {
+            COMPOSE_POST_STR => {
+                press
+                    .create_response(http, CreateInteractionResponse::Acknowledge)
+                    .await?;
+                let outcome = match post_confession(ctx, &draft, attachment.as_ref()).await {
+                    Ok(outcome) => outcome,
+                    Err(e) => {
+                        error!("Could not post confession: {}", e);
+                        CreateEmbed::default()
+                            .color(0xFF0000)
+                            .title("Error")
+                            .description(e.to_string())
+                    }
+                };
+                press
+                    .edit_response(
+                        http,
+                        EditInteractionResponse::new()
+                            .embed(outcome)
+                            .components(vec![]),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            COMPOSE_EDIT_STR => {
+                press
+                    .create_response(
+                        http,
+                        CreateInteractionResponse::Modal(composer(nonce, &draft)),
+                    )
+                    .await?;
+                // Closing the modal keeps the current draft
+                if let Some(edited) = await_composer(ctx, user_id, nonce).await {
+                    read_composer(&edited, &mut draft);
+                    edited
+                        .create_response(
+                            http,
+                            CreateInteractionResponse::UpdateMessage(
+                                CreateInteractionResponseMessage::new()
+                                    .embed(preview(&draft, attachment.as_ref()))
+                                    .components(preview_buttons(nonce)?),
+                            ),
+                        )
+                        .await?;
+                }
+            }
+            COMPOSE_CANCEL_STR => {
+                press
+                    .create_response(
+                        http,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .embed(
+                                    CreateEmbed::default()
+                                        .color(0xFFAA00)
+                                        .title("Confession Discarded")
+                                        .description("Nothing was posted."),
+                                )
+                                .components(vec![]),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Run a finished draft through the same checks as any confession, then post
+/// it or send it for review. Returns what the author should be told.
+async fn post_confession(
+    ctx: Context<'_>,
+    draft: &ConfessionDraft,
+    upload: Option<&Attachment>,
+) -> Result<CreateEmbed<'static>, Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let user_id = ctx.author().id.to_string();
+
+    let guild = match data.db.get_guild(&guild_id).await? {
         Some(guild) => guild,
         None => {
-            ctx.send(CreateReply::default().embed(
-                CreateEmbed::default()
-                    .color(0xFF0000)
-                    .title("Database Error")
-                    .description("The current Guild does not exist within the Bot's Database. Please attempt to run the command again.")
-            ))
-            .await?;
-            guilds::insert_guild(&config.db_url, &guild_id.to_string()).await?;
-            return Ok(());
+            data.db.insert_guild(&guild_id).await?;
+            return Ok(CreateEmbed::default()
+                .color(0xFF0000)
+                .title("Database Error")
+                .description("The current Guild does not exist within the Bot's Database. Please attempt to run the command again."));
         }
     };
 
@@ -54,105 +239,515 @@ pub async fn confession(
         }
     };
 
+    // The ban or blackout may have started while the draft was being written
+    if let Some(refusal) = banned_embed(data, &guild_id, &user_id).await? {
+        return Ok(refusal);
+    }
+    if guild_config.blackout {
+        return Ok(blackout_embed());
+    }
+
     let channel_id = match guild.confession_channel_id {
         Some(id) => id,
         None => {
-            ctx.send(
-                CreateReply::default().embed(
-                    CreateEmbed::default()
-                        .title("Guild Error")
-                        .description("A confession channel must be set before using `/confess`!\nPlease request a moderator to set one using the `/config` command.")
-                        .color(0xFFAA00)
-                )
-            )
-            .await?;
-            return Ok(());
+            return Ok(CreateEmbed::default()
+                .title("Guild Error")
+                .description("A confession channel must be set before using `/confess`!\nPlease request a moderator to set one using the `/config` command.")
+                .color(0xFFAA00));
         }
     };
 
-    let guild_channel = ctx
+    let guild_channel = match ctx
         .http()
         .get_channel(ChannelId::new(channel_id.parse()?))
-        .await?;
-
-    let guild_channel = match guild_channel {
+        .await?
+    {
         Channel::Guild(channel) => channel,
         _ => {
-            ctx.send(
-                CreateReply::default().embed(
-                    CreateEmbed::default()
-                        .color(0xFF0000)
-                        .title("Guild Error")
-                        .description(format!(
-                            "Channel <#{}> is not a valid guild channel.\nPlease try setting another channel!",
-                            channel_id
-                        )),
-                ),
-            )
-            .await?;
-            return Ok(());
+            return Ok(CreateEmbed::default()
+                .color(0xFF0000)
+                .title("Guild Error")
+                .description(format!(
+                "Channel <#{}> is not a valid guild channel.\nPlease try setting another channel!",
+                channel_id
+            )));
         }
     };
 
-    let count = match confessions::get_confession_count(&config.db_url, &guild_id.to_string()).await
-    {
-        Ok(count) => count,
-        Err(e) => {
-            warn!("Failed to get confession count: {}", e);
-            return Err(e);
-        }
+    let attachment = match upload {
+        Some(upload) => Some(accept_attachment(&guild_config.attachments, upload).await?),
+        None => None,
     };
 
-    let message_res = guild_channel
-        .send_message(
-            ctx.http(),
-            CreateMessage::default()
-                .embed(
-                    CreateEmbed::default()
-                        .color(random::<u16>() as u32)
-                        .title(format!("Confession #{}", count + 1))
-                        .description(content.clone()),
-                )
-                .components(&[CreateActionRow::Buttons(vec![
-                    CreateButton::new(DELETE_VOTE_STR)
-                        .emoji(ReactionType::from_str("🗑")?)
-                        .style(ButtonStyle::Danger)
-                        .label(format!("Delete (0/{})", guild_config.delete_vote_min)),
-                    CreateButton::new(EXPOSE_VOTE_STR)
-                        .emoji(ReactionType::from_str("🕵️")?)
-                        .label(format!("Expose (0/{})", guild_config.expose_vote_min)),
-                ])]),
+    // Every field is shown publicly, so each goes through the filter
+    let mut held_rules = vec![];
+    let mut screened = vec![];
+    for field in [
+        &draft.title,
+        &draft.content_warning,
+        &Some(draft.content.clone()),
+        &attachment.as_ref().and_then(attachment_text),
+    ] {
+        let field = match field {
+            Some(field) => field,
+            None => {
+                screened.push(None);
+                continue;
+            }
+        };
+        match screen(
+            data,
+            &guild_id,
+            &user_id,
+            &guild_config,
+            field,
+            CONFESSION_SOURCE_STR,
         )
-        .await;
+        .await?
+        {
+            Screened::Allowed(field) => screened.push(Some(field)),
+            Screened::Held(field, rules) => {
+                held_rules.push(rules);
+                screened.push(Some(field));
+            }
+            Screened::Blocked => return Ok(blocked_embed(CONFESSION_SOURCE_STR)),
+        }
+    }
+    let mut screened = screened.into_iter();
+    let level = guild_config.sanitise_level;
+    let draft = ConfessionDraft {
+        title: screened.next().flatten().map(|f| sanitise(&f, level)),
+        content_warning: screened.next().flatten().map(|f| sanitise(&f, level)),
+        content: screened
+            .next()
+            .flatten()
+            .map(|f| sanitise(&f, level))
+            .unwrap_or_default(),
+        // Text attachments are not shown as markdown, so are only masked
+        attachment: match (attachment, screened.next().flatten()) {
+            (Some(attachment), Some(text)) => Some(NewAttachment {
+                data: text.into_bytes(),
+                ..attachment
+            }),
+            (attachment, _) => attachment,
+        },
+        skip_role_ping: draft.skip_role_ping,
+    };
+    let filter_rules = (!held_rules.is_empty()).then(|| held_rules.join(", "));
+    if let Some(refusal) = too_long_embed(&draft) {
+        return Ok(refusal);
+    }
 
-    match message_res {
-        Ok(message) => {
-            if let Err(e) = insert_confession(
-                &config.db_url,
-                &message.id.to_string(),
-                &ctx.author().id.to_string(),
-                &guild_id.to_string(),
-                &content,
+    if let Some(refusal) = check_post_limits(
+        data,
+        &guild_id,
+        &user_id,
+        &guild_config,
+        CONFESSION_SOURCE_STR,
+    )
+    .await?
+    {
+        return Ok(refusal);
+    }
+
+    // Held for review when the guild has a review channel, or queued when the
+    // guild delays posts. Otherwise the confession is stored first so that it
+    // is numbered before it is posted
+    let review_channel = match &guild_config.review_channel {
+        Some(id) => Some(ChannelId::new(id.parse()?)),
+        None => None,
+    };
+    let release_at = match review_channel {
+        Some(_) => None,
+        None => release_time(&guild_config.post_delay, Utc::now().naive_utc()),
+    };
+    let (confession, escrow) = {
+        let config = data.config.read().await;
+        let escrow = escrow_author(
+            &config.escrow_key,
+            &guild_config,
+            &guild_id,
+            &user_id,
+            review_channel.is_some(),
+        )?;
+        match data
+            .db
+            .insert_confession(
+                &config.author_keys,
+                &escrow,
+                &user_id,
+                &guild_id,
+                &draft,
+                review_channel.is_some(),
+                release_at,
             )
             .await
-            {
-                // TODO: Delete the confession as we are unable to moderate and accept votes
-                // if it is not within the DB
+        {
+            Ok(confession) => (confession, escrow),
+            Err(e) => {
                 error!("{}", e);
                 return Err(Box::from("Could not insert Confession into DB".to_owned()));
             }
+        }
+    };
 
-            ctx.reply(format!("Posted confession here: {}", message.link()))
-                .await?;
+    if let Some(review_channel) = review_channel {
+        if let Err(e) = send_for_review(
+            ctx.http(),
+            review_channel,
+            &confession,
+            draft.attachment.as_ref(),
+            filter_rules,
+        )
+        .await
+        {
+            error!("Could not send confession for review: {}", e);
+            if let Err(e) = data.db.delete_confession(confession.id).await {
+                warn!("Could not remove unsent confession: {}", e);
+            }
+            return Err(Box::from(format!(
+                "Could not send confession for review. Reason: {}",
+                e
+            )));
+        }
+        // Only hashes of the shares were stored, so this is the one chance to
+        // hand them to the moderators. Confessions which were taken down again
+        // never get this far
+        deal_shares(ctx.http(), &guild_id, &confession, &escrow.shares).await;
+        return Ok(CreateEmbed::default()
+            .color(0xFFAA00)
+            .title("Sent for Review")
+            .description("Your confession has been sent to the moderators for review. I will send you a DM once it has been reviewed."));
+    }
+
+    if let Some(release_at) = release_at {
+        deal_shares(ctx.http(), &guild_id, &confession, &escrow.shares).await;
+        return Ok(CreateEmbed::default()
+            .color(0x00FF00)
+            .title("Confession Queued")
+            .description(format!(
+                "Your confession will be posted <t:{}:R>, so the time it appears does not give you away.",
+                release_at.and_utc().timestamp()
+            )));
+    }
+
+    match publish_confession(
+        ctx.http(),
+        &data.db,
+        &guild_channel,
+        &guild_config,
+        &confession,
+    )
+    .await
+    {
+        Ok(message) => {
+            deal_shares(ctx.http(), &guild_id, &confession, &escrow.shares).await;
+            Ok(CreateEmbed::default()
+                .color(0x00FF00)
+                .title("Confession Posted")
+                .description(format!("Posted confession here: {}", message.link())))
         }
         Err(e) => {
             error!("Could not post confession: {}", e);
-            return Err(Box::from(format!(
+            if let Err(e) = data.db.delete_confession(confession.id).await {
+                warn!("Could not remove unposted confession: {}", e);
+            }
+            Err(Box::from(format!(
                 "Could not send confession. Reason: {}",
                 e.to_string()
-            )));
+            )))
+        }
+    }
+}
+
+async fn banned_embed(
+    data: &Data,
+    guild_id: &String,
+    user_id: &String,
+) -> Result<Option<CreateEmbed<'static>>, Error> {
+    let config = data.config.read().await;
+    if !is_banned(&data.db, &config.author_keys, guild_id, user_id).await? {
+        return Ok(None);
+    }
+    Ok(Some(
+        CreateEmbed::default()
+            .color(0xFF0000)
+            .title("Cannot Confess")
+            .description(BANNED_MESSAGE),
+    ))
+}
+
+fn blackout_embed() -> CreateEmbed<'static> {
+    CreateEmbed::default()
+        .color(0xFF0000)
+        .title("Cannot Confess")
+        .description(BLACKOUT_MESSAGE)
+}
+
+/// The modal a confession is written in, filled in with the draft so far.
+pub fn composer(nonce: u64, draft: &ConfessionDraft) -> CreateModal<'static> {
+    let mut content = CreateInputText::new(InputTextStyle::Paragraph, "Confession", "content")
+        .max_length(CONTENT_MAX_LENGTH);
+    if !draft.content.is_empty() {
+        content = content.value(draft.content.clone());
+    }
+    let mut title = CreateInputText::new(InputTextStyle::Short, "Title", "title")
+        .placeholder("Optional")
+        .required(false)
+        .max_length(100);
+    if let Some(value) = &draft.title {
+        title = title.value(value.clone());
+    }
+    let mut content_warning =
+        CreateInputText::new(InputTextStyle::Short, "Content warning", "content_warning")
+            .placeholder("Optional. Hides the confession behind a spoiler")
+            .required(false)
+            .max_length(CONTENT_WARNING_MAX_LENGTH);
+    if let Some(value) = &draft.content_warning {
+        content_warning = content_warning.value(value.clone());
+    }
+
+    CreateModal::new(format!("{}:{}", COMPOSE_MODAL_STR, nonce), "Confess").components(vec![
+        CreateActionRow::InputText(content),
+        CreateActionRow::InputText(title),
+        CreateActionRow::InputText(content_warning),
+    ])
+}
+
+pub async fn await_composer(
+    ctx: Context<'_>,
+    user_id: UserId,
+    nonce: u64,
+) -> Option<ModalInteraction> {
+    let custom_id = format!("{}:{}", COMPOSE_MODAL_STR, nonce);
+    ModalInteractionCollector::new(ctx.serenity_context().shard.clone())
+        .author_id(user_id)
+        .filter(move |modal| modal.data.custom_id == custom_id.as_str())
+        .timeout(COMPOSER_TIMEOUT)
+        .await
+}
+
+pub fn read_composer(modal: &ModalInteraction, draft: &mut ConfessionDraft) {
+    for component in modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+    {
+        let input = match component {
+            ActionRowComponent::InputText(input) => input,
+            _ => continue,
+        };
+        let value = input
+            .value
+            .as_ref()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        match &*input.custom_id {
+            "content" => draft.content = value.unwrap_or_default(),
+            "title" => draft.title = value,
+            "content_warning" => draft.content_warning = value,
+            _ => {}
+        }
+    }
+}
+
+fn preview(draft: &ConfessionDraft, upload: Option<&Attachment>) -> CreateEmbed<'static> {
+    let mut embed = confession_embed(
+        "Preview".to_string(),
+        draft.title.as_ref(),
+        draft.content_warning.as_ref(),
+        &draft.content,
+    )
+    .color(0xFFAA00)
+    .footer(CreateEmbedFooter::new(
+        "Nothing has been posted yet. The server's filter is applied when you post.",
+    ));
+    if let Some(upload) = upload {
+        embed = embed.field(
+            "Attachment",
+            format!(
+                "{} ({} KB)\nIts name and metadata are removed when it is posted.",
+                upload.filename,
+                upload.size.div_ceil(1024)
+            ),
+            false,
+        );
+    }
+    if draft.skip_role_ping {
+        embed = embed.field(
+            "Role Ping",
+            "The server's confession role will not be pinged.",
+            false,
+        );
+    }
+    embed
+}
+
+fn preview_buttons(nonce: u64) -> Result<Vec<CreateActionRow<'static>>, Error> {
+    Ok(vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}:{}", COMPOSE_POST_STR, nonce))
+            .emoji(ReactionType::from_str("📨")?)
+            .style(ButtonStyle::Success)
+            .label("Post"),
+        CreateButton::new(format!("{}:{}", COMPOSE_EDIT_STR, nonce))
+            .emoji(ReactionType::from_str("✏️")?)
+            .style(ButtonStyle::Secondary)
+            .label("Edit"),
+        CreateButton::new(format!("{}:{}", COMPOSE_CANCEL_STR, nonce))
+            .style(ButtonStyle::Danger)
+            .label("Cancel"),
+    ])])
+}
+
+/// How a confession is shown, e.g. `Confession #12: Title`. A content warning
+/// hides the content behind a spoiler.
+pub fn confession_embed(
+    heading: String,
+    title: Option<&String>,
+    content_warning: Option<&String>,
+    content: &str,
+) -> CreateEmbed<'static> {
+    let title = match title {
+        Some(title) => format!("{}: {}", heading, title),
+        None => heading,
+    };
+    CreateEmbed::default()
+        .title(title)
+        .description(confession_description(content_warning, content))
+}
+
+fn confession_description(content_warning: Option<&String>, content: &str) -> String {
+    match content_warning {
+        Some(content_warning) => format!("**CW: {}**\n||{}||", content_warning, content),
+        None => content.to_string(),
+    }
+}
+
+/// Refuse a draft that would not fit in an embed once shown. The filter and
+/// sanitising can both lengthen it, so this is checked on the final text.
+pub fn too_long_embed(draft: &ConfessionDraft) -> Option<CreateEmbed<'static>> {
+    let length = confession_description(draft.content_warning.as_ref(), &draft.content)
+        .chars()
+        .count();
+    if length <= MAX_DESCRIPTION_LENGTH {
+        return None;
+    }
+    Some(
+        CreateEmbed::default()
+            .color(0xFF0000)
+            .title("Confession Too Long")
+            .description(format!(
+                "Once formatted, your confession is {} characters long, but Discord only shows {}. Please shorten it and try again.",
+                length, MAX_DESCRIPTION_LENGTH
+            )),
+    )
+}
+
+/// The role to ping for a new confession, if the guild has one and the author
+/// allows it. Whether the guild's last ping was long enough ago is left to
+/// [`GuildStore::claim_role_ping`].
+fn role_to_ping(
+    guild_config: &GuildConfig,
+    confession: &Confession,
+) -> Result<Option<RoleId>, Error> {
+    match &guild_config.role_ping {
+        Some(role_id) if !confession.skip_role_ping => Ok(Some(RoleId::new(role_id.parse()?))),
+        _ => Ok(None),
+    }
+}
+
+/// Post a numbered confession with its vote buttons and link it to the
+/// message. If it cannot be linked, the message is taken down again.
+pub async fn publish_confession(
+    http: &Http,
+    db: &Db,
+    channel: &GuildChannel,
+    guild_config: &GuildConfig,
+    confession: &Confession,
+) -> Result<Message, Error> {
+    let number = confession
+        .number
+        .ok_or("Confession was not given a number")?;
+
+    let attachment: Option<NewAttachment> = db
+        .get_confession_attachment(confession.id)
+        .await?
+        .map(Into::into);
+
+    // Only the configured role may be mentioned, never anything in the content
+    let mut builder = CreateMessage::default().allowed_mentions(CreateAllowedMentions::new());
+    // Claimed before sending, so two confessions posted at once cannot both
+    // ping
+    let mut claim = None;
+    if let Some(role_id) = role_to_ping(guild_config, confession)? {
+        claim = db
+            .claim_role_ping(&confession.guild_id, guild_config.role_ping_interval)
+            .await?;
+        if claim.is_some() {
+            builder = builder
+                .content(format!("<@&{}>", role_id))
+                .allowed_mentions(CreateAllowedMentions::new().roles(vec![role_id]));
         }
     }
 
-    Ok(())
+    let sent = channel
+        .send_message(
+            http,
+            attach(
+                builder,
+                confession_embed(
+                    format!("Confession #{}", number),
+                    confession.title.as_ref(),
+                    confession.content_warning.as_ref(),
+                    &confession.content,
+                )
+                .color(random::<u16>() as u32),
+                attachment.as_ref(),
+            )
+            .components(&[CreateActionRow::Buttons(vec![
+                CreateButton::new(DELETE_VOTE_STR)
+                    .emoji(ReactionType::from_str("🗑")?)
+                    .style(ButtonStyle::Danger)
+                    .label(format!("Delete (0/{})", guild_config.delete_vote_min)),
+                CreateButton::new(EXPOSE_VOTE_STR)
+                    .emoji(ReactionType::from_str("🕵️")?)
+                    .label(format!("Expose (0/{})", guild_config.expose_vote_min)),
+            ])]),
+        )
+        .await;
+    let message = match sent {
+        Ok(message) => message,
+        Err(e) => {
+            // The role was never pinged, so leave the window open for the
+            // next confession
+            if let Some(claim) = &claim {
+                if let Err(e) = db.release_role_ping(&confession.guild_id, claim).await {
+                    warn!("Could not release the role ping: {}", e);
+                }
+            }
+            return Err(Box::from(e));
+        }
+    };
+
+    if let Err(e) = db
+        .set_confession_message(
+            confession.id,
+            &message.channel_id.to_string(),
+            &message.id.to_string(),
+        )
+        .await
+    {
+        // We are unable to moderate and accept votes if the message is
+        // not linked within the DB, so take it down again
+        error!("{}", e);
+        if let Err(e) = http
+            .delete_message(message.channel_id, message.id, None)
+            .await
+        {
+            warn!("Could not remove unlinked confession: {}", e);
+        }
+        return Err(Box::from("Could not insert Confession into DB".to_owned()));
+    }
+    Ok(message)
 }
@@ -1,14 +1,72 @@
+use chrono_tz::Tz;
 use poise::{
-    serenity_prelude::{ChannelId, CreateEmbed, RoleId},
-    CreateReply,
+    serenity_prelude::{ChannelId, CreateEmbed, RoleId, UserId},
+    ChoiceParameter, CreateReply,
 };
 
 use crate::{
     commands::{Context, Error},
-    db_impl::guilds,
-    models::GuildConfig,
+    db_impl::GuildStore,
+    models::{AttachmentKind, DelayMode, GuildConfig, SanitiseLevel},
 };
 
+#[derive(Debug, ChoiceParameter, Copy, Clone)]
+enum SanitiseLevelChoice {
+    #[name = "Basic (mentions only)"]
+    BASIC,
+    #[name = "Standard (also unwrap masked links)"]
+    STANDARD,
+    #[name = "Strict (also strip headings)"]
+    STRICT,
+}
+
+impl From<SanitiseLevelChoice> for SanitiseLevel {
+    fn from(value: SanitiseLevelChoice) -> Self {
+        match value {
+            SanitiseLevelChoice::BASIC => SanitiseLevel::BASIC,
+            SanitiseLevelChoice::STANDARD => SanitiseLevel::STANDARD,
+            SanitiseLevelChoice::STRICT => SanitiseLevel::STRICT,
+        }
+    }
+}
+
+#[derive(Debug, ChoiceParameter, Copy, Clone)]
+enum DelayModeChoice {
+    #[name = "Off"]
+    OFF,
+    #[name = "Random delay within the window"]
+    RANDOM,
+    #[name = "Batches at the end of each window"]
+    BATCH,
+}
+
+impl From<DelayModeChoice> for DelayMode {
+    fn from(value: DelayModeChoice) -> Self {
+        match value {
+            DelayModeChoice::OFF => DelayMode::OFF,
+            DelayModeChoice::RANDOM => DelayMode::RANDOM,
+            DelayModeChoice::BATCH => DelayMode::BATCH,
+        }
+    }
+}
+
+#[derive(Debug, ChoiceParameter, Copy, Clone)]
+enum AttachmentKindChoice {
+    #[name = "Images"]
+    IMAGE,
+    #[name = "Text files"]
+    TEXT,
+}
+
+impl From<AttachmentKindChoice> for AttachmentKind {
+    fn from(value: AttachmentKindChoice) -> Self {
+        match value {
+            AttachmentKindChoice::IMAGE => AttachmentKind::IMAGE,
+            AttachmentKindChoice::TEXT => AttachmentKind::TEXT,
+        }
+    }
+}
+
 /// Define a Guild-specific configuration.
 #[poise::command(
     slash_command,
@@ -28,11 +86,47 @@ pub async fn config_guild(
     #[description = "The minimum role required for the user's vote to count towards exposing the author of the confession"]
     expose_vote_role: Option<RoleId>,
     #[description = "Role to ping when a new Confession is made"] role_ping: Option<RoleId>,
+    #[description = "Minutes which must pass between role pings (0 to ping for every confession)"]
+    #[min = 0]
+    role_ping_interval: Option<u32>,
+    #[description = "Number of reveal moderators who must approve exposing an author (0 to disable)"]
+    #[min = 0]
+    reveal_threshold: Option<u8>,
+    #[description = "Add or remove a moderator who can approve exposing an author"]
+    reveal_moderator: Option<UserId>,
+    #[description = "Channel where moderators approve confessions before they are posted (pick it again to disable)"]
+    review_channel: Option<ChannelId>,
+    #[description = "How much markdown to strip from confessions and replies"]
+    sanitise_level: Option<SanitiseLevelChoice>,
+    #[description = "Seconds an author must wait between confessions, and between replies (0 to disable)"]
+    #[min = 0]
+    cooldown: Option<u32>,
+    #[description = "Confessions, and replies, an author may post within the rate limit window (0 to disable)"]
+    #[min = 0]
+    rate_limit_posts: Option<u32>,
+    #[description = "Minutes over which the rate limit is counted"]
+    #[min = 1]
+    rate_limit_window: Option<u32>,
+    #[description = "Delay confessions so the time they appear does not give away the author"]
+    post_delay: Option<DelayModeChoice>,
+    #[description = "Minutes of the longest random delay, or between batches"]
+    #[min = 1]
+    post_delay_window: Option<u32>,
+    #[description = "Allow or disallow a kind of attachment on confessions and replies"]
+    attachment_type: Option<AttachmentKindChoice>,
+    #[description = "Largest attachment allowed, in MB"]
+    #[min = 1]
+    #[max = 25]
+    attachment_max_size: Option<u32>,
+    #[description = "Minutes after posting in which authors may delete or edit their confession (0 to disable)"]
+    #[min = 0]
+    author_edit_window: Option<u32>,
+    #[description = "Timezone times are read in, such as Europe/London"] timezone: Option<String>,
+    #[description = "Refuse new confessions until turned off"] blackout: Option<bool>,
 ) -> Result<(), Error> {
     let data = ctx.data();
-    let config = data.config.read().await;
     if let Some(guild_id) = ctx.guild_id() {
-        if let Some(guild) = guilds::get_guild(&config.db_url, &guild_id.to_string()).await? {
+        if let Some(guild) = data.db.get_guild(&guild_id.to_string()).await? {
             let mut changelog = String::new();
             let mut guild_config: GuildConfig = serde_json::from_str(guild.config.as_str())?;
             if let Some(delete_vote_min_res) = delete_vote_min {
@@ -89,28 +183,240 @@ pub async fn config_guild(
                 );
                 guild_config.role_ping = Some(role_ping_res.to_string());
             }
+            if let Some(role_ping_interval_res) = role_ping_interval {
+                changelog.push_str(
+                    format!(
+                        "Role Ping Interval: {} min :arrow_right: {} min\n",
+                        guild_config.role_ping_interval / 60,
+                        role_ping_interval_res
+                    )
+                    .as_str(),
+                );
+                guild_config.role_ping_interval = role_ping_interval_res as i64 * 60;
+            }
+            if let Some(reveal_threshold_res) = reveal_threshold {
+                let reveal_threshold_res = match reveal_threshold_res {
+                    0 => None,
+                    threshold => Some(threshold),
+                };
+                changelog.push_str(
+                    format!(
+                        "Reveal Threshold: {} :arrow_right: {}\n",
+                        guild_config
+                            .reveal_threshold
+                            .map_or("Unset".to_string(), |t| t.to_string()),
+                        reveal_threshold_res.map_or("Unset".to_string(), |t| t.to_string())
+                    )
+                    .as_str(),
+                );
+                guild_config.reveal_threshold = reveal_threshold_res;
+            }
+            if let Some(reveal_moderator_res) = reveal_moderator {
+                let moderator_id = reveal_moderator_res.to_string();
+                if let Some(position) = guild_config
+                    .reveal_moderators
+                    .iter()
+                    .position(|m| *m == moderator_id)
+                {
+                    guild_config.reveal_moderators.remove(position);
+                    changelog.push_str(
+                        format!("Reveal Moderators: Removed <@{}>\n", moderator_id).as_str(),
+                    );
+                } else {
+                    guild_config.reveal_moderators.push(moderator_id.clone());
+                    changelog.push_str(
+                        format!("Reveal Moderators: Added <@{}>\n", moderator_id).as_str(),
+                    );
+                }
+            }
+            if let Some(review_channel_res) = review_channel {
+                let review_channel_res = review_channel_res.to_string();
+                let previous = guild_config.review_channel.take();
+                changelog.push_str(
+                    format!(
+                        "Review Channel: {} :arrow_right: {}\n",
+                        previous
+                            .as_ref()
+                            .map_or("Unset".to_string(), |c| format!("<#{}>", c)),
+                        if previous.as_ref() == Some(&review_channel_res) {
+                            "Unset".to_string()
+                        } else {
+                            format!("<#{}>", review_channel_res)
+                        }
+                    )
+                    .as_str(),
+                );
+                if previous.as_ref() != Some(&review_channel_res) {
+                    guild_config.review_channel = Some(review_channel_res);
+                }
+            }
+            if let Some(sanitise_level_res) = sanitise_level {
+                let sanitise_level_res: SanitiseLevel = sanitise_level_res.into();
+                changelog.push_str(
+                    format!(
+                        "Sanitise Level: {:?} :arrow_right: {:?}\n",
+                        guild_config.sanitise_level, sanitise_level_res
+                    )
+                    .as_str(),
+                );
+                guild_config.sanitise_level = sanitise_level_res;
+            }
+            if let Some(cooldown_res) = cooldown {
+                changelog.push_str(
+                    format!(
+                        "Cooldown: {}s :arrow_right: {}s\n",
+                        guild_config.post_limits.cooldown, cooldown_res
+                    )
+                    .as_str(),
+                );
+                guild_config.post_limits.cooldown = cooldown_res as i64;
+            }
+            if let Some(rate_limit_posts_res) = rate_limit_posts {
+                changelog.push_str(
+                    format!(
+                        "Rate Limit Posts: {} :arrow_right: {}\n",
+                        guild_config.post_limits.max_posts, rate_limit_posts_res
+                    )
+                    .as_str(),
+                );
+                guild_config.post_limits.max_posts = rate_limit_posts_res as i64;
+            }
+            if let Some(rate_limit_window_res) = rate_limit_window {
+                changelog.push_str(
+                    format!(
+                        "Rate Limit Window: {} min :arrow_right: {} min\n",
+                        guild_config.post_limits.window / 60,
+                        rate_limit_window_res
+                    )
+                    .as_str(),
+                );
+                guild_config.post_limits.window = rate_limit_window_res as i64 * 60;
+            }
+            if let Some(post_delay_res) = post_delay {
+                let post_delay_res: DelayMode = post_delay_res.into();
+                changelog.push_str(
+                    format!(
+                        "Post Delay: {:?} :arrow_right: {:?}\n",
+                        guild_config.post_delay.mode, post_delay_res
+                    )
+                    .as_str(),
+                );
+                guild_config.post_delay.mode = post_delay_res;
+            }
+            if let Some(post_delay_window_res) = post_delay_window {
+                changelog.push_str(
+                    format!(
+                        "Post Delay Window: {} min :arrow_right: {} min\n",
+                        guild_config.post_delay.window / 60,
+                        post_delay_window_res
+                    )
+                    .as_str(),
+                );
+                guild_config.post_delay.window = post_delay_window_res as i64 * 60;
+            }
+            if let Some(attachment_type_res) = attachment_type {
+                let kind: AttachmentKind = attachment_type_res.into();
+                if let Some(position) = guild_config
+                    .attachments
+                    .kinds
+                    .iter()
+                    .position(|k| *k == kind)
+                {
+                    guild_config.attachments.kinds.remove(position);
+                    changelog.push_str(format!("Attachments: Disallowed {:?}\n", kind).as_str());
+                } else {
+                    guild_config.attachments.kinds.push(kind);
+                    changelog.push_str(format!("Attachments: Allowed {:?}\n", kind).as_str());
+                }
+            }
+            if let Some(attachment_max_size_res) = attachment_max_size {
+                changelog.push_str(
+                    format!(
+                        "Attachment Max Size: {} MB :arrow_right: {} MB\n",
+                        guild_config.attachments.max_size / (1024 * 1024),
+                        attachment_max_size_res
+                    )
+                    .as_str(),
+                );
+                guild_config.attachments.max_size = attachment_max_size_res * 1024 * 1024;
+            }
+            if let Some(author_edit_window_res) = author_edit_window {
+                changelog.push_str(
+                    format!(
+                        "Author Edit Window: {} min :arrow_right: {} min\n",
+                        guild_config.author_edit_window / 60,
+                        author_edit_window_res
+                    )
+                    .as_str(),
+                );
+                guild_config.author_edit_window = author_edit_window_res as i64 * 60;
+            }
+            if let Some(timezone_res) = timezone {
+                let timezone_res: Tz = timezone_res.trim().parse().map_err(|_| {
+                    format!(
+                        "`{}` is not a known timezone. Use a name such as `Europe/London`.",
+                        timezone_res
+                    )
+                })?;
+                changelog.push_str(
+                    format!(
+                        "Timezone: {} :arrow_right: {}\n",
+                        guild_config.timezone().name(),
+                        timezone_res.name()
+                    )
+                    .as_str(),
+                );
+                guild_config.timezone = Some(timezone_res.name().to_string());
+            }
+            if let Some(blackout_res) = blackout {
+                changelog.push_str(
+                    format!(
+                        "Blackout: {} :arrow_right: {}\n",
+                        guild_config.blackout, blackout_res
+                    )
+                    .as_str(),
+                );
+                guild_config.blackout = blackout_res;
+            }
+            if guild_config.post_delay.mode != DelayMode::OFF && guild_config.post_delay.window == 0
+            {
+                changelog.push_str(
+                    "\n**Warning:** The post delay has no window, so confessions will be posted straight away.\n",
+                );
+            }
+            if guild_config.post_limits.max_posts > 0 && guild_config.post_limits.window == 0 {
+                changelog.push_str(
+                    "\n**Warning:** The rate limit has no window, so it will not be applied.\n",
+                );
+            }
+            // Authors would otherwise be exposed without any approval
+            if guild_config.reveal_threshold.is_some() && guild_config.reveal_quorum().is_none() {
+                return Err(Box::from(
+                    "There are fewer reveal moderators than the reveal threshold. Add moderators or lower the threshold first.",
+                ));
+            }
 
             if let Some(guild_id) = ctx.guild_id() {
-                guilds::update_guild(
-                    &config.db_url,
-                    &guild_id.to_string(),
-                    if let Some(channel_id_res) = channel_id {
-                        // TODO: Check if channel is text based
-                        changelog.push_str(
-                            format!(
-                                "Confession Channel ID: {} :arrow_right: {}\n",
-                                guild.confession_channel_id.unwrap_or("Unset".to_owned()),
-                                channel_id_res
-                            )
-                            .as_str(),
-                        );
-                        Some(channel_id_res.to_string())
-                    } else {
-                        guild.confession_channel_id
-                    },
-                    guild_config,
-                )
-                .await?;
+                data.db
+                    .update_guild(
+                        &guild_id.to_string(),
+                        if let Some(channel_id_res) = channel_id {
+                            // TODO: Check if channel is text based
+                            changelog.push_str(
+                                format!(
+                                    "Confession Channel ID: {} :arrow_right: {}\n",
+                                    guild.confession_channel_id.unwrap_or("Unset".to_owned()),
+                                    channel_id_res
+                                )
+                                .as_str(),
+                            );
+                            Some(channel_id_res.to_string())
+                        } else {
+                            guild.confession_channel_id
+                        },
+                        guild_config,
+                    )
+                    .await?;
             }
             ctx.send(
                 CreateReply::default().embed(
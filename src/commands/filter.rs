@@ -0,0 +1,332 @@
+use confession_bot_rs::{CONFESSION_SOURCE_STR, REPLY_SOURCE_STR};
+use poise::{serenity_prelude::CreateEmbed, ChoiceParameter, CreateReply};
+
+use crate::{
+    commands::{Context, Data, Error},
+    db_impl::{AuthorStore, FilterStore, GuildStore},
+    filter,
+    models::{FilterAction, FilterKind, FilterRule, GuildConfig, NewFilterHit},
+};
+
+#[derive(Debug, ChoiceParameter, Copy, Clone)]
+enum FilterKindChoice {
+    #[name = "Word or phrase"]
+    WORD,
+    #[name = "Regex"]
+    REGEX,
+    #[name = "Server invites"]
+    INVITE,
+    #[name = "Links"]
+    LINK,
+}
+
+impl From<FilterKindChoice> for FilterKind {
+    fn from(value: FilterKindChoice) -> Self {
+        match value {
+            FilterKindChoice::WORD => FilterKind::WORD,
+            FilterKindChoice::REGEX => FilterKind::REGEX,
+            FilterKindChoice::INVITE => FilterKind::INVITE,
+            FilterKindChoice::LINK => FilterKind::LINK,
+        }
+    }
+}
+
+#[derive(Debug, ChoiceParameter, Copy, Clone)]
+enum FilterActionChoice {
+    #[name = "Mask the match"]
+    MASK,
+    #[name = "Hold for review"]
+    REVIEW,
+    #[name = "Reject"]
+    REJECT,
+}
+
+impl From<FilterActionChoice> for FilterAction {
+    fn from(value: FilterActionChoice) -> Self {
+        match value {
+            FilterActionChoice::MASK => FilterAction::MASK,
+            FilterActionChoice::REVIEW => FilterAction::REVIEW,
+            FilterActionChoice::REJECT => FilterAction::REJECT,
+        }
+    }
+}
+
+/// The outcome of running content through the guild's filter.
+pub enum Screened {
+    /// Post the content, which may have been masked.
+    Allowed(String),
+    /// Hold the confession for review, with the rules which caught it.
+    Held(String, String),
+    /// Refuse the content. See [`blocked_embed`].
+    Blocked,
+}
+
+/// Run content through the guild's filter rules, recording any hits.
+///
+/// Replies cannot be held for review, so review rules reject them instead.
+/// The same goes for confessions in guilds without a review channel.
+pub async fn screen(
+    data: &Data,
+    guild_id: &String,
+    user_id: &String,
+    guild_config: &GuildConfig,
+    content: &String,
+    source: &str,
+) -> Result<Screened, Error> {
+    let verdict = filter::apply(&guild_config.filters, content);
+    let action = match verdict.action {
+        Some(action) => action,
+        None => return Ok(Screened::Allowed(verdict.content)),
+    };
+
+    let config = data.config.read().await;
+    let author_id = data
+        .db
+        .insert_author(&config.author_keys, guild_id, user_id)
+        .await?;
+    data.db
+        .insert_filter_hits(
+            verdict
+                .matches
+                .iter()
+                .map(|m| NewFilterHit {
+                    guild_id: guild_id.clone(),
+                    rule_id: m.rule.id,
+                    author_id,
+                    source: source.to_string(),
+                    action: m.rule.action.into(),
+                    matched: m.matched.clone(),
+                })
+                .collect(),
+        )
+        .await?;
+
+    let can_hold = source == CONFESSION_SOURCE_STR && guild_config.review_channel.is_some();
+    match action {
+        FilterAction::MASK => Ok(Screened::Allowed(verdict.content)),
+        FilterAction::REVIEW if can_hold => Ok(Screened::Held(
+            verdict.content,
+            verdict
+                .matches
+                .iter()
+                .filter(|m| m.rule.action == FilterAction::REVIEW)
+                .map(|m| format!("#{}", m.rule.id))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )),
+        FilterAction::REVIEW | FilterAction::REJECT => Ok(Screened::Blocked),
+    }
+}
+
+/// What the author is told when their content is blocked.
+pub fn blocked_embed(source: &str) -> CreateEmbed<'static> {
+    CreateEmbed::default()
+        .color(0xFF0000)
+        .title("Content Blocked")
+        .description(format!(
+            "Your {} was blocked by this server's content filter.",
+            if source == REPLY_SOURCE_STR {
+                "reply"
+            } else {
+                "confession"
+            }
+        ))
+}
+
+/// Manage the content filter for confessions and replies.
+#[poise::command(
+    slash_command,
+    ephemeral,
+    subcommands("add", "remove", "list", "hits"),
+    subcommand_required,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn filter(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Add a filter rule.
+#[poise::command(slash_command, ephemeral, required_permissions = "MANAGE_GUILD")]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "What the rule matches"] kind: FilterKindChoice,
+    #[description = "What happens to matching content"] action: FilterActionChoice,
+    #[description = "The word, phrase or regex to match. Not needed for invites and links"]
+    pattern: Option<String>,
+) -> Result<(), Error> {
+    let kind: FilterKind = kind.into();
+    let pattern = pattern.unwrap_or_default();
+    if matches!(kind, FilterKind::WORD | FilterKind::REGEX) && pattern.trim().is_empty() {
+        return Err(Box::from("This kind of rule needs a pattern."));
+    }
+    if let Err(e) = filter::compile(kind, &pattern) {
+        return Err(Box::from(format!("Invalid pattern: {}", e)));
+    }
+
+    let (confession_channel_id, mut guild_config) = guild_config(ctx).await?;
+    guild_config.last_filter_id += 1;
+    let rule = FilterRule {
+        id: guild_config.last_filter_id,
+        kind,
+        pattern,
+        action: action.into(),
+    };
+    let description = describe(&rule);
+    guild_config.filters.push(rule);
+    ctx.data()
+        .db
+        .update_guild(
+            &ctx.guild_id().ok_or("Not in a guild")?.to_string(),
+            confession_channel_id,
+            guild_config,
+        )
+        .await?;
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Filter Rule Added")
+                .color(0x00FF00)
+                .description(description),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Remove a filter rule.
+#[poise::command(slash_command, ephemeral, required_permissions = "MANAGE_GUILD")]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "The ID of the rule, as shown by `/filter list`"] id: i32,
+) -> Result<(), Error> {
+    let (confession_channel_id, mut guild_config) = guild_config(ctx).await?;
+    let position = guild_config
+        .filters
+        .iter()
+        .position(|r| r.id == id)
+        .ok_or(format!("There is no filter rule with ID `{}`.", id))?;
+    let rule = guild_config.filters.remove(position);
+    ctx.data()
+        .db
+        .update_guild(
+            &ctx.guild_id().ok_or("Not in a guild")?.to_string(),
+            confession_channel_id,
+            guild_config,
+        )
+        .await?;
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Filter Rule Removed")
+                .color(0x00FF00)
+                .description(describe(&rule)),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// List the guild's filter rules.
+#[poise::command(slash_command, ephemeral, required_permissions = "MANAGE_GUILD")]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let (_, guild_config) = guild_config(ctx).await?;
+    let description = if guild_config.filters.is_empty() {
+        "No filter rules have been added.".to_string()
+    } else {
+        guild_config
+            .filters
+            .iter()
+            .map(describe)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Filter Rules")
+                .color(0x11FF00)
+                .description(description),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Show recent content caught by the filter. Authors are shown by an
+/// anonymous number, which stays the same across hits.
+#[poise::command(slash_command, ephemeral, required_permissions = "MANAGE_GUILD")]
+async fn hits(
+    ctx: Context<'_>,
+    #[description = "How many hits to show"]
+    #[min = 1]
+    #[max = 25]
+    limit: Option<u8>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let hits = ctx
+        .data()
+        .db
+        .get_filter_hits(&guild_id, limit.unwrap_or(10) as i64)
+        .await?;
+    let description = if hits.is_empty() {
+        "Nothing has been caught by the filter.".to_string()
+    } else {
+        hits.iter()
+            .map(|hit| {
+                format!(
+                    "<t:{}:R> Rule #{} ({}) caught a {} by author #{}: `{}`",
+                    hit.timestamp.and_utc().timestamp(),
+                    hit.rule_id,
+                    hit.action,
+                    hit.source,
+                    hit.author_id,
+                    hit.matched.replace('`', "'")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Filter Hits")
+                .color(0x11FF00)
+                .description(description),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn guild_config(ctx: Context<'_>) -> Result<(Option<String>, GuildConfig), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?;
+    let guild = ctx
+        .data()
+        .db
+        .get_guild(&guild_id.to_string())
+        .await?
+        .ok_or("Could not find guild within the database.")?;
+    Ok((
+        guild.confession_channel_id,
+        serde_json::from_str(guild.config.as_str())?,
+    ))
+}
+
+fn describe(rule: &FilterRule) -> String {
+    let kind = match rule.kind {
+        FilterKind::WORD => format!("Word `{}`", rule.pattern),
+        FilterKind::REGEX => format!("Regex `{}`", rule.pattern),
+        FilterKind::INVITE => "Server invites".to_string(),
+        FilterKind::LINK => "Links".to_string(),
+    };
+    let action = match rule.action {
+        FilterAction::MASK => "mask",
+        FilterAction::REVIEW => "hold for review",
+        FilterAction::REJECT => "reject",
+    };
+    format!("**#{}** {} :arrow_right: {}", rule.id, kind, action)
+}
@@ -0,0 +1,68 @@
+use chrono::Utc;
+use poise::serenity_prelude::CreateEmbed;
+
+use crate::{
+    commands::{Data, Error},
+    db_impl::{AuthorStore, CooldownStore},
+    models::{GuildConfig, PostLimits},
+};
+
+/// Count a post against the author's cooldown and rate limit. Authors are
+/// tracked by their pseudonym, so the limits never hold a user ID.
+/// # Returns
+/// What to tell the author if they have to wait before posting.
+pub async fn check_post_limits(
+    data: &Data,
+    guild_id: &String,
+    user_id: &String,
+    guild_config: &GuildConfig,
+    source: &str,
+) -> Result<Option<CreateEmbed<'static>>, Error> {
+    if guild_config.post_limits == PostLimits::default() {
+        return Ok(None);
+    }
+    let config = data.config.read().await;
+    let author_id = data
+        .db
+        .insert_author(&config.author_keys, guild_id, user_id)
+        .await?;
+
+    let retry_at = match data
+        .db
+        .try_record_post(guild_id, author_id, source, guild_config.post_limits)
+        .await?
+    {
+        Some(retry_at) => retry_at,
+        None => return Ok(None),
+    };
+    let retry_at = retry_at.and_utc().timestamp().max(Utc::now().timestamp());
+    Ok(Some(
+        CreateEmbed::default()
+            .color(0xFFAA00)
+            .title("Slow Down")
+            .description(format!(
+                "You are posting too quickly. You can post again <t:{}:R>.",
+                retry_at
+            )),
+    ))
+}
+
+/// Describe a guild's limits for the config embed.
+pub fn describe_post_limits(limits: &PostLimits) -> String {
+    let mut parts = vec![];
+    if limits.cooldown > 0 {
+        parts.push(format!("{}s cooldown", limits.cooldown));
+    }
+    if limits.max_posts > 0 && limits.window > 0 {
+        parts.push(format!(
+            "{} per {} min",
+            limits.max_posts,
+            limits.window / 60
+        ));
+    }
+    if parts.is_empty() {
+        "Unset".to_owned()
+    } else {
+        parts.join(", ")
+    }
+}
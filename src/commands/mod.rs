@@ -1,38 +1,46 @@
 use std::{str::FromStr, sync::Arc};
 
-use confession_bot_rs::{VoteType, DELETE_VOTE_STR, EXPOSE_VOTE_STR};
+use confession_bot_rs::{
+    VoteType, DELETE_VOTE_STR, EXPOSE_VOTE_STR, REVEAL_APPROVE_STR, REVIEW_REASON_STR,
+};
 use poise::{
     builtins,
     serenity_prelude::{
         self as serenity, ActionRowComponent, ButtonStyle, CreateActionRow, CreateButton,
-        CreateEmbed, CreateEmbedFooter, CreateMessage, EditMessage, GuildId, ReactionType, RoleId,
-        UserId,
+        CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
+        EditMessage, GuildId, ReactionType, RoleId,
     },
     FrameworkContext, FrameworkError,
 };
-use ring::digest::SHA256;
 use serenity::FullEvent;
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
 use tracing::{error, info};
 
 use crate::{
-    client::observe,
-    db_impl::{
-        authors::get_author_hash_by_message,
-        guilds::{self, get_guild_config},
-        votes::update_vote,
-    },
+    db_impl::{AttachmentStore, ConfessionStore, Db, GuildStore, VoteOutcome, VoteStore},
     models::GuildConfig,
     Config,
 };
 
+pub mod actions;
+pub mod attachments;
+pub mod author;
 pub mod confess;
 pub mod config;
+pub mod filter;
+pub mod limits;
+pub mod moderation;
+pub mod outbox;
 pub mod reply;
+pub mod reveal;
+pub mod review;
 pub mod schedule;
 
 pub struct Data {
     pub config: RwLock<Config>,
+    pub db: Db,
+    /// Wakes the scheduler when a job is added.
+    pub schedules: Notify,
 }
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Context<'a> = poise::Context<'a, Data, Error>;
@@ -44,6 +52,18 @@ pub async fn event_handler(
     match &event {
         FullEvent::InteractionCreate { interaction } => {
             if let Some(cmp) = interaction.as_message_component() {
+                if cmp.data.custom_id == REVEAL_APPROVE_STR {
+                    return reveal::approve_reveal(framework, cmp).await;
+                }
+                if let Some((action, confession_id)) = review::parse_custom_id(&cmp.data.custom_id)
+                {
+                    return review::review_confession(framework, cmp, action, confession_id).await;
+                }
+                // Other buttons, such as the composer's, are collected by
+                // the command which sent them
+                if cmp.data.custom_id != DELETE_VOTE_STR && cmp.data.custom_id != EXPOSE_VOTE_STR {
+                    return Ok(());
+                }
                 let reaction_type: VoteType = cmp.data.custom_id.to_string().into();
                 let message_id = cmp.message.id.to_string();
                 let author_id = cmp.user.id.to_string();
@@ -63,61 +83,89 @@ pub async fn event_handler(
 
                 let data: Arc<Data> = framework.serenity_context.data();
                 let config = data.config.read().await;
-                let guild_config = get_guild_config(&config.db_url, &guild_id).await?;
+                let guild_config = data.db.get_guild_config(&guild_id).await?;
 
-                if reaction_type == VoteType::DELETE {
-                    let updated = update_vote(
-                        &config.db_url,
-                        &author_id,
-                        &message_id,
-                        &guild_id,
-                        reaction_type,
+                if moderation::is_banned(&data.db, &config.author_keys, &guild_id, &author_id)
+                    .await?
+                {
+                    cmp.create_response(
+                        &framework.serenity_context.http,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .ephemeral(true)
+                                .content(moderation::BANNED_MESSAGE),
+                        ),
                     )
                     .await?;
+                    return Ok(());
+                }
 
-                    let updated_message = if updated.0 == updated.1 {
-                        EditMessage::new()
-                            .embed(
-                                CreateEmbed::new()
-                                    .title(
-                                        cmp.message
-                                            .embeds
-                                            .first()
-                                            .unwrap()
-                                            .title
-                                            .as_ref()
-                                            .unwrap()
-                                            .to_string(),
-                                    )
-                                    .description(format!(
-                                        "Deleted Confession ({} votes)",
-                                        updated.0
-                                    ))
-                                    .color(0xFF0000),
-                            )
-                            .components(vec![])
-                    } else {
-                        let action_row = match cmp.message.components.first() {
-                            Some(c) => c,
-                            None => {
-                                return Err(Box::from("Could not get components from message."))
-                            }
-                        };
+                if reaction_type == VoteType::DELETE {
+                    let updated = data
+                        .db
+                        .update_vote(
+                            &config.author_keys,
+                            &author_id,
+                            &message_id,
+                            &guild_id,
+                            reaction_type,
+                        )
+                        .await?;
 
-                        let expose = match action_row.components.iter().nth(1) {
-                            Some(ActionRowComponent::Button(b)) => b,
-                            e => {
-                                panic!("Did not find the right component! Got: {:?}", e)
-                            }
-                        };
-                        let components = CreateActionRow::Buttons(vec![
-                            CreateButton::new(DELETE_VOTE_STR)
-                                .emoji(ReactionType::from_str("🗑")?)
-                                .style(ButtonStyle::Danger)
-                                .label(format!("Delete ({}/{})", updated.0, updated.1)),
-                            expose.clone().into(),
-                        ]);
-                        EditMessage::new().components(vec![components])
+                    let updated_message = match updated {
+                        // Another vote has already closed the confession
+                        VoteOutcome::Closed => return Ok(()),
+                        VoteOutcome::Reached(votes) => {
+                            let confession = data
+                                .db
+                                .get_confession_by_message_id(&message_id, &guild_id)
+                                .await?;
+                            data.db.delete_attachments(confession.id).await?;
+                            EditMessage::new()
+                                .embed(
+                                    CreateEmbed::new()
+                                        .title(
+                                            cmp.message
+                                                .embeds
+                                                .first()
+                                                .unwrap()
+                                                .title
+                                                .as_ref()
+                                                .unwrap()
+                                                .to_string(),
+                                        )
+                                        .description(format!(
+                                            "Deleted Confession ({} votes)",
+                                            votes
+                                        ))
+                                        .color(0xFF0000),
+                                )
+                                .components(vec![])
+                                .remove_all_attachments()
+                        }
+                        VoteOutcome::Counted(votes, required) => {
+                            let action_row = match cmp.message.components.first() {
+                                Some(c) => c,
+                                None => {
+                                    return Err(Box::from("Could not get components from message."))
+                                }
+                            };
+
+                            let expose = match action_row.components.iter().nth(1) {
+                                Some(ActionRowComponent::Button(b)) => b,
+                                e => {
+                                    panic!("Did not find the right component! Got: {:?}", e)
+                                }
+                            };
+                            let components = CreateActionRow::Buttons(vec![
+                                CreateButton::new(DELETE_VOTE_STR)
+                                    .emoji(ReactionType::from_str("🗑")?)
+                                    .style(ButtonStyle::Danger)
+                                    .label(format!("Delete ({}/{})", votes, required)),
+                                expose.clone().into(),
+                            ]);
+                            EditMessage::new().components(vec![components])
+                        }
                     };
 
                     cmp.message
@@ -138,110 +186,61 @@ pub async fn event_handler(
                             return Ok(());
                         }
                     }
-                    let updated = update_vote(
-                        &config.db_url,
-                        &author_id,
-                        &message_id,
-                        &guild_id,
-                        reaction_type,
-                    )
-                    .await?;
-
-                    let updated_message = if updated.0 == updated.1 {
-                        // Attempt to find the author
-                        let guild = framework
-                            .serenity_context
-                            .http
-                            .get_guild(cmp.guild_id.unwrap())
-                            .await?;
-                        let author_hash = get_author_hash_by_message(
-                            &config.db_url,
-                            &cmp.message.id.to_string(),
+                    let updated = data
+                        .db
+                        .update_vote(
+                            &config.author_keys,
+                            &author_id,
+                            &message_id,
                             &guild_id,
+                            reaction_type,
                         )
                         .await?;
-                        let mut first_author_id: Option<UserId> = None;
-                        let mut last_user_id: Option<UserId> = None;
 
-                        let mut author =
-                            "Unknown (Author may no longer be within the Guild)".to_string();
-
-                        for _ in (0..250_000).skip(1000) {
-                            let members = guild
-                                .members(&framework.serenity_context.http, None, last_user_id)
+                    let updated_message = match updated {
+                        VoteOutcome::Closed => return Ok(()),
+                        VoteOutcome::Reached(_) => {
+                            let confession = data
+                                .db
+                                .get_confession_by_message_id(&message_id, &guild_id)
                                 .await?;
-
-                            if let Some(member) = members.first() {
-                                if Some(member.user.id) == first_author_id {
-                                    break;
-                                }
-                                if first_author_id.is_none() {
-                                    first_author_id = Some(member.user.id);
-                                }
+                            match confession.reveal_threshold {
+                                Some(threshold) => EditMessage::new()
+                                    .components(vec![reveal::approval_row(0, threshold as usize)?]),
+                                None => reveal::exposed_message(
+                                    &cmp.message,
+                                    reveal::reveal_author(
+                                        &framework,
+                                        &confession,
+                                        &guild_id,
+                                        &config.escrow_key,
+                                    )
+                                    .await?,
+                                ),
                             }
-
-                            let found = members.iter().enumerate().find(|(i, m)| {
-                                let mut context = ring::digest::Context::new(&SHA256);
-                                context.update(m.user.id.to_string().as_bytes());
-
-                                let hash = format!("{:X?}", context.finish());
-
-                                if i % 1000 == 0 {
-                                    last_user_id = Some(m.user.id)
-                                } else if *i == members.len() - 1 {
-                                    last_user_id = None;
+                        }
+                        VoteOutcome::Counted(votes, required) => {
+                            let action_row = match cmp.message.components.first() {
+                                Some(c) => c,
+                                None => {
+                                    return Err(Box::from("Could not get components from message."))
                                 }
+                            };
 
-                                return hash == author_hash;
-                            });
-
-                            if let Some(f) = found {
-                                author = format!(
-                                    "{} - ({})",
-                                    f.1.user.display_name(),
-                                    f.1.user.id.to_string()
-                                );
-                                break;
-                            }
+                            let delete = match action_row.components.iter().nth(0) {
+                                Some(ActionRowComponent::Button(b)) => b,
+                                e => {
+                                    panic!("Did not find the right component! Got: {:?}", e)
+                                }
+                            };
+                            let components = CreateActionRow::Buttons(vec![
+                                delete.clone().into(),
+                                CreateButton::new(EXPOSE_VOTE_STR)
+                                    .emoji(ReactionType::from_str("🕵️")?)
+                                    .label(format!("Expose ({}/{})", votes, required)),
+                            ]);
+                            EditMessage::new().components(vec![components])
                         }
-                        EditMessage::new()
-                            .embed(
-                                CreateEmbed::from(cmp.message.embeds.first().unwrap().clone())
-                                    .title(format!(
-                                        "Exposed {}",
-                                        cmp.message
-                                            .embeds
-                                            .first()
-                                            .unwrap()
-                                            .title
-                                            .as_ref()
-                                            .unwrap()
-                                            .to_string()
-                                    ))
-                                    .footer(CreateEmbedFooter::new(format!("Author: {}", author))),
-                            )
-                            .components(vec![])
-                    } else {
-                        let action_row = match cmp.message.components.first() {
-                            Some(c) => c,
-                            None => {
-                                return Err(Box::from("Could not get components from message."))
-                            }
-                        };
-
-                        let delete = match action_row.components.iter().nth(0) {
-                            Some(ActionRowComponent::Button(b)) => b,
-                            e => {
-                                panic!("Did not find the right component! Got: {:?}", e)
-                            }
-                        };
-                        let components = CreateActionRow::Buttons(vec![
-                            delete.clone().into(),
-                            CreateButton::new(EXPOSE_VOTE_STR)
-                                .emoji(ReactionType::from_str("🕵️")?)
-                                .label(format!("Expose ({}/{})", updated.0, updated.1)),
-                        ]);
-                        EditMessage::new().components(vec![components])
                     };
 
                     cmp.message
@@ -249,17 +248,18 @@ pub async fn event_handler(
                         .edit(&framework.serenity_context.http, updated_message)
                         .await?;
                 }
+            } else if let Some(modal) = interaction.as_modal_submit() {
+                if let Some((REVIEW_REASON_STR, confession_id)) =
+                    review::parse_custom_id(&modal.data.custom_id)
+                {
+                    return review::reject_with_reason(framework, modal, confession_id).await;
+                }
             }
         }
-        // FullEvent::GuildMemberUpdate { old_if_available, new, event } => {
-        //     /*
-        //     just dont bother deleting from the database until the timeout finish time is no longer
-        //     greater than this value below
-        //     basically means that the timeout does not go away ever and if the communication disabled here is less than the one in
-        //     the database it knows it's been removed and can reapply it ;)
-        //     */
-        //     // new.unwrap().communication_disabled_until
-        // }
+        FullEvent::GuildMemberUpdate { event, .. } => {
+            let data = framework.serenity_context.data::<Data>();
+            schedule::enforce_timeout(&framework.serenity_context.http, &data, event).await?;
+        }
         FullEvent::Ready { data_about_bot } => {
             info!(
                 "Logged in as: {}. Currently observing {} guild(s)",
@@ -278,21 +278,16 @@ pub async fn event_handler(
         } => {
             if *new_guild {
                 let data = framework.serenity_context.data::<Data>();
-                let config = data.config.read().await;
-                guilds::insert_guild(&config.db_url, &guild.id.to_string()).await?;
+                data.db.insert_guild(&guild.id.to_string()).await?;
                 info!("Joined Guild {}", guild.id)
             }
-            observe(framework, guild.clone()).await;
         }
         FullEvent::Message { new_message } => {
             if new_message.mentions_user_id(framework.bot_id()) {
                 let data = framework.serenity_context.data::<Data>();
-                let config = data.config.read().await;
                 if let Some(guild_id) = new_message.guild_id {
                     let guild = {
-                        if let Some(g) =
-                            guilds::get_guild(&config.db_url, &guild_id.to_string()).await?
-                        {
+                        if let Some(g) = data.db.get_guild(&guild_id.to_string()).await? {
                             g
                         } else {
                             error!(
@@ -314,24 +309,61 @@ pub async fn event_handler(
                                     } else {
                                         "Unset".to_owned()
                                     }, true),
-                                    ("", "".to_owned(), true),
-                                    ("", "".to_owned(), true),
+                                    ("Review Channel",
+                                    if let Some(review_channel) = &config.review_channel {
+                                        format!("<#{}>", review_channel)
+                                    } else {
+                                        "Unset".to_owned()
+                                    }, true),
+                                    ("Sanitise Level", format!("{:?}", config.sanitise_level), true),
+                                    ("Post Limits", limits::describe_post_limits(&config.post_limits), true),
+                                    ("Post Delay", outbox::describe_post_delay(&config.post_delay), true),
+                                    ("Attachments", attachments::describe_attachment_rules(&config.attachments), true),
+                                    ("Author Edit Window",
+                                    match config.author_edit_window {
+                                        0 => "Disabled".to_owned(),
+                                        window => format!("{} min", window / 60),
+                                    }, true),
+                                    ("Timezone", config.timezone().name().to_owned(), true),
+                                    ("Blackout", if config.blackout { "On" } else { "Off" }.to_owned(), true),
                                     ("Minimum Vote (Delete)", config.delete_vote_min.to_string(), true),
                                     ("", "".to_owned(), true),
                                     ("Minimum Vote (Expose)", config.expose_vote_min.to_string(), true),
                                     ("Minimum Role to register  (Expose)",
-                                    if let Some(expose_vote_role) = config.expose_vote_role {
+                                    if let Some(expose_vote_role) = &config.expose_vote_role {
                                         format!("<@{}>", expose_vote_role)
                                     } else {
                                         "Unset".to_string()
                                     }, true),
                                     ("", "".to_owned(), true),
                                     ("Role Ping",
-                                    if let Some(ping_role) = config.role_ping {
-                                        format!("<@{}>", ping_role)
+                                    if let Some(ping_role) = &config.role_ping {
+                                        match config.role_ping_interval {
+                                            0 => format!("<@&{}>", ping_role),
+                                            interval => format!(
+                                                "<@&{}>, at most every {} min",
+                                                ping_role,
+                                                interval / 60
+                                            ),
+                                        }
+                                    } else {
+                                        "Unset".to_owned()
+                                    }, true),
+                                    ("Reveal Approval",
+                                    if let Some(threshold) = config.reveal_quorum() {
+                                        format!(
+                                            "{} of {}",
+                                            threshold,
+                                            config
+                                                .reveal_moderators
+                                                .iter()
+                                                .map(|m| format!("<@{}>", m))
+                                                .collect::<Vec<_>>()
+                                                .join(", ")
+                                        )
                                     } else {
                                         "Unset".to_owned()
-                                    }, true)
+                                    }, false)
                                 ]
                             )
                             .color(0x11FF00);
@@ -0,0 +1,156 @@
+use chrono::{TimeDelta, Utc};
+use confession_bot_rs::crypto::AuthorKeys;
+use poise::{serenity_prelude::CreateEmbed, ChoiceParameter, CreateReply};
+use tracing::info;
+
+use crate::{
+    commands::{Context, Error},
+    db_impl::{AuthorStore, BanStore, ConfessionStore, Db},
+};
+
+/// What a banned user is told. It deliberately does not mention the ban, so
+/// it cannot be used to tell which confessions were theirs.
+pub const BANNED_MESSAGE: &str = "You cannot do that in this server right now.";
+
+#[derive(Debug, ChoiceParameter, Copy, Clone)]
+enum BanDuration {
+    #[name = "1 Hour"]
+    HOURS_1,
+    #[name = "1 Day"]
+    DAYS_1,
+    #[name = "1 Week"]
+    WEEKS_1,
+    #[name = "30 Days"]
+    DAYS_30,
+    #[name = "Permanent"]
+    PERMANENT,
+}
+
+impl From<BanDuration> for Option<TimeDelta> {
+    fn from(value: BanDuration) -> Self {
+        match value {
+            BanDuration::HOURS_1 => Some(TimeDelta::hours(1)),
+            BanDuration::DAYS_1 => Some(TimeDelta::days(1)),
+            BanDuration::WEEKS_1 => Some(TimeDelta::weeks(1)),
+            BanDuration::DAYS_30 => Some(TimeDelta::days(30)),
+            BanDuration::PERMANENT => None,
+        }
+    }
+}
+
+/// Whether a user is banned from confessing, replying and voting in a guild.
+pub async fn is_banned(
+    db: &Db,
+    keys: &AuthorKeys,
+    guild_id: &String,
+    user_id: &String,
+) -> Result<bool, Error> {
+    let author_id = db.insert_author(keys, guild_id, user_id).await?;
+    db.is_author_banned(guild_id, author_id).await
+}
+
+/// Moderate anonymous authors without learning who they are.
+#[poise::command(
+    slash_command,
+    rename = "mod",
+    subcommands("ban_author", "unban_author"),
+    subcommand_required,
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn moderation(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Ban the author of a confession from confessing, replying and voting.
+#[poise::command(
+    slash_command,
+    ephemeral,
+    rename = "ban-author",
+    required_permissions = "MODERATE_MEMBERS"
+)]
+async fn ban_author(
+    ctx: Context<'_>,
+    #[description = "The number of a confession by the author"] id: u32,
+    #[description = "How long the ban lasts"] duration: BanDuration,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let confession = data
+        .db
+        .get_confession_by_id_guild(id, &guild_id)
+        .await
+        .map_err(|_| format!("Could not find confession with ID `{}` in the Guild.", id))?;
+
+    let expires_at = Option::<TimeDelta>::from(duration).map(|d| Utc::now() + d);
+    data.db
+        .ban_author(
+            &guild_id,
+            confession.author,
+            &ctx.author().id.to_string(),
+            expires_at.map(|e| e.naive_utc()),
+        )
+        .await?;
+    info!(
+        "Moderator {} banned the author of confession {}",
+        ctx.author().id,
+        confession.id
+    );
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Author Banned")
+                .color(0x00FF00)
+                .description(format!(
+                    "The author of Confession #{} is banned {}.",
+                    id,
+                    match expires_at {
+                        Some(expires_at) => format!("until <t:{}:f>", expires_at.timestamp()),
+                        None => "permanently".to_string(),
+                    }
+                )),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Lift the ban on the author of a confession.
+#[poise::command(
+    slash_command,
+    ephemeral,
+    rename = "unban-author",
+    required_permissions = "MODERATE_MEMBERS"
+)]
+async fn unban_author(
+    ctx: Context<'_>,
+    #[description = "The number of a confession by the author"] id: u32,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let confession = data
+        .db
+        .get_confession_by_id_guild(id, &guild_id)
+        .await
+        .map_err(|_| format!("Could not find confession with ID `{}` in the Guild.", id))?;
+
+    // The same reply either way, so unbanning through different confessions
+    // does not show whether they share an author
+    if data.db.unban_author(&guild_id, confession.author).await? {
+        info!(
+            "Moderator {} unbanned the author of confession {}",
+            ctx.author().id,
+            confession.id
+        );
+    }
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Author Unbanned")
+                .color(0x00FF00)
+                .description(format!("The author of Confession #{} is not banned.", id)),
+        ),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use poise::serenity_prelude::{Channel, ChannelId, GetMessages, Http, Message};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::{
+    commands::{confess::publish_confession, Data, Error},
+    db_impl::{ConfessionStore, GuildStore, OutboxStore},
+    models::{Confession, DelayMode, OutboxEntry, PostDelay},
+};
+
+/// How often the outbox is checked for confessions which are due.
+const OUTBOX_INTERVAL: Duration = Duration::from_secs(15);
+/// Attempts at posting a queued confession before it is kept as a dead letter.
+const MAX_ATTEMPTS: i32 = 5;
+/// Seconds before the first retry of a post, doubled for each retry after.
+const RETRY_BACKOFF: i64 = 30;
+/// How far back the confession channel is searched for an unlinked post.
+const RECENT_MESSAGES: u8 = 50;
+
+/// When a confession sent now should be posted, if the guild delays posts.
+pub fn release_time(delay: &PostDelay, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    if delay.window <= 0 {
+        return None;
+    }
+    match delay.mode {
+        DelayMode::OFF => None,
+        DelayMode::RANDOM => {
+            Some(now + TimeDelta::seconds(thread_rng().gen_range(0..=delay.window)))
+        }
+        DelayMode::BATCH => {
+            // Batches go out on multiples of the window, so everything queued
+            // within one is posted together
+            let next = (now.and_utc().timestamp() / delay.window + 1) * delay.window;
+            DateTime::from_timestamp(next, 0).map(|t| t.naive_utc())
+        }
+    }
+}
+
+/// Describe a guild's post delay for the config embed.
+pub fn describe_post_delay(delay: &PostDelay) -> String {
+    if delay.window <= 0 {
+        return "Unset".to_owned();
+    }
+    match delay.mode {
+        DelayMode::OFF => "Unset".to_owned(),
+        DelayMode::RANDOM => format!("Random, up to {} min", delay.window / 60),
+        DelayMode::BATCH => format!("Batches every {} min", delay.window / 60),
+    }
+}
+
+/// Post queued confessions once they are due. Runs for as long as the bot does,
+/// and picks up anything left queued from before a restart.
+pub async fn release_outbox(http: Arc<Http>, data: Arc<Data>) {
+    loop {
+        if let Err(e) = release_due(&http, &data).await {
+            error!("Could not check the outbox: {}", e);
+        }
+        sleep(OUTBOX_INTERVAL).await;
+    }
+}
+
+async fn release_due(http: &Http, data: &Data) -> Result<(), Error> {
+    let mut due = data.db.get_due_outbox(Utc::now().naive_utc()).await?;
+    // Post due confessions in a random order, so a batch does not give away
+    // the order they were sent in
+    due.shuffle(&mut thread_rng());
+    for entry in due {
+        if let Err(e) = release(http, data, &entry).await {
+            let now = Utc::now().naive_utc();
+            let retry_at = (entry.attempts + 1 < MAX_ATTEMPTS)
+                .then(|| now + TimeDelta::seconds(RETRY_BACKOFF << entry.attempts));
+            match retry_at {
+                Some(retry_at) => warn!(
+                    "Could not post queued confession {}, retrying in {}s: {}",
+                    entry.confession_id,
+                    (retry_at - now).num_seconds(),
+                    e
+                ),
+                None => error!(
+                    "Could not post queued confession {} after {} attempts, giving up: {}",
+                    entry.confession_id, MAX_ATTEMPTS, e
+                ),
+            }
+            data.db
+                .fail_outbox(entry.id, &e.to_string(), retry_at)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn release(http: &Http, data: &Data, entry: &OutboxEntry) -> Result<(), Error> {
+    let guild = data
+        .db
+        .get_guild(&entry.guild_id)
+        .await?
+        .ok_or("Guild not found")?;
+    let guild_config = data.db.get_guild_config(&entry.guild_id).await?;
+    // Find the channel before releasing, so the confession is not numbered
+    // while it cannot be posted
+    let channel = match guild.confession_channel_id {
+        Some(channel_id) => match http
+            .get_channel(ChannelId::new(channel_id.parse()?))
+            .await?
+        {
+            Channel::Guild(channel) => channel,
+            _ => return Err(Box::from("The confession channel is not a guild channel")),
+        },
+        None => return Err(Box::from("No confession channel has been set")),
+    };
+
+    let confession = match data.db.release_confession(entry.confession_id).await? {
+        Some(confession) => confession,
+        None => return data.db.remove_from_outbox(entry.id).await,
+    };
+    if let Some(message) = find_unlinked_post(http, channel.id, &confession).await? {
+        data.db
+            .set_confession_message(
+                confession.id,
+                &message.channel_id.to_string(),
+                &message.id.to_string(),
+            )
+            .await?;
+        data.db.remove_from_outbox(entry.id).await?;
+        info!(
+            "Linked queued confession {} to its earlier post",
+            confession.id
+        );
+        return Ok(());
+    }
+    publish_confession(http, &data.db, &channel, &guild_config, &confession).await?;
+    data.db.remove_from_outbox(entry.id).await?;
+    info!("Posted queued confession {}", confession.id);
+    Ok(())
+}
+
+/// A confession may have been posted before a crash left it unlinked from its
+/// message. Look for the post among the channel's latest messages, so it is
+/// not posted twice.
+async fn find_unlinked_post(
+    http: &Http,
+    channel_id: ChannelId,
+    confession: &Confession,
+) -> Result<Option<Message>, Error> {
+    let heading = format!(
+        "Confession #{}",
+        confession
+            .number
+            .ok_or("Confession was not given a number")?
+    );
+    let bot_id = http.get_current_user().await?.id;
+    let recent = channel_id
+        .messages(http, GetMessages::new().limit(RECENT_MESSAGES))
+        .await?;
+    Ok(recent.into_iter().find(|message| {
+        message.author.id == bot_id
+            && message.embeds.iter().any(|embed| {
+                embed.title.as_deref().is_some_and(|title| {
+                    title == heading || title.starts_with(&format!("{}: ", heading))
+                })
+            })
+    }))
+}
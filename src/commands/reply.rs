@@ -1,14 +1,24 @@
 use std::collections::HashMap;
 
 use crate::{
-    commands::{Context, Error},
-    db_impl::{
-        confessions::get_confession_by_id_guild,
-        guilds::get_guild,
-        reply::{get_confession_replies, insert_reply},
+    commands::{
+        attachments::{accept_attachment, attach, attachment_text, check_attachment},
+        filter::{blocked_embed, screen, Screened},
+        limits::check_post_limits,
+        moderation::{is_banned, BANNED_MESSAGE},
+        Context, Error,
     },
+    db_impl::{ConfessionStore, GuildStore, ReplyStore},
+    sanitise::sanitise,
+};
+use confession_bot_rs::{ConfessionStatus, REPLY_SOURCE_STR};
+use poise::{
+    serenity_prelude::{
+        Attachment, ChannelId, CreateAllowedMentions, CreateEmbed, CreateMessage, GuildChannel,
+        MessageId,
+    },
+    CreateReply,
 };
-use poise::serenity_prelude::{ChannelId, CreateEmbed, CreateMessage, GuildChannel, MessageId};
 use rand::random;
 use tracing::error;
 
@@ -17,6 +27,7 @@ pub async fn reply(
     ctx: Context<'_>,
     #[description = "The ID of the confession to respond to"] id: u32,
     #[description = "The confession text content"] content: String,
+    #[description = "An image or text file to attach"] attachment: Option<Attachment>,
 ) -> Result<(), Error> {
     let data = ctx.data();
     let config = data.config.read().await;
@@ -29,8 +40,20 @@ pub async fn reply(
         }
     };
 
+    if is_banned(
+        &data.db,
+        &config.author_keys,
+        &guild_id.to_string(),
+        &ctx.author().id.to_string(),
+    )
+    .await?
+    {
+        ctx.reply(BANNED_MESSAGE).await?;
+        return Ok(());
+    }
+
     let confession_channel =
-        match get_guild(&config.db_url, &guild_id.to_string()).await {
+        match data.db.get_guild(&guild_id.to_string()).await {
             Ok(res) => {
                 if let Some(g) = res {
                     match g.confession_channel_id {
@@ -50,28 +73,122 @@ pub async fn reply(
             }
         };
 
-    if let Ok(confession) =
-        get_confession_by_id_guild(&config.db_url, id, &guild_id.to_string()).await
+    if let Ok(confession) = data
+        .db
+        .get_confession_by_id_guild(id, &guild_id.to_string())
+        .await
     {
-        // Check that the message hasn't been deleted.
-        if confession.deleted == 1 {
-            ctx.reply("Cannot respond to the Confession. Reason: Confession has been deleted.")
+        // Check that the message hasn't been taken down. Exposed confessions
+        // can still be replied to.
+        let status: ConfessionStatus = confession.status.clone().into();
+        match status {
+            ConfessionStatus::DELETED => {
+                ctx.reply("Cannot respond to the Confession. Reason: Confession has been deleted.")
+                    .await?;
+                return Ok(());
+            }
+            ConfessionStatus::REMOVED => {
+                ctx.reply(
+                    "Cannot respond to the Confession. Reason: Confession was removed by a moderator.",
+                )
                 .await?;
+                return Ok(());
+            }
+            ConfessionStatus::PENDING | ConfessionStatus::QUEUED | ConfessionStatus::REJECTED => {
+                ctx.reply("Cannot respond to the Confession. Reason: Confession was not posted.")
+                    .await?;
+                return Ok(());
+            }
+            ConfessionStatus::ACTIVE | ConfessionStatus::EXPOSED => {}
+        }
+        // Numbered before it is sent, so there is a moment with no message yet
+        if confession.message_id.is_empty() {
+            ctx.reply(
+                "Cannot respond to the Confession. Reason: Confession has not been posted yet.",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let guild_config = data.db.get_guild_config(&guild_id.to_string()).await?;
+        let user_id = ctx.author().id.to_string();
+        let content = match screen(
+            data,
+            &guild_id.to_string(),
+            &user_id,
+            &guild_config,
+            &content,
+            REPLY_SOURCE_STR,
+        )
+        .await?
+        {
+            Screened::Allowed(content) => content,
+            // Replies are never held for review
+            Screened::Held(..) | Screened::Blocked => {
+                ctx.send(CreateReply::default().embed(blocked_embed(REPLY_SOURCE_STR)))
+                    .await?;
+                return Ok(());
+            }
+        };
+        let content = sanitise(&content, guild_config.sanitise_level);
+        let attachment = match attachment {
+            Some(upload) => {
+                check_attachment(&guild_config.attachments, &upload)?;
+                // Downloading and stripping it can take a while
+                ctx.defer_ephemeral().await?;
+                let mut attachment = accept_attachment(&guild_config.attachments, &upload).await?;
+                if let Some(text) = attachment_text(&attachment) {
+                    match screen(
+                        data,
+                        &guild_id.to_string(),
+                        &user_id,
+                        &guild_config,
+                        &text,
+                        REPLY_SOURCE_STR,
+                    )
+                    .await?
+                    {
+                        Screened::Allowed(text) => attachment.data = text.into_bytes(),
+                        Screened::Held(..) | Screened::Blocked => {
+                            ctx.send(CreateReply::default().embed(blocked_embed(REPLY_SOURCE_STR)))
+                                .await?;
+                            return Ok(());
+                        }
+                    }
+                }
+                Some(attachment)
+            }
+            None => None,
+        };
+        if let Some(refusal) = check_post_limits(
+            data,
+            &guild_id.to_string(),
+            &user_id,
+            &guild_config,
+            REPLY_SOURCE_STR,
+        )
+        .await?
+        {
+            ctx.send(CreateReply::default().embed(refusal)).await?;
             return Ok(());
         }
 
         let mut map = HashMap::new();
         map.insert("name", format!("Confession {} replies", id));
-        let reply_count = get_confession_replies(&config.db_url, confession.id)
-            .await?
-            .len();
+        let reply_count = data.db.get_confession_replies(confession.id).await?.len();
 
         let reply_channel: GuildChannel = if reply_count == 0 {
             match ctx
                 .http()
                 .create_thread_from_message(
-                    ChannelId::new(confession_channel.parse().unwrap()),
-                    MessageId::new(confession.message_id.parse().unwrap()),
+                    ChannelId::new(
+                        confession
+                            .channel_id
+                            .as_ref()
+                            .unwrap_or(&confession_channel)
+                            .parse()?,
+                    ),
+                    MessageId::new(confession.message_id.parse()?),
                     &map,
                     None,
                 )
@@ -100,11 +217,13 @@ pub async fn reply(
         let message_res = match reply_channel
             .send_message(
                 ctx.http(),
-                CreateMessage::default().embed(
+                attach(
+                    CreateMessage::default().allowed_mentions(CreateAllowedMentions::new()),
                     CreateEmbed::default()
                         .color(random::<u16>() as u32)
                         .title(format!("Response #{} to Confession", reply_count + 1))
                         .description(content.clone()),
+                    attachment.as_ref(),
                 ),
             )
             .await
@@ -115,15 +234,17 @@ pub async fn reply(
             }
         };
 
-        insert_reply(
-            &config.db_url,
-            confession.id,
-            &confession.guild_id,
-            &message_res.id.to_string(),
-            &content,
-            &ctx.author().id.to_string(),
-        )
-        .await?;
+        data.db
+            .insert_reply(
+                &config.author_keys,
+                confession.id,
+                &confession.guild_id,
+                &message_res.id.to_string(),
+                &content,
+                &ctx.author().id.to_string(),
+                attachment.as_ref(),
+            )
+            .await?;
     } else {
         return Err(Box::from(format!(
             "Could not find confession with ID `{}` in the Guild.",
@@ -0,0 +1,262 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use confession_bot_rs::{
+    crypto::{combine_shares, parse_share_token, share_hash, share_token, EscrowKey},
+    ConfessionStatus, REVEAL_APPROVE_STR, REVEAL_SHARE_MODAL_STR,
+};
+use poise::{
+    serenity_prelude::{
+        ActionRowComponent, ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton,
+        CreateEmbed, CreateEmbedFooter, CreateInputText, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, CreateModal, EditMessage, Http,
+        InputTextStyle, Message, ModalInteraction, ModalInteractionCollector, ReactionType, UserId,
+    },
+    FrameworkContext,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    commands::{Data, Error},
+    db_impl::{ConfessionStore, DealtShare, RevealStore},
+    models::Confession,
+};
+
+/// How long a moderator has to paste their share once they approve.
+const SHARE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Send each designated moderator their share of a confession's reveal key.
+/// The bot only keeps a hash of each share, so one that cannot be delivered
+/// is lost, along with that moderator's approval.
+pub async fn deal_shares(
+    http: &Http,
+    guild_id: &String,
+    confession: &Confession,
+    shares: &[DealtShare],
+) {
+    for share in shares {
+        let user_id = match UserId::from_str(&share.moderator_id) {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+        let embed = CreateEmbed::default()
+            .color(0xFFAA00)
+            .title("Reveal Share")
+            .description(format!(
+                "You hold a share of the key revealing the author of confession `R{}` in server `{}`. If its author is exposed, approving the reveal will ask for it:\n`{}`\nI do not keep a copy, so keep this message.",
+                confession.id,
+                guild_id,
+                share.token()
+            ));
+        if let Err(e) = user_id
+            .direct_message(http, CreateMessage::default().embed(embed))
+            .await
+        {
+            warn!(
+                "Could not send moderator {} their share for confession {}: {}",
+                share.moderator_id, confession.id, e
+            );
+        }
+    }
+}
+
+/// Take back a designated moderator's share to reveal the author of a
+/// confession. Once enough moderators have given theirs back, the shares are
+/// combined and the author is revealed.
+pub async fn approve_reveal(
+    framework: FrameworkContext<'_, Data, Error>,
+    cmp: &ComponentInteraction,
+) -> Result<(), Error> {
+    let guild_id = cmp
+        .guild_id
+        .ok_or("Could not get Guild ID for interaction.")?
+        .to_string();
+    let moderator_id = cmp.user.id.to_string();
+    let http = &framework.serenity_context.http;
+
+    let data: Arc<Data> = framework.serenity_context.data();
+
+    let confession = data
+        .db
+        .get_confession_by_message_id(&cmp.message.id.to_string(), &guild_id)
+        .await?;
+    let threshold = match confession.reveal_threshold {
+        Some(threshold) => threshold as usize,
+        None => return Ok(()),
+    };
+    // Reveals can only be approved once the expose vote has passed
+    if Into::<ConfessionStatus>::into(confession.status.clone()) != ConfessionStatus::EXPOSED {
+        return Ok(());
+    }
+
+    // Only moderators who were dealt a share may approve
+    let dealt = match data
+        .db
+        .get_reveal_shares(confession.id, &[moderator_id.clone()])
+        .await?
+        .pop()
+    {
+        Some(dealt) => dealt,
+        None => return Ok(()),
+    };
+
+    let custom_id = format!("{}:{}", REVEAL_SHARE_MODAL_STR, cmp.id);
+    cmp.create_response(
+        http,
+        CreateInteractionResponse::Modal(
+            CreateModal::new(
+                custom_id.clone(),
+                format!("Reveal share for R{}", confession.id),
+            )
+            .components(vec![CreateActionRow::InputText(
+                CreateInputText::new(InputTextStyle::Short, "Share", "share")
+                    .placeholder("The share I sent you when the confession was made"),
+            )]),
+        ),
+    )
+    .await?;
+    let modal = match ModalInteractionCollector::new(framework.serenity_context.shard.clone())
+        .author_id(cmp.user.id)
+        .filter(move |modal| modal.data.custom_id == custom_id.as_str())
+        .timeout(SHARE_TIMEOUT)
+        .await
+    {
+        Some(modal) => modal,
+        None => return Ok(()),
+    };
+
+    let share = modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) => input.value.as_ref().map(|v| v.to_string()),
+            _ => None,
+        })
+        .and_then(|token| parse_share_token(&token))
+        .filter(|(index, share)| {
+            i32::from(*index) == dealt.share_index && share_hash(*index, share) == dealt.share_hash
+        });
+    let (index, share) = match share {
+        Some(share) => share,
+        None => {
+            return share_reply(
+                http,
+                &modal,
+                "That is not the share you were sent for this confession.",
+            )
+            .await
+        }
+    };
+
+    let approvals = data
+        .db
+        .insert_reveal_approval(confession.id, &moderator_id, &share_token(index, &share))
+        .await?;
+    info!(
+        "Moderator {} approved revealing the author of confession {} ({}/{})",
+        moderator_id,
+        confession.id,
+        approvals.len(),
+        threshold
+    );
+    share_reply(
+        http,
+        &modal,
+        &format!(
+            "Your share has been accepted ({}/{}).",
+            approvals.len(),
+            threshold
+        ),
+    )
+    .await?;
+
+    let updated_message = if approvals.len() >= threshold {
+        let shares = approvals[..threshold]
+            .iter()
+            .filter_map(|token| parse_share_token(token))
+            .collect::<Vec<_>>();
+        let key = EscrowKey::new(&combine_shares(&shares))
+            .map_err(|_| "Could not rebuild the reveal key")?;
+        let author = reveal_author(&framework, &confession, &guild_id, &key).await?;
+        // The shares are only needed until the author is known
+        data.db.clear_reveal_approvals(confession.id).await?;
+        exposed_message(&cmp.message, author)
+    } else {
+        EditMessage::new().components(vec![approval_row(approvals.len(), threshold)?])
+    };
+
+    cmp.message.clone().edit(http, updated_message).await?;
+    Ok(())
+}
+
+async fn share_reply(http: &Http, modal: &ModalInteraction, reply: &str) -> Result<(), Error> {
+    modal
+        .create_response(
+            http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(reply.to_string()),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Recover the author of a confession from its escrow, formatted for the
+/// exposed confession's footer.
+pub async fn reveal_author(
+    framework: &FrameworkContext<'_, Data, Error>,
+    confession: &Confession,
+    guild_id: &String,
+    key: &EscrowKey,
+) -> Result<String, Error> {
+    let sealed = match &confession.author_escrow {
+        Some(sealed) => sealed,
+        None => return Ok("Unknown (Confession predates author escrow)".to_string()),
+    };
+    let user_id = match key.open(guild_id, sealed) {
+        Ok(id) => UserId::from_str(&id)?,
+        Err(_) => {
+            error!(
+                "Could not open the author escrow for confession {}",
+                confession.id
+            );
+            return Ok("Unknown (Author could not be recovered)".to_string());
+        }
+    };
+    match framework.serenity_context.http.get_user(user_id).await {
+        Ok(user) => Ok(format!("{} - ({})", user.display_name(), user.id)),
+        Err(_) => Ok(format!("<@{}> - ({})", user_id, user_id)),
+    }
+}
+
+pub fn exposed_message(message: &Message, author: String) -> EditMessage<'static> {
+    let (embed, title) = match message.embeds.first() {
+        Some(embed) => (
+            CreateEmbed::from(embed.clone()),
+            embed
+                .title
+                .as_ref()
+                .map(|t| t.to_string())
+                .unwrap_or_default(),
+        ),
+        None => (CreateEmbed::new(), String::new()),
+    };
+    EditMessage::new()
+        .embed(
+            embed
+                .title(format!("Exposed {}", title))
+                .footer(CreateEmbedFooter::new(format!("Author: {}", author))),
+        )
+        .components(vec![])
+}
+
+pub fn approval_row(approvals: usize, threshold: usize) -> Result<CreateActionRow<'static>, Error> {
+    let approve = CreateButton::new(REVEAL_APPROVE_STR)
+        .emoji(ReactionType::from_str("🔑")?)
+        .style(ButtonStyle::Primary)
+        .label(format!("Approve Reveal ({}/{})", approvals, threshold));
+    Ok(CreateActionRow::Buttons(vec![approve]))
+}
@@ -0,0 +1,377 @@
+use std::{str::FromStr, sync::Arc};
+
+use confession_bot_rs::{REVIEW_APPROVE_STR, REVIEW_REASON_STR, REVIEW_REJECT_STR};
+use poise::{
+    serenity_prelude::{
+        ActionRowComponent, ButtonStyle, Channel, ChannelId, ComponentInteraction, CreateActionRow,
+        CreateAllowedMentions, CreateButton, CreateEmbed, CreateEmbedFooter, CreateInputText,
+        CreateInteractionResponse, CreateMessage, CreateModal, EditMessage, Http, InputTextStyle,
+        Member, Message, ModalInteraction, ReactionType, User, UserId,
+    },
+    FrameworkContext,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    commands::{
+        attachments::attach,
+        confess::{confession_embed, publish_confession},
+        Data, Error,
+    },
+    db_impl::{review_notice_context, ConfessionStore, GuildStore},
+    models::{Confession, NewAttachment},
+};
+
+/// Split a review button or modal ID into its action and confession ID.
+pub fn parse_custom_id(custom_id: &str) -> Option<(&str, i32)> {
+    let (action, confession_id) = custom_id.split_once(':')?;
+    match action {
+        REVIEW_APPROVE_STR | REVIEW_REJECT_STR | REVIEW_REASON_STR => {
+            Some((action, confession_id.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// Post a pending confession into the review channel. Moderators are only
+/// shown the content and attachment, never the author, along with any filter
+/// rules which asked for it to be held.
+pub async fn send_for_review(
+    http: &Http,
+    review_channel: ChannelId,
+    confession: &Confession,
+    attachment: Option<&NewAttachment>,
+    filter_rules: Option<String>,
+) -> Result<Message, Error> {
+    let mut embed = confession_embed(
+        "Pending Confession".to_string(),
+        confession.title.as_ref(),
+        confession.content_warning.as_ref(),
+        &confession.content,
+    )
+    .color(0xFFAA00);
+    if let Some(filter_rules) = filter_rules {
+        embed = embed.field("Held by filter rules", filter_rules, false);
+    }
+    Ok(review_channel
+        .send_message(
+            http,
+            attach(
+                CreateMessage::default().allowed_mentions(CreateAllowedMentions::new()),
+                embed,
+                attachment,
+            )
+            .components(&[CreateActionRow::Buttons(vec![
+                CreateButton::new(format!("{}:{}", REVIEW_APPROVE_STR, confession.id))
+                    .emoji(ReactionType::from_str("✅")?)
+                    .style(ButtonStyle::Success)
+                    .label("Approve"),
+                CreateButton::new(format!("{}:{}", REVIEW_REJECT_STR, confession.id))
+                    .emoji(ReactionType::from_str("❌")?)
+                    .style(ButtonStyle::Danger)
+                    .label("Reject"),
+                CreateButton::new(format!("{}:{}", REVIEW_REASON_STR, confession.id))
+                    .emoji(ReactionType::from_str("📝")?)
+                    .style(ButtonStyle::Secondary)
+                    .label("Reject with reason"),
+            ])]),
+        )
+        .await?)
+}
+
+/// Handle a moderator pressing one of the review buttons.
+pub async fn review_confession(
+    framework: FrameworkContext<'_, Data, Error>,
+    cmp: &ComponentInteraction,
+    action: &str,
+    confession_id: i32,
+) -> Result<(), Error> {
+    if !is_moderator(cmp.member.as_deref()) {
+        return Ok(());
+    }
+    let http = &framework.serenity_context.http;
+
+    match action {
+        REVIEW_APPROVE_STR => approve(framework, cmp, confession_id).await,
+        REVIEW_REJECT_STR => reject(framework, &cmp.message, &cmp.user, confession_id, None).await,
+        REVIEW_REASON_STR => {
+            // The rejection happens once the reason is submitted
+            cmp.create_response(
+                http,
+                CreateInteractionResponse::Modal(
+                    CreateModal::new(
+                        format!("{}:{}", REVIEW_REASON_STR, confession_id),
+                        "Reject Confession",
+                    )
+                    .components(vec![CreateActionRow::InputText(
+                        CreateInputText::new(InputTextStyle::Paragraph, "Reason", "reason")
+                            .placeholder("This is sent to the author")
+                            .max_length(1000),
+                    )]),
+                ),
+            )
+            .await?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Handle the reason modal opened by [`review_confession`].
+pub async fn reject_with_reason(
+    framework: FrameworkContext<'_, Data, Error>,
+    modal: &ModalInteraction,
+    confession_id: i32,
+) -> Result<(), Error> {
+    if !is_moderator(modal.member.as_deref()) {
+        return Ok(());
+    }
+    modal
+        .create_response(
+            &framework.serenity_context.http,
+            CreateInteractionResponse::Acknowledge,
+        )
+        .await?;
+
+    let reason = modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) => input.value.as_ref().map(|v| v.to_string()),
+            _ => None,
+        })
+        .filter(|reason| !reason.trim().is_empty());
+
+    let message = modal
+        .message
+        .as_deref()
+        .ok_or("Could not find the review message for the modal.")?;
+    reject(framework, message, &modal.user, confession_id, reason).await
+}
+
+fn is_moderator(member: Option<&Member>) -> bool {
+    member
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_messages())
+}
+
+async fn approve(
+    framework: FrameworkContext<'_, Data, Error>,
+    cmp: &ComponentInteraction,
+    confession_id: i32,
+) -> Result<(), Error> {
+    let guild_id = cmp
+        .guild_id
+        .ok_or("Could not get Guild ID for interaction.")?
+        .to_string();
+    let http = &framework.serenity_context.http;
+    let data: Arc<Data> = framework.serenity_context.data();
+
+    let confession = match data.db.approve_confession(&guild_id, confession_id).await? {
+        Some(confession) => confession,
+        // Already reviewed by another moderator
+        None => return Ok(()),
+    };
+    let guild = data
+        .db
+        .get_guild(&guild_id)
+        .await?
+        .ok_or("Guild not found")?;
+    let guild_config = data.db.get_guild_config(&guild_id).await?;
+
+    let published = match guild.confession_channel_id {
+        Some(channel_id) => match http.get_channel(ChannelId::new(channel_id.parse()?)).await {
+            Ok(Channel::Guild(channel)) => {
+                publish_confession(http, &data.db, &channel, &guild_config, &confession).await
+            }
+            Ok(_) => Err(Box::from("The confession channel is not a guild channel")),
+            Err(e) => Err(Box::from(e)),
+        },
+        None => Err(Box::from("No confession channel has been set")),
+    };
+    let message = match published {
+        Ok(message) => message,
+        Err(e) => {
+            // Keep the confession in review so it can be approved again
+            error!(
+                "Could not post approved confession {}: {}",
+                confession.id, e
+            );
+            data.db.return_confession_to_review(confession.id).await?;
+            return Err(Box::from(format!(
+                "Could not post the confession. Reason: {}",
+                e
+            )));
+        }
+    };
+    info!(
+        "Moderator {} approved confession {}",
+        cmp.user.id, confession.id
+    );
+
+    notify_author(
+        framework,
+        &guild_id,
+        confession.id,
+        CreateEmbed::default()
+            .color(0x00FF00)
+            .title("Confession Approved")
+            .description(format!(
+                "Your confession has been posted here: {}",
+                message.link()
+            )),
+    )
+    .await?;
+
+    cmp.message
+        .clone()
+        .edit(
+            http,
+            reviewed_message(
+                &cmp.message,
+                format!(
+                    "Approved Confession #{}",
+                    confession.number.unwrap_or_default()
+                ),
+                0x00FF00,
+                &cmp.user,
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn reject(
+    framework: FrameworkContext<'_, Data, Error>,
+    review_message: &Message,
+    moderator: &User,
+    confession_id: i32,
+    reason: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = review_message
+        .guild_id
+        .ok_or("Could not get Guild ID for interaction.")?
+        .to_string();
+    let data: Arc<Data> = framework.serenity_context.data();
+
+    if !data.db.reject_confession(&guild_id, confession_id).await? {
+        return Ok(());
+    }
+    info!(
+        "Moderator {} rejected confession {}",
+        moderator.id, confession_id
+    );
+
+    // The review message holds the content, as rejected confessions have no
+    // number to look them up by
+    let content = review_message
+        .embeds
+        .first()
+        .and_then(|e| e.description.as_ref())
+        .map(|d| d.to_string())
+        .unwrap_or_default();
+    let mut embed = CreateEmbed::default()
+        .color(0xFF0000)
+        .title("Confession Rejected")
+        .description("Your confession was not approved by the moderators.")
+        .field("Confession", truncate(&content, 1024), false);
+    if let Some(reason) = &reason {
+        embed = embed.field("Reason", reason.clone(), false);
+    }
+    notify_author(framework, &guild_id, confession_id, embed).await?;
+
+    review_message
+        .clone()
+        .edit(
+            &framework.serenity_context.http,
+            reviewed_message(
+                review_message,
+                match &reason {
+                    Some(reason) => format!("Rejected Confession ({})", truncate(reason, 200)),
+                    None => "Rejected Confession".to_string(),
+                },
+                0xFF0000,
+                moderator,
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// DM the author the outcome of their review. The notice is cleared first, so
+/// the author is told at most once.
+async fn notify_author(
+    framework: FrameworkContext<'_, Data, Error>,
+    guild_id: &String,
+    confession_id: i32,
+    embed: CreateEmbed<'_>,
+) -> Result<(), Error> {
+    let data: Arc<Data> = framework.serenity_context.data();
+    let config = data.config.read().await;
+
+    let sealed = match data.db.take_review_notice(confession_id).await? {
+        Some(sealed) => sealed,
+        None => return Ok(()),
+    };
+    let user_id = match config
+        .escrow_key
+        .open(&review_notice_context(guild_id), &sealed)
+    {
+        Ok(id) => UserId::from_str(&id)?,
+        Err(_) => {
+            error!(
+                "Could not open the review notice for confession {}",
+                confession_id
+            );
+            return Ok(());
+        }
+    };
+    // The author may have DMs closed, which should not undo the review
+    if let Err(e) = user_id
+        .direct_message(
+            &framework.serenity_context.http,
+            CreateMessage::default().embed(embed),
+        )
+        .await
+    {
+        warn!(
+            "Could not notify the author of confession {}: {}",
+            confession_id, e
+        );
+    }
+    Ok(())
+}
+
+fn reviewed_message(
+    message: &Message,
+    title: String,
+    color: u32,
+    moderator: &User,
+) -> EditMessage<'static> {
+    let embed = match message.embeds.first() {
+        Some(embed) => CreateEmbed::from(embed.clone()),
+        None => CreateEmbed::new(),
+    };
+    EditMessage::new()
+        .embed(
+            embed
+                .title(title)
+                .color(color)
+                .footer(CreateEmbedFooter::new(format!(
+                    "Reviewed by {}",
+                    moderator.display_name()
+                ))),
+        )
+        .components(vec![])
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
@@ -1,85 +1,78 @@
-use chrono::{Duration, Local, Utc};
-use confession_bot_rs::establish_connection;
-use diesel::{ExpressionMethods, RunQueryDsl, SelectableHelper, SqliteConnection};
+use std::{sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use confession_bot_rs::{ConfessionStatus, ScheduleStatus};
 use poise::{
-    serenity_prelude::{Timestamp, UserId},
+    serenity_prelude::{ChannelId, GuildId, GuildMemberUpdateEvent, Http, RoleId, UserId},
     ChoiceParameter,
 };
+use tokio::time::sleep;
+use tracing::{error, info, warn};
 
 use crate::{
-    commands::{Context, Error},
-    models::{InsertSchedule, Schedule},
-    schema::schedule,
+    commands::{
+        actions::{apply_timeout, describe_action, run_action},
+        Context, Data, Error,
+    },
+    db_impl::{ConfessionStore, GuildStore, ScheduleStore},
+    models::{InsertSchedule, Schedule, ScheduleAction},
+    time_expr,
 };
 
+/// Attempts at a job before it is kept as a dead letter.
+const MAX_ATTEMPTS: i32 = 5;
+/// Seconds before the first retry of a job, doubled for each retry after.
+const RETRY_BACKOFF: i64 = 30;
+/// The longest the scheduler sleeps without checking for due jobs.
+const MAX_IDLE: StdDuration = StdDuration::from_secs(60 * 60);
+/// How long to wait after the jobs could not be checked at all.
+const ERROR_IDLE: StdDuration = StdDuration::from_secs(30);
+/// Scheduled timeouts shown on each page of `/schedule list`.
+const SCHEDULES_PER_PAGE: usize = 10;
+
 #[derive(Debug, ChoiceParameter, Copy, Clone)]
-enum TimeoutDuration {
-    #[name = "60 seconds"]
-    SECONDS_60,
-    #[name = "5 minutes"]
-    MINS_5,
-    #[name = "10 Minutes"]
-    MINS_10,
-    #[name = "1 Hour"]
-    HOURS_1,
-    #[name = "1 Day"]
-    DAYS_1,
-    #[name = "1 Week"]
-    WEEKS_1,
-}
-
-impl From<TimeoutDuration> for i64 {
-    fn from(value: TimeoutDuration) -> Self {
-        match value {
-            TimeoutDuration::SECONDS_60 => 60,
-            TimeoutDuration::MINS_5 => 300,
-            TimeoutDuration::MINS_10 => 600,
-            TimeoutDuration::HOURS_1 => 3600,
-            TimeoutDuration::DAYS_1 => 3600 * 24,
-            TimeoutDuration::WEEKS_1 => 3600 * 24 * 7,
-        }
-    }
+enum RoleChangeChoice {
+    #[name = "Give"]
+    GIVE,
+    #[name = "Take"]
+    TAKE,
 }
 
-impl From<TimeoutDuration> for &str {
-    fn from(value: TimeoutDuration) -> Self {
-        match value {
-            TimeoutDuration::SECONDS_60 => "60 seconds",
-            TimeoutDuration::MINS_5 => "5 minutes",
-            TimeoutDuration::MINS_10 => "10 minutes",
-            TimeoutDuration::HOURS_1 => "1 hour",
-            TimeoutDuration::DAYS_1 => "1 day",
-            TimeoutDuration::WEEKS_1 => "1 week",
-        }
-    }
+/// Schedule timeouts and other moderation actions, and list, edit or cancel
+/// those already scheduled.
+#[poise::command(
+    slash_command,
+    rename = "schedule",
+    owners_only,
+    subcommands(
+        "create", "role", "lock", "post", "close", "blackout", "list", "cancel", "edit"
+    ),
+    subcommand_required
+)]
+pub async fn schedule(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
 }
 
-#[poise::command(slash_command, ephemeral, rename = "schedule", owners_only)]
-pub async fn schedule_timeout(
+/// Schedule a timeout for a member.
+#[poise::command(slash_command, ephemeral, owners_only)]
+async fn create(
     ctx: Context<'_>,
     #[description = "The user to timeout"] victim: UserId,
-    #[description = "In how long the timeout should begin"] start_in: String,
-    #[description = "How long the timeout should last"] ends_in: TimeoutDuration,
+    #[description = "When the timeout should begin, e.g. 30m, 18:00 or 2024-12-25 18:00"]
+    start_in: String,
+    #[description = "How long the timeout should last, e.g. 1h30m, or when it should end"]
+    ends_in: String,
+    #[description = "Reapply the timeout if it is lifted early (default: no)"] enforce: Option<
+        bool,
+    >,
 ) -> Result<(), Error> {
-    let now = Utc::now();
     let data = ctx.data();
-    let config = data.config.read().await;
-
-    // Check if the string ends with 'h' or 'm'
-    let unit = start_in.chars().last().ok_or("Empty string")?;
-
-    // Parse the number part of the string
-    let number_part = &start_in[..start_in.len() - 1];
-    let value: i64 = number_part.parse().map_err(|_| "Invalid number")?;
-
-    // Calculate the new time based on the unit (hours or minutes)
-    let start_time = match unit {
-        's' => now + Duration::seconds(value),
-        'h' => now + Duration::hours(value),
-        'm' => now + Duration::minutes(value),
-        'd' => now + Duration::days(value),
-        _ => return Err("Invalid time unit".into()),
-    };
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let timezone = data.db.get_guild_config(&guild_id).await?.timezone();
+    let now = Utc::now();
+    let start = time_expr::parse_time(&start_in, now, timezone)?;
+    let length = time_expr::parse_length(&ends_in, start, timezone)?;
+    let (start_at, ends_at) = time_expr::timeout_window(start, length, now)?;
 
     match victim {
         victim if victim == ctx.author().id => {
@@ -89,22 +82,19 @@ pub async fn schedule_timeout(
             return Err(Box::from("You cannot schedule a timeout on the bot!"));
         }
         _ => {
-            let mut connection = establish_connection(&config.db_url);
-            let insert_schedule = InsertSchedule {
-                victim_id: victim.to_string(),
-                guild_id: ctx.guild_id().unwrap().to_string(),
-                ends_at: i32::try_from(Into::<i64>::into(ends_in) + start_time.timestamp())
-                    .unwrap(),
-                start_at: i32::try_from(start_time.timestamp()).unwrap(),
+            let schedule = InsertSchedule {
+                victim_id: Some(victim.to_string()),
+                guild_id,
+                ends_at: Some(ends_at),
+                start_at,
+                enforce: enforce.unwrap_or(false),
+                action: serde_json::to_string(&ScheduleAction::TIMEOUT)?,
             };
-            match diesel::insert_into(schedule::table)
-                .values(&insert_schedule)
-                .returning(Schedule::as_returning())
-                .get_result(&mut connection)
-            {
-                Ok(_) => {
+            match data.db.insert_schedule(schedule).await {
+                Ok(schedule) => {
+                    data.schedules.notify_one();
                     ctx.reply(
-                        format!("Succesfully scheduled a timeout for User <@{}> starting <t:{}:R> lasting for {}", victim.to_string(), start_time.timestamp(), Into::<&str>::into(ends_in))
+                        format!("Succesfully scheduled timeout #{} for User <@{}> starting <t:{}:R> and ending <t:{}:f>", schedule.id, victim.to_string(), start_at, ends_at)
                     ).await?;
                     Ok(())
                 }
@@ -113,3 +103,510 @@ pub async fn schedule_timeout(
         }
     }
 }
+
+/// Schedule giving a role to a member, or taking it away.
+#[poise::command(slash_command, ephemeral, owners_only)]
+async fn role(
+    ctx: Context<'_>,
+    #[description = "Give or take the role"] change: RoleChangeChoice,
+    #[description = "The member to change"] user: UserId,
+    #[description = "The role to give or take"] role: RoleId,
+    #[description = "When to change the role, e.g. 30m, 18:00 or 2024-12-25 18:00"]
+    start_in: String,
+) -> Result<(), Error> {
+    let (start_at, _) = start_and_end(ctx, &start_in, None).await?;
+    let (user_id, role_id) = (user.to_string(), role.to_string());
+    let action = match change {
+        RoleChangeChoice::GIVE => ScheduleAction::GRANT { user_id, role_id },
+        RoleChangeChoice::TAKE => ScheduleAction::REVOKE { user_id, role_id },
+    };
+    let job = insert_job(ctx, start_at, action).await?;
+    ctx.reply(scheduled_message(&job)).await?;
+    Ok(())
+}
+
+/// Schedule locking a channel, and optionally unlocking it again.
+#[poise::command(slash_command, ephemeral, owners_only)]
+async fn lock(
+    ctx: Context<'_>,
+    #[description = "The channel to lock"] channel: ChannelId,
+    #[description = "When to lock it, e.g. 30m, 18:00 or 2024-12-25 18:00"] start_in: String,
+    #[description = "How long to keep it locked, e.g. 2h, or when to unlock it"] ends_in: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let (start_at, ends_at) = start_and_end(ctx, &start_in, ends_in).await?;
+    let channel_id = channel.to_string();
+    let mut reply = scheduled_message(
+        &insert_job(
+            ctx,
+            start_at,
+            ScheduleAction::LOCK {
+                channel_id: channel_id.clone(),
+            },
+        )
+        .await?,
+    );
+    if let Some(ends_at) = ends_at {
+        let unlock = insert_job(ctx, ends_at, ScheduleAction::UNLOCK { channel_id }).await?;
+        reply = format!("{}\n{}", reply, scheduled_message(&unlock));
+    }
+    ctx.reply(reply).await?;
+    Ok(())
+}
+
+/// Schedule a message to be posted in a channel.
+#[poise::command(slash_command, ephemeral, owners_only)]
+async fn post(
+    ctx: Context<'_>,
+    #[description = "The channel to post in"] channel: ChannelId,
+    #[description = "The message to post. Mentions will not ping anyone"]
+    #[max_length = 2000]
+    message: String,
+    #[description = "When to post it, e.g. 30m, 18:00 or 2024-12-25 18:00"] start_in: String,
+) -> Result<(), Error> {
+    let (start_at, _) = start_and_end(ctx, &start_in, None).await?;
+    let job = insert_job(
+        ctx,
+        start_at,
+        ScheduleAction::POST {
+            channel_id: channel.to_string(),
+            content: message,
+        },
+    )
+    .await?;
+    ctx.reply(scheduled_message(&job)).await?;
+    Ok(())
+}
+
+/// Schedule closing the replies to a confession.
+#[poise::command(slash_command, ephemeral, owners_only)]
+async fn close(
+    ctx: Context<'_>,
+    #[description = "The number of the confession"] id: u32,
+    #[description = "When to close its replies, e.g. 30m, 18:00 or 2024-12-25 18:00"]
+    start_in: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let confession = ctx
+        .data()
+        .db
+        .get_confession_by_id_guild(id, &guild_id)
+        .await
+        .map_err(|_| format!("Could not find confession with ID `{}` in the Guild.", id))?;
+    let status: ConfessionStatus = confession.status.clone().into();
+    if status != ConfessionStatus::ACTIVE || confession.message_id.is_empty() {
+        return Err(Box::from(format!(
+            "Confession #{} is not posted, or has been taken down, so has no replies to close.",
+            id
+        )));
+    }
+    // The reply thread is started from the confession's message, so shares its
+    // ID. It is only started by the first reply
+    if ctx
+        .http()
+        .get_channel(ChannelId::new(confession.message_id.parse()?))
+        .await
+        .is_err()
+    {
+        return Err(Box::from(format!(
+            "Confession #{} has no replies to close yet.",
+            id
+        )));
+    }
+    let (start_at, _) = start_and_end(ctx, &start_in, None).await?;
+    let job = insert_job(
+        ctx,
+        start_at,
+        ScheduleAction::CLOSE {
+            confession_number: confession.number.unwrap_or_default(),
+            thread_id: confession.message_id,
+        },
+    )
+    .await?;
+    ctx.reply(scheduled_message(&job)).await?;
+    Ok(())
+}
+
+/// Schedule a confession blackout, during which new confessions are refused.
+#[poise::command(slash_command, ephemeral, owners_only)]
+async fn blackout(
+    ctx: Context<'_>,
+    #[description = "When the blackout should begin, e.g. 30m, 18:00 or 2024-12-25 18:00"]
+    start_in: String,
+    #[description = "How long the blackout should last, e.g. 8h, or when it should end"]
+    ends_in: Option<String>,
+) -> Result<(), Error> {
+    let (start_at, ends_at) = start_and_end(ctx, &start_in, ends_in).await?;
+    let mut reply = scheduled_message(
+        &insert_job(ctx, start_at, ScheduleAction::BLACKOUT { enabled: true }).await?,
+    );
+    if let Some(ends_at) = ends_at {
+        let end = insert_job(ctx, ends_at, ScheduleAction::BLACKOUT { enabled: false }).await?;
+        reply = format!("{}\n{}", reply, scheduled_message(&end));
+    }
+    ctx.reply(reply).await?;
+    Ok(())
+}
+
+/// List the jobs scheduled in this server.
+#[poise::command(slash_command, ephemeral, owners_only)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let now = Utc::now().timestamp();
+
+    let schedules = data.db.get_guild_schedules(&guild_id, now).await?;
+    if schedules.is_empty() {
+        ctx.reply("Nothing is scheduled.").await?;
+        return Ok(());
+    }
+
+    let pages: Vec<String> = schedules
+        .chunks(SCHEDULES_PER_PAGE)
+        .map(|page| {
+            page.iter()
+                .map(|schedule| {
+                    format!(
+                        "**#{}** <t:{}:R>: {}, {}{}",
+                        schedule.id,
+                        schedule.start_at,
+                        describe_action(schedule),
+                        describe_status(schedule),
+                        if schedule.enforce { ", enforced" } else { "" }
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+        })
+        .collect();
+    let pages: Vec<&str> = pages.iter().map(String::as_str).collect();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
+
+/// Cancel a scheduled job. A timeout which is already running is lifted.
+#[poise::command(slash_command, ephemeral, owners_only)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "The ID of the scheduled job"] id: i32,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?;
+
+    let schedule = data
+        .db
+        .cancel_schedule(&guild_id.to_string(), id)
+        .await?
+        .ok_or(format!("Could not find scheduled job #{}.", id))?;
+    info!("Cancelled scheduled job {}", schedule.id);
+
+    let status: ScheduleStatus = schedule.status.clone().into();
+    if status != ScheduleStatus::APPLIED || schedule.action()? != ScheduleAction::TIMEOUT {
+        ctx.reply(format!("Cancelled job #{}.", id)).await?;
+        return Ok(());
+    }
+    let victim_id = schedule.victim_id.clone().unwrap_or_default();
+    // The job is gone either way, so only report a timeout that could not be lifted
+    match lift_timeout(ctx.http(), &schedule).await {
+        Ok(true) => {
+            ctx.reply(format!(
+                "Cancelled timeout #{} and lifted it from <@{}>.",
+                id, victim_id
+            ))
+            .await?;
+        }
+        Ok(false) => {
+            ctx.reply(format!(
+                "Cancelled timeout #{}. The timeout of <@{}> was changed since, so it was left alone.",
+                id, victim_id
+            ))
+            .await?;
+        }
+        Err(e) => {
+            warn!("Could not lift the timeout of scheduled job {}: {}", id, e);
+            ctx.reply(format!(
+                "Cancelled timeout #{}, but could not lift it from <@{}>: {}",
+                id, victim_id, e
+            ))
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Change when a scheduled job runs, or how long a timeout lasts.
+#[poise::command(slash_command, ephemeral, owners_only)]
+async fn edit(
+    ctx: Context<'_>,
+    #[description = "The ID of the scheduled job"] id: i32,
+    #[description = "When the job should run, e.g. 30m, 18:00 or 2024-12-25 18:00"]
+    start_in: Option<String>,
+    #[description = "How long a timeout should last, e.g. 1h30m, or when it should end"]
+    ends_in: Option<String>,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let timezone = data.db.get_guild_config(&guild_id).await?.timezone();
+    let now = Utc::now();
+
+    let schedule = data
+        .db
+        .get_guild_schedules(&guild_id, now.timestamp())
+        .await?
+        .into_iter()
+        .find(|schedule| schedule.id == id)
+        .ok_or(format!("Could not find scheduled job #{}.", id))?;
+    let started = format!("Job #{} has already run, cancel it instead.", id);
+    if Into::<ScheduleStatus>::into(schedule.status.clone()) == ScheduleStatus::APPLIED {
+        return Err(Box::from(started));
+    }
+
+    let start = match start_in {
+        Some(start_in) => time_expr::parse_time(&start_in, now, timezone)?,
+        None => DateTime::from_timestamp(schedule.start_at, 0).ok_or("Invalid start time")?,
+    };
+    let (start_at, ends_at) = match (schedule.action()?, schedule.ends_at) {
+        (ScheduleAction::TIMEOUT, Some(old_ends_at)) => {
+            // Keep the timeout as long as it was unless told otherwise
+            let length = match ends_in {
+                Some(ends_in) => time_expr::parse_length(&ends_in, start, timezone)?,
+                None => TimeDelta::seconds(old_ends_at - schedule.start_at),
+            };
+            let (start_at, ends_at) = time_expr::timeout_window(start, length, now)?;
+            (start_at, Some(ends_at))
+        }
+        _ if ends_in.is_some() => {
+            return Err(Box::from(
+                "Only timeouts have an end. Schedule another job to undo this one.",
+            ))
+        }
+        _ => (time_expr::check_start(start, now)?, None),
+    };
+
+    let schedule = data
+        .db
+        .update_schedule(&guild_id, id, start_at, ends_at)
+        .await?
+        .ok_or(started)?;
+    data.schedules.notify_one();
+    info!("Edited scheduled job {}", schedule.id);
+
+    ctx.reply(format!(
+        "Job #{} now runs <t:{}:R>: {}",
+        schedule.id,
+        schedule.start_at,
+        describe_action(&schedule)
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Read when a job should run, and optionally when it should be undone, in
+/// the guild's timezone.
+async fn start_and_end(
+    ctx: Context<'_>,
+    start_in: &str,
+    ends_in: Option<String>,
+) -> Result<(i64, Option<i64>), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in a guild")?.to_string();
+    let timezone = ctx.data().db.get_guild_config(&guild_id).await?.timezone();
+    let now = Utc::now();
+    let start = time_expr::parse_time(start_in, now, timezone)?;
+    let start_at = time_expr::check_start(start, now)?;
+    let ends_at = match ends_in {
+        Some(ends_in) => {
+            let length = time_expr::parse_length(&ends_in, start, timezone)?;
+            if length <= TimeDelta::zero() {
+                return Err(Box::from("The end must be after the start."));
+            }
+            Some(start_at + length.num_seconds())
+        }
+        None => None,
+    };
+    Ok((start_at, ends_at))
+}
+
+/// Store a job other than a timeout, and wake the scheduler for it.
+async fn insert_job(
+    ctx: Context<'_>,
+    start_at: i64,
+    action: ScheduleAction,
+) -> Result<Schedule, Error> {
+    let data = ctx.data();
+    let schedule = data
+        .db
+        .insert_schedule(InsertSchedule {
+            guild_id: ctx.guild_id().ok_or("Not in a guild")?.to_string(),
+            victim_id: None,
+            ends_at: None,
+            start_at,
+            enforce: false,
+            action: serde_json::to_string(&action)?,
+        })
+        .await?;
+    data.schedules.notify_one();
+    info!("Scheduled job {}", schedule.id);
+    Ok(schedule)
+}
+
+fn scheduled_message(job: &Schedule) -> String {
+    format!(
+        "Scheduled job #{} <t:{}:R>: {}",
+        job.id,
+        job.start_at,
+        describe_action(job)
+    )
+}
+
+/// How a scheduled job is getting on, for `/schedule list`.
+fn describe_status(schedule: &Schedule) -> String {
+    let status: ScheduleStatus = schedule.status.clone().into();
+    match status {
+        ScheduleStatus::PENDING if schedule.attempts > 0 => {
+            format!("retrying after {} failed attempts", schedule.attempts)
+        }
+        ScheduleStatus::PENDING => "waiting".to_string(),
+        ScheduleStatus::APPLIED => match schedule.reapplied_at {
+            Some(reapplied_at) => format!(
+                "running, put back after being lifted early ({}x, last <t:{}:R>)",
+                schedule.reapplied, reapplied_at
+            ),
+            None => "running".to_string(),
+        },
+        ScheduleStatus::DEAD => format!(
+            "failed: {}",
+            schedule.last_error.as_deref().unwrap_or("unknown error")
+        ),
+    }
+}
+
+/// Lift the timeout a job applied, unless it has since been changed by hand.
+/// # Returns
+/// Whether the timeout was lifted.
+async fn lift_timeout(http: &Http, job: &Schedule) -> Result<bool, Error> {
+    let guild_id = GuildId::new(job.guild_id.parse()?);
+    let victim_id = UserId::new(
+        job.victim_id
+            .as_deref()
+            .ok_or("No member timed out")?
+            .parse()?,
+    );
+
+    let mut member = http.get_member(guild_id, victim_id).await?;
+    match member.communication_disabled_until {
+        Some(until) if Some(until.unix_timestamp()) == job.ends_at => {
+            member.enable_communication(http).await?;
+            info!("Enabled communication for member with ID: {}", victim_id);
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Run scheduled jobs as they become due. Started once for the whole bot, so
+/// reconnects cannot run a job twice, and picks up anything left from before
+/// a restart. Between jobs it sleeps until the next is due, or until
+/// [`Data::schedules`] is notified of a new one.
+pub async fn run_schedules(http: Arc<Http>, data: Arc<Data>) {
+    loop {
+        let idle = match run_due(&http, &data).await {
+            Ok(idle) => idle,
+            Err(e) => {
+                error!("Could not check scheduled jobs: {}", e);
+                ERROR_IDLE
+            }
+        };
+        tokio::select! {
+            _ = sleep(idle) => {}
+            _ = data.schedules.notified() => {}
+        }
+    }
+}
+
+/// Run every due job. Failed jobs are retried with backoff until they run out
+/// of attempts.
+/// # Returns
+/// How long until the next job is due.
+async fn run_due(http: &Http, data: &Data) -> Result<StdDuration, Error> {
+    let now = Utc::now().timestamp();
+    for job in data.db.get_due_schedules(now).await? {
+        // Retrying could not help, so it goes straight to the dead letters
+        if job.ends_at.is_some_and(|ends_at| ends_at <= now) {
+            warn!("Scheduled job {} is past its end, giving up", job.id);
+            data.db
+                .fail_schedule(
+                    job.id,
+                    &"The timeout ended before it could be applied.".to_string(),
+                    None,
+                )
+                .await?;
+            continue;
+        }
+        match run_action(http, data, &job).await {
+            Ok(()) => {
+                info!("Ran scheduled job {}", job.id);
+                data.db.complete_schedule(job.id).await?;
+            }
+            Err(e) => {
+                let retry_at = (job.attempts + 1 < MAX_ATTEMPTS)
+                    .then(|| now + (RETRY_BACKOFF << job.attempts));
+                match retry_at {
+                    Some(retry_at) => warn!(
+                        "Scheduled job {} failed, retrying in {}s: {}",
+                        job.id,
+                        retry_at - now,
+                        e
+                    ),
+                    None => error!(
+                        "Scheduled job {} failed {} times, giving up: {}",
+                        job.id, MAX_ATTEMPTS, e
+                    ),
+                }
+                data.db
+                    .fail_schedule(job.id, &e.to_string(), retry_at)
+                    .await?;
+            }
+        }
+    }
+
+    let now = Utc::now().timestamp();
+    Ok(match data.db.next_schedule_at().await? {
+        Some(run_at) => StdDuration::from_secs((run_at - now).max(0) as u64).min(MAX_IDLE),
+        None => MAX_IDLE,
+    })
+}
+
+/// Reapply an enforced timeout which was lifted or shortened before its end,
+/// whether by a moderator or by Discord.
+pub async fn enforce_timeout(
+    http: &Http,
+    data: &Data,
+    event: &GuildMemberUpdateEvent,
+) -> Result<(), Error> {
+    let now = Utc::now().timestamp();
+    let job = match data
+        .db
+        .get_enforced_schedule(&event.guild_id.to_string(), &event.user.id.to_string(), now)
+        .await?
+    {
+        Some(job) => job,
+        None => return Ok(()),
+    };
+    let ends_at = job.ends_at.unwrap_or_default();
+    // Our own reapplication comes back through here, and then ends in time
+    if event
+        .communication_disabled_until
+        .is_some_and(|until| until.unix_timestamp() >= ends_at)
+    {
+        return Ok(());
+    }
+
+    apply_timeout(http, &job).await?;
+    data.db.reapply_schedule(job.id, now).await?;
+    warn!(
+        "Reapplied scheduled job {} to member {} in guild {}, its timeout was lifted before <t:{}>",
+        job.id, event.user.id, event.guild_id, ends_at
+    );
+    Ok(())
+}
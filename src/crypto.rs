@@ -0,0 +1,336 @@
+use ring::{
+    aead,
+    digest::{digest, Context, SHA256},
+    error::Unspecified,
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
+/// Secret keys used to derive author pseudonyms.
+///
+/// Each guild gets its own HMAC key, derived from the bot-wide secret, so the
+/// same user produces unrelated pseudonyms in different guilds. The secret is
+/// rotated by moving the old value into `AUTHOR_KEY_PREVIOUS` and setting a new
+/// `AUTHOR_KEY`; rows made with the previous key are re-keyed the next time
+/// their author is seen.
+#[derive(Clone)]
+pub struct AuthorKeys {
+    current: hmac::Key,
+    current_id: String,
+    previous: Option<hmac::Key>,
+}
+
+impl AuthorKeys {
+    pub fn new(current: &[u8], previous: Option<&[u8]>) -> Self {
+        let current = hmac::Key::new(hmac::HMAC_SHA256, current);
+        let current_id = to_hex(&hmac::sign(&current, b"key-id").as_ref()[..4]);
+        Self {
+            current,
+            current_id,
+            previous: previous.map(|key| hmac::Key::new(hmac::HMAC_SHA256, key)),
+        }
+    }
+
+    /// A short, non-secret identifier for the current key, stored beside each
+    /// pseudonym so stale rows can be found after a rotation.
+    pub fn key_id(&self) -> &str {
+        &self.current_id
+    }
+
+    /// The pseudonym for `user_id` within `guild_id` under the current key.
+    pub fn pseudonym(&self, guild_id: &str, user_id: &str) -> String {
+        pseudonym_with(&self.current, guild_id, user_id)
+    }
+
+    /// The pseudonym for `user_id` within `guild_id` under the previous key, if
+    /// a rotation is in progress.
+    pub fn previous_pseudonym(&self, guild_id: &str, user_id: &str) -> Option<String> {
+        self.previous
+            .as_ref()
+            .map(|key| pseudonym_with(key, guild_id, user_id))
+    }
+}
+
+fn pseudonym_with(master: &hmac::Key, guild_id: &str, user_id: &str) -> String {
+    let guild_key = hmac::Key::new(
+        hmac::HMAC_SHA256,
+        hmac::sign(master, format!("guild:{}", guild_id).as_bytes()).as_ref(),
+    );
+    to_hex(hmac::sign(&guild_key, user_id.as_bytes()).as_ref())
+}
+
+/// Hashes written before pseudonyms were keyed. Authors were stored as a plain
+/// SHA-256 of their user ID, and voters as a SHA-256 of that hash.
+pub fn legacy_hashes(user_id: &str) -> [String; 2] {
+    let once = sha256(user_id);
+    let twice = sha256(&once);
+    [once, twice]
+}
+
+fn sha256(value: &str) -> String {
+    let mut context = Context::new(&SHA256);
+    context.update(value.as_bytes());
+    format!("{:X?}", context.finish())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Bot-held key used to escrow the author of a confession, so an expose can
+/// recover them without searching the guild's member list.
+#[derive(Clone)]
+pub struct EscrowKey {
+    key: aead::LessSafeKey,
+}
+
+impl EscrowKey {
+    pub fn new(key: &[u8]) -> Result<Self, Unspecified> {
+        Ok(Self {
+            key: aead::LessSafeKey::new(aead::UnboundKey::new(&aead::CHACHA20_POLY1305, key)?),
+        })
+    }
+
+    /// Encrypt `plaintext`, binding it to `context` (usually the guild ID) so
+    /// it cannot be replayed elsewhere.
+    pub fn seal(&self, context: &str, plaintext: &str) -> Result<String, Unspecified> {
+        seal_with(&self.key, context, plaintext.as_bytes())
+    }
+
+    pub fn open(&self, context: &str, sealed: &str) -> Result<String, Unspecified> {
+        String::from_utf8(open_with(&self.key, context, sealed)?).map_err(|_| Unspecified)
+    }
+}
+
+/// Generate a random key suitable for [`EscrowKey::new`].
+pub fn random_key() -> Result<[u8; 32], Unspecified> {
+    let mut key = [0u8; 32];
+    SystemRandom::new().fill(&mut key)?;
+    Ok(key)
+}
+
+fn seal_with(
+    key: &aead::LessSafeKey,
+    context: &str,
+    plaintext: &[u8],
+) -> Result<String, Unspecified> {
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce)?;
+    let mut in_out = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        aead::Nonce::assume_unique_for_key(nonce),
+        aead::Aad::from(context.as_bytes()),
+        &mut in_out,
+    )?;
+    Ok(format!("{}{}", to_hex(&nonce), to_hex(&in_out)))
+}
+
+fn open_with(key: &aead::LessSafeKey, context: &str, sealed: &str) -> Result<Vec<u8>, Unspecified> {
+    let sealed = from_hex(sealed).ok_or(Unspecified)?;
+    if sealed.len() < aead::NONCE_LEN {
+        return Err(Unspecified);
+    }
+    let (nonce, ciphertext) = sealed.split_at(aead::NONCE_LEN);
+    let mut in_out = ciphertext.to_vec();
+    let plaintext = key.open_in_place(
+        aead::Nonce::try_assume_unique_for_key(nonce)?,
+        aead::Aad::from(context.as_bytes()),
+        &mut in_out,
+    )?;
+    Ok(plaintext.to_vec())
+}
+
+/// Split `secret` into `count` shares, any `threshold` of which can rebuild it
+/// with [`combine_shares`]. Each share is returned with its x-coordinate, which
+/// is never zero.
+pub fn split_secret(
+    secret: &[u8],
+    threshold: u8,
+    count: u8,
+) -> Result<Vec<(u8, Vec<u8>)>, Unspecified> {
+    if threshold == 0 || threshold > count {
+        return Err(Unspecified);
+    }
+    let rng = SystemRandom::new();
+    let mut shares: Vec<(u8, Vec<u8>)> = (1..=count)
+        .map(|x| (x, Vec::with_capacity(secret.len())))
+        .collect();
+    let mut coefficients = vec![0u8; threshold as usize];
+    for byte in secret {
+        coefficients[0] = *byte;
+        rng.fill(&mut coefficients[1..])?;
+        for (x, share) in shares.iter_mut() {
+            // Horner's method, highest coefficient first
+            let y = coefficients
+                .iter()
+                .rev()
+                .fold(0u8, |acc, c| gf_mul(acc, *x) ^ c);
+            share.push(y);
+        }
+    }
+    Ok(shares)
+}
+
+/// Rebuild a secret from at least `threshold` shares made by [`split_secret`].
+/// Passing fewer shares yields an unrelated value rather than an error.
+pub fn combine_shares(shares: &[(u8, Vec<u8>)]) -> Vec<u8> {
+    let length = shares.first().map(|(_, s)| s.len()).unwrap_or(0);
+    (0..length)
+        .map(|i| {
+            // Lagrange interpolation at x = 0
+            shares.iter().fold(0u8, |acc, (xj, share)| {
+                let basis = shares
+                    .iter()
+                    .filter(|(xm, _)| xm != xj)
+                    .fold(1u8, |b, (xm, _)| gf_mul(b, gf_div(*xm, xm ^ xj)));
+                acc ^ gf_mul(share[i], basis)
+            })
+        })
+        .collect()
+}
+
+/// A share as handed to its moderator: its x-coordinate followed by its bytes,
+/// hex encoded.
+pub fn share_token(index: u8, share: &[u8]) -> String {
+    let mut bytes = Vec::with_capacity(share.len() + 1);
+    bytes.push(index);
+    bytes.extend_from_slice(share);
+    to_hex(&bytes)
+}
+
+/// Read a share handed back by its moderator.
+pub fn parse_share_token(token: &str) -> Option<(u8, Vec<u8>)> {
+    let bytes = from_hex(token.trim())?;
+    match bytes.split_first() {
+        Some((&index, share)) if index != 0 && !share.is_empty() => Some((index, share.to_vec())),
+        _ => None,
+    }
+}
+
+/// What is kept of a share, so one handed back can be checked without the bot
+/// ever holding enough to rebuild the key itself.
+pub fn share_hash(index: u8, share: &[u8]) -> String {
+    let mut bytes = Vec::with_capacity(share.len() + 1);
+    bytes.push(index);
+    bytes.extend_from_slice(share);
+    to_hex(digest(&SHA256, &bytes).as_ref())
+}
+
+/// Multiplication in GF(2^8) with the AES reduction polynomial.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    // b^254 is the inverse of b, as every non-zero element has order 255
+    let mut inverse = 1u8;
+    for _ in 0..254 {
+        inverse = gf_mul(inverse, b);
+    }
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gf_mul_matches_known_products() {
+        // The worked examples from FIPS 197
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+        for a in 0..=255u8 {
+            assert_eq!(gf_mul(a, 0), 0);
+            assert_eq!(gf_mul(a, 1), a);
+            for b in 0..=255u8 {
+                assert_eq!(gf_mul(a, b), gf_mul(b, a));
+            }
+        }
+    }
+
+    #[test]
+    fn gf_div_undoes_gf_mul() {
+        for a in 0..=255u8 {
+            for b in 1..=255u8 {
+                assert_eq!(gf_div(gf_mul(a, b), b), a);
+            }
+        }
+    }
+
+    #[test]
+    fn split_secret_refuses_impossible_thresholds() {
+        assert!(split_secret(b"secret", 0, 3).is_err());
+        assert!(split_secret(b"secret", 4, 3).is_err());
+    }
+
+    #[test]
+    fn every_threshold_round_trips() {
+        let secret = random_key().unwrap();
+        for count in 1..=8u8 {
+            for threshold in 1..=count {
+                let shares = split_secret(&secret, threshold, count).unwrap();
+                assert_eq!(shares.len(), count as usize);
+                assert!(shares.iter().all(|(x, share)| *x != 0 && share.len() == 32));
+                assert_eq!(combine_shares(&shares), secret);
+                // Any run of `threshold` shares will do, in any order
+                for start in 0..=(count - threshold) as usize {
+                    let mut some = shares[start..start + threshold as usize].to_vec();
+                    assert_eq!(combine_shares(&some), secret);
+                    some.reverse();
+                    assert_eq!(combine_shares(&some), secret);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn one_share_short_does_not_rebuild() {
+        let secret = random_key().unwrap();
+        for threshold in 2..=8u8 {
+            let shares = split_secret(&secret, threshold, 8).unwrap();
+            for start in 0..=(9 - threshold) as usize {
+                let short = &shares[start..start + threshold as usize - 1];
+                assert_ne!(combine_shares(short), secret);
+            }
+        }
+    }
+
+    #[test]
+    fn share_tokens_round_trip() {
+        let shares = split_secret(&random_key().unwrap(), 2, 3).unwrap();
+        for (index, share) in &shares {
+            let token = share_token(*index, share);
+            assert_eq!(
+                parse_share_token(&format!(" {} ", token.to_uppercase())),
+                Some((*index, share.clone()))
+            );
+            assert_eq!(share_hash(*index, share).len(), 64);
+            assert_ne!(share_hash(*index, share), token);
+        }
+        assert_eq!(parse_share_token("00aabb"), None);
+        assert_eq!(parse_share_token("01"), None);
+        assert_eq!(parse_share_token("not hex"), None);
+    }
+}
@@ -0,0 +1,1311 @@
+use std::sync::{Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use confession_bot_rs::{crypto::AuthorKeys, ConfessionStatus, DbError, ScheduleStatus, VoteType};
+
+use crate::{
+    db_impl::{
+        default_guild_config, post_horizon, post_retry_at, vote_minimum, AttachmentStore,
+        AuthorEscrow, AuthorStore, BanStore, ConfessionStore, CooldownStore, FilterStore,
+        GuildStore, OutboxStore, ReplyStore, RevealStore, RolePingClaim, ScheduleStore,
+        VoteOutcome, VoteStore,
+    },
+    models::{
+        Author, Confession, ConfessionDraft, FilterHit, Guild, GuildConfig, InsertSchedule,
+        NewAttachment, NewFilterHit, OutboxEntry, PostLimits, Reply, RevealShare, Schedule,
+        StoredAttachment, Vote,
+    },
+};
+
+/// A backend which keeps everything in memory, for tests and local runs. All
+/// data is lost when the bot stops.
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+}
+
+#[derive(Default)]
+struct Tables {
+    last_id: i32,
+    authors: Vec<Author>,
+    guilds: Vec<Guild>,
+    confessions: Vec<Confession>,
+    votes: Vec<Vote>,
+    replies: Vec<Reply>,
+    reveal_shares: Vec<RevealShare>,
+    /// `(confession_id, moderator_id, share)` in the order approvals were
+    /// made. The share is cleared once the author is revealed.
+    reveal_approvals: Vec<(i32, String, Option<String>)>,
+    schedules: Vec<Schedule>,
+    filter_hits: Vec<FilterHit>,
+    /// `(author_id, source, timestamp)` of recent posts, oldest first.
+    author_posts: Vec<(i32, String, NaiveDateTime)>,
+    /// `(guild_id, author_id, expires_at)`, one per banned author.
+    author_bans: Vec<(String, i32, Option<NaiveDateTime>)>,
+    outbox: Vec<OutboxEntry>,
+    attachments: Vec<StoredAttachment>,
+    /// `(confession_id, title, content_warning, content)` before each edit,
+    /// oldest first.
+    confession_revisions: Vec<(i32, Option<String>, Option<String>, String)>,
+}
+
+impl Tables {
+    /// Row IDs are shared between tables, which is enough to keep them unique
+    /// within each.
+    fn next_id(&mut self) -> i32 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn confession_by_message_id(
+        &self,
+        message_id: &String,
+        guild_id: &String,
+    ) -> Result<&Confession, DbError> {
+        self.confessions
+            .iter()
+            .find(|c| &c.message_id == message_id && &c.guild_id == guild_id)
+            .ok_or_else(|| Box::from(format!("No confession for message {}", message_id)))
+    }
+
+    fn insert_attachment(
+        &mut self,
+        guild_id: &String,
+        confession_id: Option<i32>,
+        reply_id: Option<i32>,
+        attachment: &NewAttachment,
+    ) {
+        let id = self.next_id();
+        self.attachments.push(StoredAttachment {
+            id,
+            guild_id: guild_id.clone(),
+            confession_id,
+            reply_id,
+            filename: attachment.filename.clone(),
+            content_type: attachment.content_type.clone(),
+            data: attachment.data.clone(),
+            timestamp: Utc::now().naive_utc(),
+        });
+    }
+
+    fn next_number(&self, guild_id: &String) -> i32 {
+        self.confessions
+            .iter()
+            .filter(|c| &c.guild_id == guild_id)
+            .filter_map(|c| c.number)
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    fn pending_confession(
+        &mut self,
+        guild_id: &String,
+        confession_id: i32,
+    ) -> Option<&mut Confession> {
+        let pending: String = ConfessionStatus::PENDING.into();
+        self.confessions
+            .iter_mut()
+            .find(|c| c.id == confession_id && &c.guild_id == guild_id && c.status == pending)
+    }
+
+    fn guild_config(&self, guild_id: &String) -> Result<GuildConfig, DbError> {
+        match self.guilds.iter().find(|g| &g.guild_id == guild_id) {
+            Some(guild) => Ok(serde_json::from_str::<GuildConfig>(&guild.config)?),
+            None => Err(Box::from(format!(
+                "Could not find a guild with Guild ID: {}",
+                guild_id
+            ))),
+        }
+    }
+}
+
+impl MemoryStore {
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        // A panic while holding the lock leaves the tables usable, as every
+        // write below is a single push or field update
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl AttachmentStore for MemoryStore {
+    async fn get_confession_attachment(
+        &self,
+        confession_id: i32,
+    ) -> Result<Option<StoredAttachment>, DbError> {
+        Ok(self
+            .tables()
+            .attachments
+            .iter()
+            .find(|a| a.confession_id == Some(confession_id))
+            .cloned())
+    }
+
+    async fn delete_attachments(&self, confession_id: i32) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        let reply_ids: Vec<i32> = tables
+            .replies
+            .iter()
+            .filter(|r| r.original_confession_id == confession_id)
+            .map(|r| r.id)
+            .collect();
+        tables.attachments.retain(|a| {
+            a.confession_id != Some(confession_id)
+                && !a.reply_id.is_some_and(|id| reply_ids.contains(&id))
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuthorStore for MemoryStore {
+    async fn insert_author(
+        &self,
+        keys: &AuthorKeys,
+        guild_id: &String,
+        user_id: &String,
+    ) -> Result<i32, DbError> {
+        let hash = keys.pseudonym(guild_id, user_id);
+        let previous = keys.previous_pseudonym(guild_id, user_id);
+        let mut tables = self.tables();
+
+        if let Some(author) = tables.authors.iter().find(|a| a.hash == hash) {
+            return Ok(author.id);
+        }
+        if let Some(author) = tables
+            .authors
+            .iter_mut()
+            .find(|a| Some(&a.hash) == previous.as_ref())
+        {
+            author.hash = hash;
+            author.key_id = Some(keys.key_id().to_string());
+            return Ok(author.id);
+        }
+
+        let id = tables.next_id();
+        tables.authors.push(Author {
+            id,
+            hash,
+            guild_id: Some(guild_id.clone()),
+            key_id: Some(keys.key_id().to_string()),
+        });
+        Ok(id)
+    }
+
+    async fn count_stale_authors(&self, key_id: &String) -> Result<i64, DbError> {
+        Ok(self
+            .tables()
+            .authors
+            .iter()
+            .filter(|a| a.key_id.as_ref() != Some(key_id))
+            .count() as i64)
+    }
+}
+
+#[async_trait]
+impl BanStore for MemoryStore {
+    async fn ban_author(
+        &self,
+        guild_id: &String,
+        author_id: i32,
+        _moderator_id: &String,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        tables
+            .author_bans
+            .retain(|(g, a, _)| !(g == guild_id && *a == author_id));
+        tables
+            .author_bans
+            .push((guild_id.clone(), author_id, expires_at));
+        Ok(())
+    }
+
+    async fn unban_author(&self, guild_id: &String, author_id: i32) -> Result<bool, DbError> {
+        let mut tables = self.tables();
+        let before = tables.author_bans.len();
+        tables
+            .author_bans
+            .retain(|(g, a, _)| !(g == guild_id && *a == author_id));
+        Ok(tables.author_bans.len() < before)
+    }
+
+    async fn is_author_banned(&self, guild_id: &String, author_id: i32) -> Result<bool, DbError> {
+        let now = Utc::now().naive_utc();
+        Ok(self.tables().author_bans.iter().any(|(g, a, expires_at)| {
+            g == guild_id && *a == author_id && expires_at.is_none_or(|e| e > now)
+        }))
+    }
+}
+
+#[async_trait]
+impl ConfessionStore for MemoryStore {
+    async fn get_confession_by_id_guild(
+        &self,
+        confession_id: u32,
+        guild_id: &String,
+    ) -> Result<Confession, DbError> {
+        self.tables()
+            .confessions
+            .iter()
+            .find(|c| &c.guild_id == guild_id && c.number == Some(confession_id as i32))
+            .cloned()
+            .ok_or_else(|| Box::from(format!("No confession #{}", confession_id)))
+    }
+
+    async fn get_confession_by_message_id(
+        &self,
+        message_id: &String,
+        guild_id: &String,
+    ) -> Result<Confession, DbError> {
+        self.tables()
+            .confession_by_message_id(message_id, guild_id)
+            .cloned()
+    }
+
+    async fn insert_confession(
+        &self,
+        keys: &AuthorKeys,
+        escrow: &AuthorEscrow,
+        author_id: &String,
+        guild_id: &String,
+        draft: &ConfessionDraft,
+        review: bool,
+        release_at: Option<NaiveDateTime>,
+    ) -> Result<Confession, DbError> {
+        let author = self.insert_author(keys, guild_id, author_id).await?;
+        let escrow = escrow.clone();
+
+        let mut tables = self.tables();
+        let release_at = release_at.filter(|_| !review);
+        let (number, status) = match (review, release_at) {
+            (true, _) => (None, ConfessionStatus::PENDING),
+            (false, Some(_)) => (None, ConfessionStatus::QUEUED),
+            (false, None) => (Some(tables.next_number(guild_id)), ConfessionStatus::ACTIVE),
+        };
+        let confession = Confession {
+            id: tables.next_id(),
+            guild_id: guild_id.clone(),
+            message_id: String::new(),
+            content: draft.content.clone(),
+            author,
+            timestamp: Utc::now().naive_utc(),
+            author_escrow: Some(escrow.sealed),
+            reveal_threshold: escrow.threshold.map(i32::from),
+            number,
+            status: status.into(),
+            status_changed_at: None,
+            review_notice: escrow.review_notice,
+            title: draft.title.clone(),
+            content_warning: draft.content_warning.clone(),
+            skip_role_ping: draft.skip_role_ping,
+            channel_id: None,
+        };
+        for share in escrow.shares {
+            let id = tables.next_id();
+            tables.reveal_shares.push(RevealShare {
+                id,
+                confession_id: confession.id,
+                share_index: share.index as i32,
+                share_hash: share.hash(),
+                moderator_id: share.moderator_id,
+            });
+        }
+        if let Some(attachment) = &draft.attachment {
+            tables.insert_attachment(guild_id, Some(confession.id), None, attachment);
+        }
+        if let Some(release_at) = release_at {
+            let id = tables.next_id();
+            tables.outbox.push(OutboxEntry {
+                id,
+                confession_id: confession.id,
+                guild_id: guild_id.clone(),
+                release_at,
+                attempts: 0,
+                last_error: None,
+                dead: false,
+            });
+        }
+        tables.confessions.push(confession.clone());
+        Ok(confession)
+    }
+
+    async fn approve_confession(
+        &self,
+        guild_id: &String,
+        confession_id: i32,
+    ) -> Result<Option<Confession>, DbError> {
+        let mut tables = self.tables();
+        let number = tables.next_number(guild_id);
+        Ok(tables
+            .pending_confession(guild_id, confession_id)
+            .map(|confession| {
+                confession.status = ConfessionStatus::ACTIVE.into();
+                confession.status_changed_at = Some(Utc::now().naive_utc());
+                confession.number = Some(number);
+                confession.clone()
+            }))
+    }
+
+    async fn reject_confession(
+        &self,
+        guild_id: &String,
+        confession_id: i32,
+    ) -> Result<bool, DbError> {
+        let mut tables = self.tables();
+        Ok(tables
+            .pending_confession(guild_id, confession_id)
+            .map(|confession| {
+                confession.status = ConfessionStatus::REJECTED.into();
+                confession.status_changed_at = Some(Utc::now().naive_utc());
+            })
+            .is_some())
+    }
+
+    async fn return_confession_to_review(&self, confession_id: i32) -> Result<(), DbError> {
+        if let Some(confession) = self
+            .tables()
+            .confessions
+            .iter_mut()
+            .find(|c| c.id == confession_id)
+        {
+            confession.status = ConfessionStatus::PENDING.into();
+            confession.status_changed_at = None;
+            confession.number = None;
+        }
+        Ok(())
+    }
+
+    async fn take_review_notice(&self, confession_id: i32) -> Result<Option<String>, DbError> {
+        Ok(self
+            .tables()
+            .confessions
+            .iter_mut()
+            .find(|c| c.id == confession_id)
+            .and_then(|c| c.review_notice.take()))
+    }
+
+    async fn set_confession_message(
+        &self,
+        confession_id: i32,
+        channel_id: &String,
+        message_id: &String,
+    ) -> Result<(), DbError> {
+        if let Some(confession) = self
+            .tables()
+            .confessions
+            .iter_mut()
+            .find(|c| c.id == confession_id)
+        {
+            confession.channel_id = Some(channel_id.clone());
+            confession.message_id = message_id.clone();
+        }
+        Ok(())
+    }
+
+    async fn delete_confession(&self, confession_id: i32) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        tables
+            .reveal_shares
+            .retain(|s| s.confession_id != confession_id);
+        tables.outbox.retain(|e| e.confession_id != confession_id);
+        tables
+            .attachments
+            .retain(|a| a.confession_id != Some(confession_id));
+        tables.confessions.retain(|c| c.id != confession_id);
+        Ok(())
+    }
+
+    async fn retract_confession(&self, confession_id: i32) -> Result<bool, DbError> {
+        let active: String = ConfessionStatus::ACTIVE.into();
+        let mut tables = self.tables();
+        match tables
+            .confessions
+            .iter_mut()
+            .find(|c| c.id == confession_id && c.status == active)
+        {
+            Some(confession) => {
+                confession.status = ConfessionStatus::DELETED.into();
+                confession.status_changed_at = Some(Utc::now().naive_utc());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn edit_confession(
+        &self,
+        confession_id: i32,
+        draft: &ConfessionDraft,
+    ) -> Result<Option<Confession>, DbError> {
+        let active: String = ConfessionStatus::ACTIVE.into();
+        let mut tables = self.tables();
+        let confession = match tables
+            .confessions
+            .iter_mut()
+            .find(|c| c.id == confession_id && c.status == active)
+        {
+            Some(confession) => confession,
+            None => return Ok(None),
+        };
+        let revision = (
+            confession_id,
+            confession.title.clone(),
+            confession.content_warning.clone(),
+            confession.content.clone(),
+        );
+        confession.title = draft.title.clone();
+        confession.content_warning = draft.content_warning.clone();
+        confession.content = draft.content.clone();
+        let edited = confession.clone();
+        tables.confession_revisions.push(revision);
+        Ok(Some(edited))
+    }
+
+    async fn undo_confession_edit(&self, edited: &Confession) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        let unchanged = tables.confessions.iter().any(|c| {
+            c.id == edited.id
+                && (&c.title, &c.content_warning, &c.content)
+                    == (&edited.title, &edited.content_warning, &edited.content)
+        });
+        let position = tables
+            .confession_revisions
+            .iter()
+            .rposition(|(id, ..)| *id == edited.id);
+        let (_, title, content_warning, content) = match (unchanged, position) {
+            (true, Some(position)) => tables.confession_revisions.remove(position),
+            _ => return Ok(()),
+        };
+        if let Some(confession) = tables.confessions.iter_mut().find(|c| c.id == edited.id) {
+            confession.title = title;
+            confession.content_warning = content_warning;
+            confession.content = content;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CooldownStore for MemoryStore {
+    async fn try_record_post(
+        &self,
+        _guild_id: &String,
+        author_id: i32,
+        source: &str,
+        limits: PostLimits,
+    ) -> Result<Option<NaiveDateTime>, DbError> {
+        let now = Utc::now().naive_utc();
+        let horizon = now - post_horizon(&limits);
+        let mut tables = self.tables();
+        tables
+            .author_posts
+            .retain(|(a, s, t)| !(*a == author_id && s == source && *t <= horizon));
+
+        let recent: Vec<_> = tables
+            .author_posts
+            .iter()
+            .filter(|(a, s, _)| *a == author_id && s == source)
+            .map(|(_, _, t)| *t)
+            .collect();
+        if let Some(retry_at) = post_retry_at(&limits, now, &recent) {
+            return Ok(Some(retry_at));
+        }
+        tables
+            .author_posts
+            .push((author_id, source.to_string(), now));
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl FilterStore for MemoryStore {
+    async fn insert_filter_hits(&self, hits: Vec<NewFilterHit>) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        for hit in hits {
+            let id = tables.next_id();
+            tables.filter_hits.push(FilterHit {
+                id,
+                guild_id: hit.guild_id,
+                rule_id: hit.rule_id,
+                author_id: hit.author_id,
+                source: hit.source,
+                action: hit.action,
+                matched: hit.matched,
+                timestamp: Utc::now().naive_utc(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn get_filter_hits(
+        &self,
+        guild_id: &String,
+        limit: i64,
+    ) -> Result<Vec<FilterHit>, DbError> {
+        Ok(self
+            .tables()
+            .filter_hits
+            .iter()
+            .rev()
+            .filter(|h| &h.guild_id == guild_id)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl GuildStore for MemoryStore {
+    async fn get_guild(&self, guild_id: &String) -> Result<Option<Guild>, DbError> {
+        Ok(self
+            .tables()
+            .guilds
+            .iter()
+            .find(|g| &g.guild_id == guild_id)
+            .cloned())
+    }
+
+    async fn get_guild_config(&self, guild_id: &String) -> Result<GuildConfig, DbError> {
+        self.tables().guild_config(guild_id)
+    }
+
+    async fn insert_guild(&self, guild_id: &String) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        if tables.guilds.iter().any(|g| &g.guild_id == guild_id) {
+            return Ok(());
+        }
+        tables.guilds.push(Guild {
+            guild_id: guild_id.clone(),
+            confession_channel_id: None,
+            config: serde_json::to_string(&default_guild_config())?,
+            timestamp: Utc::now().naive_utc(),
+            last_role_ping: None,
+        });
+        Ok(())
+    }
+
+    async fn update_guild(
+        &self,
+        guild_id: &String,
+        confession_channel_id: Option<String>,
+        config: GuildConfig,
+    ) -> Result<(), DbError> {
+        let config = serde_json::to_string(&config)?;
+        if let Some(guild) = self
+            .tables()
+            .guilds
+            .iter_mut()
+            .find(|g| &g.guild_id == guild_id)
+        {
+            guild.confession_channel_id = confession_channel_id;
+            guild.config = config;
+        }
+        Ok(())
+    }
+
+    async fn set_blackout(&self, guild_id: &String, enabled: bool) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        if let Some(guild) = tables.guilds.iter_mut().find(|g| &g.guild_id == guild_id) {
+            let mut config = serde_json::from_str::<GuildConfig>(&guild.config)?;
+            config.blackout = enabled;
+            guild.config = serde_json::to_string(&config)?;
+        }
+        Ok(())
+    }
+
+    async fn claim_role_ping(
+        &self,
+        guild_id: &String,
+        interval: i64,
+    ) -> Result<Option<RolePingClaim>, DbError> {
+        let now = Utc::now().naive_utc();
+        let since = now - TimeDelta::seconds(interval.max(0));
+        let mut tables = self.tables();
+        let guild = match tables.guilds.iter_mut().find(|g| &g.guild_id == guild_id) {
+            Some(guild) => guild,
+            None => return Ok(None),
+        };
+        let previous = guild.last_role_ping;
+        if previous.is_some_and(|last| last > since) {
+            return Ok(None);
+        }
+        guild.last_role_ping = Some(now);
+        Ok(Some(RolePingClaim { at: now, previous }))
+    }
+
+    async fn release_role_ping(
+        &self,
+        guild_id: &String,
+        claim: &RolePingClaim,
+    ) -> Result<(), DbError> {
+        let mut tables = self.tables();
+        if let Some(guild) = tables.guilds.iter_mut().find(|g| &g.guild_id == guild_id) {
+            if guild.last_role_ping == Some(claim.at) {
+                guild.last_role_ping = claim.previous;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OutboxStore for MemoryStore {
+    async fn get_due_outbox(&self, now: NaiveDateTime) -> Result<Vec<OutboxEntry>, DbError> {
+        let mut due: Vec<OutboxEntry> = self
+            .tables()
+            .outbox
+            .iter()
+            .filter(|e| !e.dead && e.release_at <= now)
+            .cloned()
+            .collect();
+        due.sort_by_key(|e| e.release_at);
+        Ok(due)
+    }
+
+    async fn release_confession(&self, confession_id: i32) -> Result<Option<Confession>, DbError> {
+        let mut tables = self.tables();
+        let guild_id = match tables.confessions.iter().find(|c| c.id == confession_id) {
+            Some(confession) => confession.guild_id.clone(),
+            None => return Ok(None),
+        };
+        let number = tables.next_number(&guild_id);
+        let queued: String = ConfessionStatus::QUEUED.into();
+        let active: String = ConfessionStatus::ACTIVE.into();
+        let confession = tables
+            .confessions
+            .iter_mut()
+            .find(|c| c.id == confession_id)
+            .ok_or("Confession not found")?;
+        if confession.status == active {
+            // Released before, but never posted
+            return Ok(confession.message_id.is_empty().then(|| confession.clone()));
+        }
+        if confession.status != queued {
+            return Ok(None);
+        }
+        confession.status = active;
+        confession.status_changed_at = Some(Utc::now().naive_utc());
+        confession.number = Some(number);
+        Ok(Some(confession.clone()))
+    }
+
+    async fn remove_from_outbox(&self, entry_id: i32) -> Result<(), DbError> {
+        self.tables().outbox.retain(|e| e.id != entry_id);
+        Ok(())
+    }
+
+    async fn fail_outbox(
+        &self,
+        entry_id: i32,
+        error: &String,
+        retry_at: Option<NaiveDateTime>,
+    ) -> Result<(), DbError> {
+        if let Some(entry) = self.tables().outbox.iter_mut().find(|e| e.id == entry_id) {
+            entry.attempts += 1;
+            entry.last_error = Some(error.clone());
+            match retry_at {
+                Some(retry_at) => entry.release_at = retry_at,
+                None => entry.dead = true,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ReplyStore for MemoryStore {
+    async fn get_confession_replies(&self, confession_id: i32) -> Result<Vec<Reply>, DbError> {
+        Ok(self
+            .tables()
+            .replies
+            .iter()
+            .filter(|r| r.original_confession_id == confession_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn insert_reply(
+        &self,
+        keys: &AuthorKeys,
+        confession_id: i32,
+        guild_id: &String,
+        message_id: &String,
+        content: &String,
+        author_id: &String,
+        attachment: Option<&NewAttachment>,
+    ) -> Result<(), DbError> {
+        let author = self.insert_author(keys, guild_id, author_id).await?;
+        let mut tables = self.tables();
+        let id = tables.next_id();
+        tables.replies.push(Reply {
+            id,
+            guild_id: guild_id.clone(),
+            original_confession_id: confession_id,
+            author,
+            message_id: message_id.clone(),
+            content: content.clone(),
+            timestamp: Utc::now().naive_utc(),
+        });
+        if let Some(attachment) = attachment {
+            tables.insert_attachment(guild_id, None, Some(id), attachment);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RevealStore for MemoryStore {
+    async fn insert_reveal_approval(
+        &self,
+        confession_id: i32,
+        moderator_id: &String,
+        share: &String,
+    ) -> Result<Vec<String>, DbError> {
+        let mut tables = self.tables();
+        if !tables
+            .reveal_approvals
+            .iter()
+            .any(|(id, moderator, _)| *id == confession_id && moderator == moderator_id)
+        {
+            tables.reveal_approvals.push((
+                confession_id,
+                moderator_id.clone(),
+                Some(share.clone()),
+            ));
+        }
+        Ok(tables
+            .reveal_approvals
+            .iter()
+            .filter(|(id, _, _)| *id == confession_id)
+            .filter_map(|(_, _, share)| share.clone())
+            .collect())
+    }
+
+    async fn clear_reveal_approvals(&self, confession_id: i32) -> Result<(), DbError> {
+        for (id, _, share) in self.tables().reveal_approvals.iter_mut() {
+            if *id == confession_id {
+                *share = None;
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_reveal_shares(
+        &self,
+        confession_id: i32,
+        moderator_ids: &[String],
+    ) -> Result<Vec<RevealShare>, DbError> {
+        Ok(self
+            .tables()
+            .reveal_shares
+            .iter()
+            .filter(|s| s.confession_id == confession_id && moderator_ids.contains(&s.moderator_id))
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for MemoryStore {
+    async fn get_due_schedules(&self, now: i64) -> Result<Vec<Schedule>, DbError> {
+        let pending: String = ScheduleStatus::PENDING.into();
+        let mut due: Vec<Schedule> = self
+            .tables()
+            .schedules
+            .iter()
+            .filter(|s| s.status == pending && s.run_at <= now)
+            .cloned()
+            .collect();
+        due.sort_by_key(|s| s.run_at);
+        Ok(due)
+    }
+
+    async fn next_schedule_at(&self) -> Result<Option<i64>, DbError> {
+        let pending: String = ScheduleStatus::PENDING.into();
+        Ok(self
+            .tables()
+            .schedules
+            .iter()
+            .filter(|s| s.status == pending)
+            .map(|s| s.run_at)
+            .min())
+    }
+
+    async fn get_guild_schedules(
+        &self,
+        guild_id: &String,
+        now: i64,
+    ) -> Result<Vec<Schedule>, DbError> {
+        let applied: String = ScheduleStatus::APPLIED.into();
+        let mut schedules: Vec<Schedule> = self
+            .tables()
+            .schedules
+            .iter()
+            .filter(|s| {
+                &s.guild_id == guild_id
+                    && (s.status != applied || s.ends_at.is_some_and(|ends_at| ends_at > now))
+            })
+            .cloned()
+            .collect();
+        schedules.sort_by_key(|s| s.start_at);
+        Ok(schedules)
+    }
+
+    async fn get_enforced_schedule(
+        &self,
+        guild_id: &String,
+        victim_id: &String,
+        now: i64,
+    ) -> Result<Option<Schedule>, DbError> {
+        let applied: String = ScheduleStatus::APPLIED.into();
+        Ok(self
+            .tables()
+            .schedules
+            .iter()
+            .filter(|s| {
+                &s.guild_id == guild_id
+                    && s.victim_id.as_ref() == Some(victim_id)
+                    && s.status == applied
+                    && s.enforce
+                    && s.ends_at.is_some_and(|ends_at| ends_at > now)
+            })
+            .max_by_key(|s| s.ends_at)
+            .cloned())
+    }
+
+    async fn insert_schedule(&self, insert_schedule: InsertSchedule) -> Result<Schedule, DbError> {
+        let mut tables = self.tables();
+        let schedule = Schedule {
+            id: tables.next_id(),
+            guild_id: insert_schedule.guild_id,
+            victim_id: insert_schedule.victim_id,
+            ends_at: insert_schedule.ends_at,
+            start_at: insert_schedule.start_at,
+            run_at: insert_schedule.start_at,
+            status: ScheduleStatus::PENDING.into(),
+            attempts: 0,
+            last_error: None,
+            enforce: insert_schedule.enforce,
+            action: insert_schedule.action,
+            reapplied: 0,
+            reapplied_at: None,
+        };
+        tables.schedules.push(schedule.clone());
+        Ok(schedule)
+    }
+
+    async fn update_schedule(
+        &self,
+        guild_id: &String,
+        schedule_id: i32,
+        start_at: i64,
+        ends_at: Option<i64>,
+    ) -> Result<Option<Schedule>, DbError> {
+        let applied: String = ScheduleStatus::APPLIED.into();
+        let mut tables = self.tables();
+        match tables
+            .schedules
+            .iter_mut()
+            .find(|s| s.id == schedule_id && &s.guild_id == guild_id && s.status != applied)
+        {
+            Some(schedule) => {
+                schedule.start_at = start_at;
+                schedule.run_at = start_at;
+                schedule.ends_at = ends_at;
+                schedule.status = ScheduleStatus::PENDING.into();
+                schedule.attempts = 0;
+                schedule.last_error = None;
+                Ok(Some(schedule.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn cancel_schedule(
+        &self,
+        guild_id: &String,
+        schedule_id: i32,
+    ) -> Result<Option<Schedule>, DbError> {
+        let mut tables = self.tables();
+        match tables
+            .schedules
+            .iter()
+            .position(|s| s.id == schedule_id && &s.guild_id == guild_id)
+        {
+            Some(position) => Ok(Some(tables.schedules.remove(position))),
+            None => Ok(None),
+        }
+    }
+
+    async fn complete_schedule(&self, schedule_id: i32) -> Result<(), DbError> {
+        if let Some(schedule) = self
+            .tables()
+            .schedules
+            .iter_mut()
+            .find(|s| s.id == schedule_id)
+        {
+            schedule.status = ScheduleStatus::APPLIED.into();
+        }
+        Ok(())
+    }
+
+    async fn reapply_schedule(&self, schedule_id: i32, now: i64) -> Result<(), DbError> {
+        if let Some(schedule) = self
+            .tables()
+            .schedules
+            .iter_mut()
+            .find(|s| s.id == schedule_id)
+        {
+            schedule.reapplied += 1;
+            schedule.reapplied_at = Some(now);
+        }
+        Ok(())
+    }
+
+    async fn fail_schedule(
+        &self,
+        schedule_id: i32,
+        error: &String,
+        retry_at: Option<i64>,
+    ) -> Result<(), DbError> {
+        if let Some(schedule) = self
+            .tables()
+            .schedules
+            .iter_mut()
+            .find(|s| s.id == schedule_id)
+        {
+            schedule.attempts += 1;
+            schedule.last_error = Some(error.clone());
+            match retry_at {
+                Some(retry_at) => schedule.run_at = retry_at,
+                None => schedule.status = ScheduleStatus::DEAD.into(),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VoteStore for MemoryStore {
+    async fn get_vote(
+        &self,
+        message_id: &String,
+        guild_id: &String,
+        vote_type: VoteType,
+    ) -> Result<(u32, u32), DbError> {
+        let tables = self.tables();
+        let min_vote = vote_minimum(&tables.guild_config(guild_id)?, &vote_type);
+        let confession_id = tables.confession_by_message_id(message_id, guild_id)?.id;
+        let vote_type: String = vote_type.into();
+        let count = tables
+            .votes
+            .iter()
+            .filter(|v| v.confession_id == confession_id && v.vote_type == vote_type)
+            .count();
+        Ok((count as u32, min_vote))
+    }
+
+    async fn update_vote(
+        &self,
+        keys: &AuthorKeys,
+        author_id: &String,
+        message_id: &String,
+        guild_id: &String,
+        vote_type: VoteType,
+    ) -> Result<VoteOutcome, DbError> {
+        let author = self.insert_author(keys, guild_id, author_id).await?;
+
+        let mut tables = self.tables();
+        let min_vote = vote_minimum(&tables.guild_config(guild_id)?, &vote_type);
+        let confession = tables.confession_by_message_id(message_id, guild_id)?;
+        let confession_id = confession.id;
+        if Into::<ConfessionStatus>::into(confession.status.clone()) != ConfessionStatus::ACTIVE {
+            return Ok(VoteOutcome::Closed);
+        }
+
+        let reached: String = match vote_type {
+            VoteType::DELETE => ConfessionStatus::DELETED,
+            VoteType::EXPOSE => ConfessionStatus::EXPOSED,
+        }
+        .into();
+        let vote_type: String = vote_type.into();
+        let is_counted = |v: &Vote| v.confession_id == confession_id && v.vote_type == vote_type;
+        let total_votes = tables.votes.iter().filter(|v| is_counted(v)).count() as u32;
+
+        // User has already made a vote, so we will remove it
+        if let Some(index) = tables
+            .votes
+            .iter()
+            .position(|v| is_counted(v) && v.author_id == author)
+        {
+            tables.votes.remove(index);
+            return Ok(VoteOutcome::Counted(total_votes - 1, min_vote));
+        }
+
+        let id = tables.next_id();
+        tables.votes.push(Vote {
+            id,
+            confession_id,
+            author_id: author,
+            vote_type: vote_type.clone(),
+            timestamp: Utc::now().naive_utc(),
+        });
+
+        if total_votes + 1 >= min_vote {
+            if let Some(confession) = tables
+                .confessions
+                .iter_mut()
+                .find(|c| c.id == confession_id)
+            {
+                confession.status = reached;
+                confession.status_changed_at = Some(Utc::now().naive_utc());
+            }
+            return Ok(VoteOutcome::Reached(total_votes + 1));
+        }
+
+        Ok(VoteOutcome::Counted(total_votes + 1, min_vote))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use confession_bot_rs::crypto::EscrowKey;
+
+    use super::*;
+    use crate::db_impl::escrow_author;
+
+    const GUILD: &str = "1";
+
+    fn keys() -> AuthorKeys {
+        AuthorKeys::new(&[7; 32], None)
+    }
+
+    /// A store with one guild using `config`.
+    async fn store(config: GuildConfig) -> MemoryStore {
+        let store = MemoryStore::default();
+        let guild_id = GUILD.to_string();
+        store.insert_guild(&guild_id).await.unwrap();
+        store
+            .update_guild(&guild_id, Some("2".to_string()), config)
+            .await
+            .unwrap();
+        store
+    }
+
+    async fn confess(
+        store: &MemoryStore,
+        author_id: &str,
+        release_at: Option<NaiveDateTime>,
+    ) -> Confession {
+        let guild_id = GUILD.to_string();
+        let config = store.get_guild_config(&guild_id).await.unwrap();
+        let escrow_key = EscrowKey::new(&[9; 32]).unwrap();
+        let author_id = author_id.to_string();
+        let escrow = escrow_author(&escrow_key, &config, &guild_id, &author_id, false).unwrap();
+        let draft = ConfessionDraft {
+            content: "A confession".to_string(),
+            ..Default::default()
+        };
+        store
+            .insert_confession(
+                &keys(),
+                &escrow,
+                &author_id,
+                &guild_id,
+                &draft,
+                false,
+                release_at,
+            )
+            .await
+            .unwrap()
+    }
+
+    /// Post a confession as message `message_id`.
+    async fn posted(store: &MemoryStore, message_id: &str) -> Confession {
+        let confession = confess(store, "10", None).await;
+        store
+            .set_confession_message(confession.id, &"2".to_string(), &message_id.to_string())
+            .await
+            .unwrap();
+        confession
+    }
+
+    async fn vote(store: &MemoryStore, voter: &str, message_id: &str) -> VoteOutcome {
+        store
+            .update_vote(
+                &keys(),
+                &voter.to_string(),
+                &message_id.to_string(),
+                &GUILD.to_string(),
+                VoteType::DELETE,
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn votes_reach_the_minimum_once() {
+        let mut config = default_guild_config();
+        config.delete_vote_min = 2;
+        let store = store(config).await;
+        posted(&store, "100").await;
+
+        assert!(matches!(
+            vote(&store, "20", "100").await,
+            VoteOutcome::Counted(1, 2)
+        ));
+        // Voting again takes the vote back
+        assert!(matches!(
+            vote(&store, "20", "100").await,
+            VoteOutcome::Counted(0, 2)
+        ));
+        assert!(matches!(
+            vote(&store, "20", "100").await,
+            VoteOutcome::Counted(1, 2)
+        ));
+        assert!(matches!(
+            vote(&store, "21", "100").await,
+            VoteOutcome::Reached(2)
+        ));
+        assert!(matches!(
+            vote(&store, "22", "100").await,
+            VoteOutcome::Closed
+        ));
+
+        let confession = store
+            .get_confession_by_message_id(&"100".to_string(), &GUILD.to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            Into::<ConfessionStatus>::into(confession.status),
+            ConfessionStatus::DELETED
+        );
+    }
+
+    #[tokio::test]
+    async fn posts_are_limited_per_source() {
+        let store = store(default_guild_config()).await;
+        let limits = PostLimits {
+            cooldown: 60 * 60,
+            ..Default::default()
+        };
+        let guild_id = GUILD.to_string();
+        let record = |source| store.try_record_post(&guild_id, 1, source, limits);
+
+        assert_eq!(record("confession").await.unwrap(), None);
+        assert!(record("confession").await.unwrap().is_some());
+        assert_eq!(record("reply").await.unwrap(), None);
+        // Without limits nothing is refused
+        assert_eq!(
+            store
+                .try_record_post(&guild_id, 1, "confession", PostLimits::default())
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn queued_confessions_are_numbered_on_release() {
+        let store = store(default_guild_config()).await;
+        let release_at = Utc::now().naive_utc() + TimeDelta::minutes(10);
+        let queued = confess(&store, "10", Some(release_at)).await;
+        let posted = posted(&store, "100").await;
+        assert_eq!(queued.number, None);
+        assert_eq!(posted.number, Some(1));
+
+        assert!(store
+            .get_due_outbox(release_at - TimeDelta::seconds(1))
+            .await
+            .unwrap()
+            .is_empty());
+        let due = store.get_due_outbox(release_at).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].confession_id, queued.id);
+
+        let released = store.release_confession(queued.id).await.unwrap().unwrap();
+        assert_eq!(released.number, Some(2));
+    }
+
+    async fn approve(
+        store: &MemoryStore,
+        confession_id: i32,
+        moderator: &str,
+        share: &String,
+    ) -> Vec<String> {
+        store
+            .insert_reveal_approval(confession_id, &moderator.to_string(), share)
+            .await
+            .unwrap()
+    }
+
+    /// Two of three moderators must approve a reveal.
+    fn reveal_config() -> GuildConfig {
+        let mut config = default_guild_config();
+        config.reveal_moderators = vec!["30".to_string(), "31".to_string(), "32".to_string()];
+        config.reveal_threshold = Some(2);
+        config
+    }
+
+    #[tokio::test]
+    async fn only_share_hashes_are_kept() {
+        let store = store(reveal_config()).await;
+        let config = reveal_config();
+
+        let guild_id = GUILD.to_string();
+        let escrow_key = EscrowKey::new(&[9; 32]).unwrap();
+        let escrow =
+            escrow_author(&escrow_key, &config, &guild_id, &"10".to_string(), false).unwrap();
+        let confession = store
+            .insert_confession(
+                &keys(),
+                &escrow,
+                &"10".to_string(),
+                &guild_id,
+                &ConfessionDraft::default(),
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(confession.reveal_threshold, Some(2));
+
+        let stored = store
+            .get_reveal_shares(confession.id, &config.reveal_moderators)
+            .await
+            .unwrap();
+        assert_eq!(stored.len(), 3);
+        for (stored, dealt) in stored.iter().zip(&escrow.shares) {
+            assert_eq!(stored.moderator_id, dealt.moderator_id);
+            assert_eq!(stored.share_index, dealt.index as i32);
+            assert_eq!(stored.share_hash, dealt.hash());
+            assert_ne!(stored.share_hash, dealt.token());
+        }
+
+        // Approving twice keeps the first share, and shares are forgotten once
+        // the author is revealed
+        let first = escrow.shares[0].token();
+        let second = escrow.shares[1].token();
+        assert_eq!(
+            approve(&store, confession.id, "30", &first).await,
+            vec![first.clone()]
+        );
+        assert_eq!(
+            approve(&store, confession.id, "30", &second).await,
+            vec![first.clone()]
+        );
+        assert_eq!(
+            approve(&store, confession.id, "31", &second).await,
+            vec![first.clone(), second.clone()]
+        );
+        store.clear_reveal_approvals(confession.id).await.unwrap();
+        let third = escrow.shares[2].token();
+        assert_eq!(
+            approve(&store, confession.id, "32", &third).await,
+            vec![third.clone()]
+        );
+    }
+}
@@ -1,5 +1,695 @@
-pub mod authors;
-pub mod confessions;
-pub mod guilds;
-pub mod reply;
-pub mod votes;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, TimeDelta};
+use confession_bot_rs::{
+    crypto::{random_key, share_hash, share_token, split_secret, AuthorKeys, EscrowKey},
+    DbError, VoteType,
+};
+
+use crate::models::{
+    AttachmentRules, Confession, ConfessionDraft, FilterHit, Guild, GuildConfig, InsertSchedule,
+    NewAttachment, NewFilterHit, OutboxEntry, PostDelay, PostLimits, Reply, RevealShare,
+    SanitiseLevel, Schedule, StoredAttachment,
+};
+
+pub mod memory;
+pub mod sql;
+
+/// `DATABASE_URL` which selects the in-memory backend instead of SQLite.
+pub const MEMORY_URL: &str = "memory:";
+
+/// Every store the bot needs, implemented by each backend.
+pub trait Store:
+    AttachmentStore
+    + AuthorStore
+    + BanStore
+    + ConfessionStore
+    + CooldownStore
+    + FilterStore
+    + GuildStore
+    + OutboxStore
+    + ReplyStore
+    + RevealStore
+    + ScheduleStore
+    + VoteStore
+    + Send
+    + Sync
+{
+}
+
+impl<T> Store for T where
+    T: AttachmentStore
+        + AuthorStore
+        + BanStore
+        + ConfessionStore
+        + CooldownStore
+        + FilterStore
+        + GuildStore
+        + OutboxStore
+        + ReplyStore
+        + RevealStore
+        + ScheduleStore
+        + VoteStore
+        + Send
+        + Sync
+{
+}
+
+/// The backend chosen at startup, shared by every command.
+pub type Db = Arc<dyn Store>;
+
+#[async_trait]
+pub trait AttachmentStore {
+    /// The attachment a confession was sent with, if any.
+    async fn get_confession_attachment(
+        &self,
+        confession_id: i32,
+    ) -> Result<Option<StoredAttachment>, DbError>;
+
+    /// Remove the attachments of a confession and of its replies, once it has
+    /// been taken down.
+    async fn delete_attachments(&self, confession_id: i32) -> Result<(), DbError>;
+}
+
+#[async_trait]
+pub trait AuthorStore {
+    /// Get (or create) the author row for a user within a guild.
+    async fn insert_author(
+        &self,
+        keys: &AuthorKeys,
+        guild_id: &String,
+        user_id: &String,
+    ) -> Result<i32, DbError>;
+
+    /// How many authors are not yet keyed with `key_id`. Each is re-keyed the
+    /// next time its author is seen, so once this reaches zero the previous
+    /// key can be retired.
+    async fn count_stale_authors(&self, key_id: &String) -> Result<i64, DbError>;
+}
+
+#[async_trait]
+pub trait BanStore {
+    /// Ban an author from posting and voting in a guild, replacing any
+    /// existing ban. No expiry means the ban is permanent.
+    async fn ban_author(
+        &self,
+        guild_id: &String,
+        author_id: i32,
+        moderator_id: &String,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<(), DbError>;
+
+    /// # Returns
+    /// Whether there was a ban to lift.
+    async fn unban_author(&self, guild_id: &String, author_id: i32) -> Result<bool, DbError>;
+
+    /// Whether an author is currently banned. Expired bans do not count.
+    async fn is_author_banned(&self, guild_id: &String, author_id: i32) -> Result<bool, DbError>;
+}
+
+#[async_trait]
+pub trait ConfessionStore {
+    /// Get a confession by its number within the guild.
+    async fn get_confession_by_id_guild(
+        &self,
+        confession_id: u32,
+        guild_id: &String,
+    ) -> Result<Confession, DbError>;
+
+    async fn get_confession_by_message_id(
+        &self,
+        message_id: &String,
+        guild_id: &String,
+    ) -> Result<Confession, DbError>;
+
+    /// Insert a confession with its author escrowed by [`escrow_author`], and
+    /// give it the next number in its guild. Only the hashes of the reveal
+    /// shares are kept. It has no message until
+    /// [`ConfessionStore::set_confession_message`] is called.
+    ///
+    /// With `review`, the confession is left pending and unnumbered until
+    /// [`ConfessionStore::approve_confession`]. Otherwise, with `release_at`,
+    /// it is queued in the outbox and left unnumbered until
+    /// [`OutboxStore::release_confession`].
+    async fn insert_confession(
+        &self,
+        keys: &AuthorKeys,
+        escrow: &AuthorEscrow,
+        author_id: &String,
+        guild_id: &String,
+        draft: &ConfessionDraft,
+        review: bool,
+        release_at: Option<NaiveDateTime>,
+    ) -> Result<Confession, DbError>;
+
+    /// Make a pending confession active and give it the next number in its
+    /// guild.
+    /// # Returns
+    /// The approved confession, or `None` if it was not pending, e.g. because
+    /// another moderator got there first.
+    async fn approve_confession(
+        &self,
+        guild_id: &String,
+        confession_id: i32,
+    ) -> Result<Option<Confession>, DbError>;
+
+    /// Reject a pending confession.
+    /// # Returns
+    /// Whether the confession was pending.
+    async fn reject_confession(
+        &self,
+        guild_id: &String,
+        confession_id: i32,
+    ) -> Result<bool, DbError>;
+
+    /// Put an approved confession which could not be posted back into review,
+    /// freeing its number.
+    async fn return_confession_to_review(&self, confession_id: i32) -> Result<(), DbError>;
+
+    /// Clear and return the sealed review notice, so the author is only told
+    /// the outcome once.
+    async fn take_review_notice(&self, confession_id: i32) -> Result<Option<String>, DbError>;
+
+    /// Link a confession to the message it was posted as, and the channel it
+    /// was posted in.
+    async fn set_confession_message(
+        &self,
+        confession_id: i32,
+        channel_id: &String,
+        message_id: &String,
+    ) -> Result<(), DbError>;
+
+    /// Remove a confession which could not be posted, along with its reveal
+    /// shares, attachment and any place in the outbox.
+    async fn delete_confession(&self, confession_id: i32) -> Result<(), DbError>;
+
+    /// Take down an active confession at its author's request.
+    /// # Returns
+    /// Whether the confession was active.
+    async fn retract_confession(&self, confession_id: i32) -> Result<bool, DbError>;
+
+    /// Replace the text of an active confession, keeping what it said before
+    /// as a revision.
+    /// # Returns
+    /// The edited confession, or `None` if it is no longer active.
+    async fn edit_confession(
+        &self,
+        confession_id: i32,
+        draft: &ConfessionDraft,
+    ) -> Result<Option<Confession>, DbError>;
+
+    /// Undo an edit from [`ConfessionStore::edit_confession`] which could not
+    /// be shown, putting back its revision. Left alone if the confession has
+    /// been edited again since.
+    async fn undo_confession_edit(&self, edited: &Confession) -> Result<(), DbError>;
+}
+
+#[async_trait]
+pub trait CooldownStore {
+    /// Record a post by an author, unless it would break the guild's limits.
+    /// Posts from each source are limited separately.
+    /// # Returns
+    /// When the author may post again, if the post was refused.
+    async fn try_record_post(
+        &self,
+        guild_id: &String,
+        author_id: i32,
+        source: &str,
+        limits: PostLimits,
+    ) -> Result<Option<NaiveDateTime>, DbError>;
+}
+
+#[async_trait]
+pub trait FilterStore {
+    async fn insert_filter_hits(&self, hits: Vec<NewFilterHit>) -> Result<(), DbError>;
+
+    /// The most recent filter hits in a guild, newest first.
+    async fn get_filter_hits(
+        &self,
+        guild_id: &String,
+        limit: i64,
+    ) -> Result<Vec<FilterHit>, DbError>;
+}
+
+#[async_trait]
+pub trait GuildStore {
+    async fn get_guild(&self, guild_id: &String) -> Result<Option<Guild>, DbError>;
+
+    async fn get_guild_config(&self, guild_id: &String) -> Result<GuildConfig, DbError>;
+
+    /// Insert a guild with the default config. Existing guilds are left alone.
+    async fn insert_guild(&self, guild_id: &String) -> Result<(), DbError>;
+
+    async fn update_guild(
+        &self,
+        guild_id: &String,
+        confession_channel_id: Option<String>,
+        config: GuildConfig,
+    ) -> Result<(), DbError>;
+
+    /// Turn the guild's confession blackout on or off, leaving the rest of its
+    /// config as it is.
+    async fn set_blackout(&self, guild_id: &String, enabled: bool) -> Result<(), DbError>;
+
+    /// Record a ping of the guild's confession role, unless the last one was
+    /// under `interval` seconds ago.
+    /// # Returns
+    /// The claim, if it was recorded.
+    async fn claim_role_ping(
+        &self,
+        guild_id: &String,
+        interval: i64,
+    ) -> Result<Option<RolePingClaim>, DbError>;
+
+    /// Hand back a claim whose ping was never sent, so the next confession
+    /// may ping instead. Left alone if another ping has been recorded since.
+    async fn release_role_ping(
+        &self,
+        guild_id: &String,
+        claim: &RolePingClaim,
+    ) -> Result<(), DbError>;
+}
+
+#[async_trait]
+pub trait OutboxStore {
+    /// Queued confessions due by `now`, across every guild, earliest first.
+    /// Dead letters are left out.
+    async fn get_due_outbox(&self, now: NaiveDateTime) -> Result<Vec<OutboxEntry>, DbError>;
+
+    /// Make a queued confession active and give it the next number in its
+    /// guild. A confession released before, but never posted, is returned
+    /// as it is so posting can be retried.
+    /// # Returns
+    /// The released confession, or `None` if it can no longer be posted.
+    async fn release_confession(&self, confession_id: i32) -> Result<Option<Confession>, DbError>;
+
+    /// Remove a confession from the outbox once it has been posted.
+    async fn remove_from_outbox(&self, entry_id: i32) -> Result<(), DbError>;
+
+    /// Record a failed attempt at posting a queued confession. It is tried
+    /// again at `retry_at`, or without one is kept as a dead letter.
+    async fn fail_outbox(
+        &self,
+        entry_id: i32,
+        error: &String,
+        retry_at: Option<NaiveDateTime>,
+    ) -> Result<(), DbError>;
+}
+
+#[async_trait]
+pub trait ReplyStore {
+    async fn get_confession_replies(&self, confession_id: i32) -> Result<Vec<Reply>, DbError>;
+
+    async fn insert_reply(
+        &self,
+        keys: &AuthorKeys,
+        confession_id: i32,
+        guild_id: &String,
+        message_id: &String,
+        content: &String,
+        author_id: &String,
+        attachment: Option<&NewAttachment>,
+    ) -> Result<(), DbError>;
+}
+
+#[async_trait]
+pub trait RevealStore {
+    /// Record a moderator's approval to reveal a confession's author, with the
+    /// share they were dealt. Approving twice has no effect.
+    /// # Returns
+    /// The shares given back so far, oldest first.
+    async fn insert_reveal_approval(
+        &self,
+        confession_id: i32,
+        moderator_id: &String,
+        share: &String,
+    ) -> Result<Vec<String>, DbError>;
+
+    /// Forget the shares given back for a confession, once its author has
+    /// been revealed.
+    async fn clear_reveal_approvals(&self, confession_id: i32) -> Result<(), DbError>;
+
+    async fn get_reveal_shares(
+        &self,
+        confession_id: i32,
+        moderator_ids: &[String],
+    ) -> Result<Vec<RevealShare>, DbError>;
+}
+
+#[async_trait]
+pub trait ScheduleStore {
+    /// Pending jobs due by `now`, across every guild, earliest first.
+    async fn get_due_schedules(&self, now: i64) -> Result<Vec<Schedule>, DbError>;
+
+    /// When the next pending job is due, if there is one.
+    async fn next_schedule_at(&self) -> Result<Option<i64>, DbError>;
+
+    /// A guild's jobs which are waiting, applied or dead, soonest first.
+    /// Applied jobs are left out once their timeout has ended.
+    async fn get_guild_schedules(
+        &self,
+        guild_id: &String,
+        now: i64,
+    ) -> Result<Vec<Schedule>, DbError>;
+
+    /// The enforced job holding a member's timeout in place at `now`, if
+    /// there is one. The one ending last wins.
+    async fn get_enforced_schedule(
+        &self,
+        guild_id: &String,
+        victim_id: &String,
+        now: i64,
+    ) -> Result<Option<Schedule>, DbError>;
+
+    /// Insert a job, due at its `start_at`.
+    async fn insert_schedule(&self, insert_schedule: InsertSchedule) -> Result<Schedule, DbError>;
+
+    /// Move a job which has not been applied to new times. A dead job is
+    /// given its attempts back. Only timeouts have an `ends_at`.
+    /// # Returns
+    /// The updated job, or `None` if there is no such job in the guild which
+    /// can still be moved.
+    async fn update_schedule(
+        &self,
+        guild_id: &String,
+        schedule_id: i32,
+        start_at: i64,
+        ends_at: Option<i64>,
+    ) -> Result<Option<Schedule>, DbError>;
+
+    /// Remove a job from a guild, whatever its status.
+    /// # Returns
+    /// The removed job, if there was one.
+    async fn cancel_schedule(
+        &self,
+        guild_id: &String,
+        schedule_id: i32,
+    ) -> Result<Option<Schedule>, DbError>;
+
+    /// Record that a job has been run.
+    async fn complete_schedule(&self, schedule_id: i32) -> Result<(), DbError>;
+
+    /// Record that an enforced timeout was put back at `now` after being
+    /// lifted early.
+    async fn reapply_schedule(&self, schedule_id: i32, now: i64) -> Result<(), DbError>;
+
+    /// Record a failed attempt at a job. It is tried again at `retry_at`, or
+    /// without one is kept as a dead letter.
+    async fn fail_schedule(
+        &self,
+        schedule_id: i32,
+        error: &String,
+        retry_at: Option<i64>,
+    ) -> Result<(), DbError>;
+}
+
+#[async_trait]
+pub trait VoteStore {
+    /// Get the amount of votes of a type on a confession, and the amount
+    /// required to act on them.
+    async fn get_vote(
+        &self,
+        message_id: &String,
+        guild_id: &String,
+        vote_type: VoteType,
+    ) -> Result<(u32, u32), DbError>;
+
+    /// Toggle a user's vote on a confession. Once the threshold is reached the
+    /// confession's status changes and further votes are ignored.
+    async fn update_vote(
+        &self,
+        keys: &AuthorKeys,
+        author_id: &String,
+        message_id: &String,
+        guild_id: &String,
+        vote_type: VoteType,
+    ) -> Result<VoteOutcome, DbError>;
+}
+
+/// The result of toggling a vote with [`VoteStore::update_vote`].
+pub enum VoteOutcome {
+    /// The vote was added or removed. Holds the updated amount of votes and
+    /// the amount required for deletion/exposing.
+    Counted(u32, u32),
+    /// This vote reached the threshold. Exactly one vote per confession and
+    /// vote type gets this outcome.
+    Reached(u32),
+    /// A threshold has already been reached, so the vote was ignored.
+    Closed,
+}
+
+/// A ping of the confession role recorded by [`GuildStore::claim_role_ping`].
+#[derive(Clone, Copy)]
+pub struct RolePingClaim {
+    /// When the ping was recorded.
+    pub at: NaiveDateTime,
+    /// The ping recorded before it, put back if the claim is released.
+    pub previous: Option<NaiveDateTime>,
+}
+
+/// A confession author sealed for later reveal.
+#[derive(Clone)]
+pub struct AuthorEscrow {
+    pub sealed: String,
+    /// Approvals needed to reveal, if the author key was split.
+    pub threshold: Option<u8>,
+    /// A share of the author key for each designated moderator.
+    pub shares: Vec<DealtShare>,
+    /// The author sealed under the bot's key alone, so they can be told the
+    /// outcome of a review. See [`review_notice_context`].
+    pub review_notice: Option<String>,
+}
+
+/// A share of a reveal key dealt to a moderator. Only its hash is stored, so
+/// the moderator must be sent the share before it is dropped.
+#[derive(Clone)]
+pub struct DealtShare {
+    pub moderator_id: String,
+    pub index: u8,
+    pub share: Vec<u8>,
+}
+
+impl DealtShare {
+    /// The share as the moderator is given it, and hands it back.
+    pub fn token(&self) -> String {
+        share_token(self.index, &self.share)
+    }
+
+    pub fn hash(&self) -> String {
+        share_hash(self.index, &self.share)
+    }
+}
+
+/// The escrow context for review notices. Distinct from the author escrow, so
+/// a notice cannot be passed off as an escrowed author.
+pub fn review_notice_context(guild_id: &str) -> String {
+    format!("review:{}", guild_id)
+}
+
+/// Escrow the author of a new confession.
+///
+/// When the guild requires moderator approval for reveals, the author is sealed
+/// under a fresh key which is split between the designated moderators. The
+/// bot keeps no copy of the key or the shares. Otherwise it is sealed directly
+/// under the bot's escrow key.
+pub fn escrow_author(
+    escrow_key: &EscrowKey,
+    guild_config: &GuildConfig,
+    guild_id: &String,
+    author_id: &String,
+    review: bool,
+) -> Result<AuthorEscrow, DbError> {
+    let threshold = guild_config.reveal_quorum();
+    let mut shares = vec![];
+    let sealed = match threshold {
+        Some(threshold) => {
+            let data_key = random_key().map_err(|_| "Could not generate a reveal key")?;
+            let moderators = &guild_config.reveal_moderators;
+            for ((index, share), moderator_id) in
+                split_secret(&data_key, threshold, moderators.len() as u8)
+                    .map_err(|_| "Could not split the reveal key")?
+                    .into_iter()
+                    .zip(moderators)
+            {
+                shares.push(DealtShare {
+                    moderator_id: moderator_id.clone(),
+                    index,
+                    share,
+                });
+            }
+            EscrowKey::new(&data_key)
+                .and_then(|key| key.seal(guild_id, author_id))
+                .map_err(|_| "Could not escrow the confession author")?
+        }
+        None => escrow_key
+            .seal(guild_id, author_id)
+            .map_err(|_| "Could not escrow the confession author")?,
+    };
+    let review_notice = match review {
+        true => Some(
+            escrow_key
+                .seal(&review_notice_context(guild_id), author_id)
+                .map_err(|_| "Could not seal the review notice")?,
+        ),
+        false => None,
+    };
+    Ok(AuthorEscrow {
+        sealed,
+        threshold,
+        shares,
+        review_notice,
+    })
+}
+
+/// The config given to guilds when they are first seen.
+pub fn default_guild_config() -> GuildConfig {
+    GuildConfig {
+        delete_vote_min: 10,
+        expose_vote_min: 50,
+        expose_vote_role: None,
+        role_ping: None,
+        role_ping_interval: 0,
+        reveal_moderators: vec![],
+        reveal_threshold: None,
+        review_channel: None,
+        filters: vec![],
+        last_filter_id: 0,
+        sanitise_level: SanitiseLevel::BASIC,
+        post_limits: PostLimits::default(),
+        post_delay: PostDelay::default(),
+        attachments: AttachmentRules::default(),
+        author_edit_window: 0,
+        timezone: None,
+        blackout: false,
+    }
+}
+
+/// How far back posts matter to `limits`. Older posts can be forgotten.
+pub fn post_horizon(limits: &PostLimits) -> TimeDelta {
+    TimeDelta::seconds(limits.cooldown.max(limits.window).max(0))
+}
+
+/// When an author may post again, given the times of their posts within
+/// [`post_horizon`], oldest first.
+pub fn post_retry_at(
+    limits: &PostLimits,
+    now: NaiveDateTime,
+    recent: &[NaiveDateTime],
+) -> Option<NaiveDateTime> {
+    let mut retry_at = None;
+    if let Some(last) = recent.last() {
+        let ready = *last + TimeDelta::seconds(limits.cooldown);
+        if limits.cooldown > 0 && ready > now {
+            retry_at = Some(ready);
+        }
+    }
+    if limits.max_posts > 0 && limits.window > 0 {
+        let in_window: Vec<_> = recent
+            .iter()
+            .filter(|t| **t + TimeDelta::seconds(limits.window) > now)
+            .collect();
+        let excess = in_window.len() as i64 - limits.max_posts;
+        if excess >= 0 {
+            // Enough posts have to fall out of the window to make room
+            let ready = *in_window[excess as usize] + TimeDelta::seconds(limits.window);
+            retry_at = retry_at.max(Some(ready));
+        }
+    }
+    retry_at
+}
+
+/// The amount of votes of a type needed to act on a confession.
+pub fn vote_minimum(config: &GuildConfig, vote_type: &VoteType) -> u32 {
+    let min_vote = match vote_type {
+        VoteType::DELETE => config.delete_vote_min,
+        VoteType::EXPOSE => config.expose_vote_min,
+    };
+    min_vote as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        DateTime::from_timestamp(1_700_000_000, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn ago(minutes: i64) -> NaiveDateTime {
+        now() - TimeDelta::minutes(minutes)
+    }
+
+    #[test]
+    fn no_limits_never_wait() {
+        let limits = PostLimits::default();
+        assert_eq!(post_retry_at(&limits, now(), &[]), None);
+        assert_eq!(post_retry_at(&limits, now(), &[ago(0), ago(0)]), None);
+    }
+
+    #[test]
+    fn cooldowns_start_from_the_last_post() {
+        let limits = PostLimits {
+            cooldown: 10 * 60,
+            ..Default::default()
+        };
+        assert_eq!(
+            post_retry_at(&limits, now(), &[ago(30), ago(4)]),
+            Some(ago(-6))
+        );
+        assert_eq!(post_retry_at(&limits, now(), &[ago(20), ago(10)]), None);
+    }
+
+    #[test]
+    fn windows_wait_for_room() {
+        let limits = PostLimits {
+            max_posts: 2,
+            window: 60 * 60,
+            ..Default::default()
+        };
+        assert_eq!(post_retry_at(&limits, now(), &[ago(50)]), None);
+        // Posts outside the window do not count
+        assert_eq!(post_retry_at(&limits, now(), &[ago(70), ago(50)]), None);
+        assert_eq!(
+            post_retry_at(&limits, now(), &[ago(50), ago(10)]),
+            Some(ago(-10))
+        );
+        // Over the limit, e.g. after it was lowered, more posts have to expire
+        assert_eq!(
+            post_retry_at(&limits, now(), &[ago(55), ago(50), ago(10)]),
+            Some(ago(-10))
+        );
+    }
+
+    #[test]
+    fn the_later_limit_wins() {
+        let limits = PostLimits {
+            cooldown: 30 * 60,
+            max_posts: 2,
+            window: 60 * 60,
+        };
+        assert_eq!(
+            post_retry_at(&limits, now(), &[ago(50), ago(10)]),
+            Some(ago(-20))
+        );
+        assert_eq!(
+            post_retry_at(&limits, now(), &[ago(59), ago(40)]),
+            Some(ago(-1))
+        );
+    }
+
+    #[test]
+    fn vote_minimums_follow_the_config() {
+        let mut config = default_guild_config();
+        assert_eq!(vote_minimum(&config, &VoteType::DELETE), 10);
+        assert_eq!(vote_minimum(&config, &VoteType::EXPOSE), 50);
+        config.delete_vote_min = 3;
+        config.expose_vote_min = 7;
+        assert_eq!(vote_minimum(&config, &VoteType::DELETE), 3);
+        assert_eq!(vote_minimum(&config, &VoteType::EXPOSE), 7);
+    }
+}
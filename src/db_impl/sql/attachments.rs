@@ -0,0 +1,54 @@
+use std::error::Error;
+
+use confession_bot_rs::{run, DbPool};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods, OptionalExtension,
+    QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+use crate::{
+    models::StoredAttachment,
+    schema::{attachments, replies},
+};
+
+pub async fn get_confession_attachment(
+    pool: &DbPool,
+    confession_id: i32,
+) -> Result<Option<StoredAttachment>, Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        match attachments::table
+            .filter(attachments::confession_id.eq(Some(confession_id)))
+            .select(StoredAttachment::as_select())
+            .first(conn)
+            .optional()
+        {
+            Ok(attachment) => Ok(attachment),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn delete_attachments(
+    pool: &DbPool,
+    confession_id: i32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        let reply_ids = replies::table
+            .select(replies::id)
+            .filter(replies::original_confession_id.eq(confession_id));
+        match diesel::delete(
+            attachments::table.filter(
+                attachments::confession_id
+                    .eq(Some(confession_id))
+                    .or(attachments::reply_id.eq_any(reply_ids.nullable())),
+            ),
+        )
+        .execute(conn)
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
@@ -0,0 +1,212 @@
+use std::error::Error;
+
+use confession_bot_rs::{
+    crypto::{legacy_hashes, AuthorKeys},
+    run, DbConnection, DbPool,
+};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+};
+use tracing::info;
+
+use crate::{
+    db_impl::sql::is_unique_violation,
+    schema::{author_bans, author_posts, authors, confession, delete_votes, filter_hits, replies},
+};
+
+/// Get (or create) the author row for a user within a guild.
+///
+/// Rows hashed with the previous key are re-keyed in place. Rows left over
+/// from unkeyed hashing (a user may have several) are shared between guilds,
+/// so everything this guild holds against them (confessions, replies, votes,
+/// bans, post history and filter hits) is moved onto a fresh row, and each
+/// old row is dropped once nothing refers to it.
+pub async fn insert_author(
+    pool: &DbPool,
+    keys: &AuthorKeys,
+    guild_id: &String,
+    user_id: &String,
+) -> Result<i32, Box<dyn Error + Send + Sync>> {
+    let keys = keys.clone();
+    let guild_id = guild_id.clone();
+    let user_id = user_id.clone();
+    run(pool, move |conn| {
+        let hash = keys.pseudonym(&guild_id, &user_id);
+
+        if let Some(id) = find_author(conn, &hash)? {
+            return Ok(id);
+        }
+
+        if let Some(previous) = keys.previous_pseudonym(&guild_id, &user_id) {
+            if let Some(id) = find_author(conn, &previous)? {
+                diesel::update(authors::table.filter(authors::id.eq(id)))
+                    .set((authors::hash.eq(&hash), authors::key_id.eq(keys.key_id())))
+                    .execute(conn)?;
+                info!("Re-keyed author {} with the current key", id);
+                return Ok(id);
+            }
+        }
+
+        let legacy_ids = authors::table
+            .select(authors::id)
+            .filter(
+                authors::hash
+                    .eq_any(legacy_hashes(&user_id))
+                    .and(authors::guild_id.is_null()),
+            )
+            .load::<i32>(conn)?;
+        if !legacy_ids.is_empty() {
+            let id = conn.transaction(|conn| {
+                let id = diesel::insert_into(authors::table)
+                    .values((
+                        authors::hash.eq(&hash),
+                        authors::guild_id.eq(&guild_id),
+                        authors::key_id.eq(keys.key_id()),
+                    ))
+                    .returning(authors::id)
+                    .get_result::<i32>(conn)?;
+
+                diesel::update(
+                    confession::table.filter(
+                        confession::author
+                            .eq_any(&legacy_ids)
+                            .and(confession::guild_id.eq(&guild_id)),
+                    ),
+                )
+                .set(confession::author.eq(id))
+                .execute(conn)?;
+                diesel::update(
+                    replies::table.filter(
+                        replies::author
+                            .eq_any(&legacy_ids)
+                            .and(replies::guild_id.eq(&guild_id)),
+                    ),
+                )
+                .set(replies::author.eq(id))
+                .execute(conn)?;
+                diesel::update(
+                    delete_votes::table.filter(
+                        delete_votes::author_id.eq_any(&legacy_ids).and(
+                            delete_votes::confession_id.eq_any(
+                                confession::table
+                                    .select(confession::id)
+                                    .filter(confession::guild_id.eq(&guild_id)),
+                            ),
+                        ),
+                    ),
+                )
+                .set(delete_votes::author_id.eq(id))
+                .execute(conn)?;
+                diesel::update(
+                    author_bans::table.filter(
+                        author_bans::author_id
+                            .eq_any(&legacy_ids)
+                            .and(author_bans::guild_id.eq(&guild_id)),
+                    ),
+                )
+                .set(author_bans::author_id.eq(id))
+                .execute(conn)?;
+                diesel::update(
+                    author_posts::table.filter(
+                        author_posts::author_id
+                            .eq_any(&legacy_ids)
+                            .and(author_posts::guild_id.eq(&guild_id)),
+                    ),
+                )
+                .set(author_posts::author_id.eq(id))
+                .execute(conn)?;
+                diesel::update(
+                    filter_hits::table.filter(
+                        filter_hits::author_id
+                            .eq_any(&legacy_ids)
+                            .and(filter_hits::guild_id.eq(&guild_id)),
+                    ),
+                )
+                .set(filter_hits::author_id.eq(id))
+                .execute(conn)?;
+
+                for &legacy_id in &legacy_ids {
+                    let still_used = confession::table
+                        .filter(confession::author.eq(legacy_id))
+                        .count()
+                        .get_result::<i64>(conn)?
+                        + replies::table
+                            .filter(replies::author.eq(legacy_id))
+                            .count()
+                            .get_result::<i64>(conn)?
+                        + delete_votes::table
+                            .filter(delete_votes::author_id.eq(legacy_id))
+                            .count()
+                            .get_result::<i64>(conn)?
+                        + author_bans::table
+                            .filter(author_bans::author_id.eq(legacy_id))
+                            .count()
+                            .get_result::<i64>(conn)?
+                        + author_posts::table
+                            .filter(author_posts::author_id.eq(legacy_id))
+                            .count()
+                            .get_result::<i64>(conn)?
+                        + filter_hits::table
+                            .filter(filter_hits::author_id.eq(legacy_id))
+                            .count()
+                            .get_result::<i64>(conn)?;
+                    if still_used == 0 {
+                        diesel::delete(authors::table.filter(authors::id.eq(legacy_id)))
+                            .execute(conn)?;
+                    }
+                }
+                Ok::<i32, diesel::result::Error>(id)
+            })?;
+            info!(
+                "Moved legacy authors {:?} onto keyed author {}",
+                legacy_ids, id
+            );
+            return Ok(id);
+        }
+
+        match diesel::insert_into(authors::table)
+            .values((
+                authors::hash.eq(&hash),
+                authors::guild_id.eq(&guild_id),
+                authors::key_id.eq(keys.key_id()),
+            ))
+            .returning(authors::id)
+            .get_result::<i32>(conn)
+        {
+            Ok(id) => Ok(id),
+            // We have had a conflict, and can access the ID using the hash
+            Err(e) if is_unique_violation(&e) => {
+                Ok(find_author(conn, &hash)?.ok_or("Author vanished after a conflict")?)
+            }
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// How many authors are not keyed with `key_id`: rows from before a rotation,
+/// and rows left over from unkeyed hashing.
+pub async fn count_stale_authors(
+    pool: &DbPool,
+    key_id: &String,
+) -> Result<i64, Box<dyn Error + Send + Sync>> {
+    let key_id = key_id.clone();
+    run(pool, move |conn| {
+        Ok(authors::table
+            .filter(authors::key_id.is_null().or(authors::key_id.ne(&key_id)))
+            .count()
+            .get_result::<i64>(conn)?)
+    })
+    .await
+}
+
+fn find_author(
+    conn: &mut DbConnection,
+    hash: &String,
+) -> Result<Option<i32>, diesel::result::Error> {
+    authors::table
+        .select(authors::id)
+        .filter(authors::hash.eq(hash))
+        .first::<i32>(conn)
+        .optional()
+}
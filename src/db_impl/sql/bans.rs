@@ -0,0 +1,101 @@
+use std::error::Error;
+
+use chrono::{NaiveDateTime, Utc};
+use confession_bot_rs::{run, DbPool};
+use diesel::{dsl::count_star, BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::{db_impl::sql::is_unique_violation, schema::author_bans};
+
+pub async fn ban_author(
+    pool: &DbPool,
+    guild_id: &String,
+    author_id: i32,
+    moderator_id: &String,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    let moderator_id = moderator_id.clone();
+    run(pool, move |conn| {
+        match diesel::insert_into(author_bans::table)
+            .values((
+                author_bans::guild_id.eq(&guild_id),
+                author_bans::author_id.eq(author_id),
+                author_bans::moderator_id.eq(&moderator_id),
+                author_bans::expires_at.eq(expires_at),
+            ))
+            .execute(conn)
+        {
+            Ok(_) => Ok(()),
+            // Already banned, so the new ban replaces the old one
+            Err(e) if is_unique_violation(&e) => {
+                diesel::update(
+                    author_bans::table.filter(
+                        author_bans::guild_id
+                            .eq(&guild_id)
+                            .and(author_bans::author_id.eq(author_id)),
+                    ),
+                )
+                .set((
+                    author_bans::moderator_id.eq(&moderator_id),
+                    author_bans::expires_at.eq(expires_at),
+                    author_bans::timestamp.eq(diesel::dsl::now),
+                ))
+                .execute(conn)?;
+                Ok(())
+            }
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn unban_author(
+    pool: &DbPool,
+    guild_id: &String,
+    author_id: i32,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    run(pool, move |conn| {
+        match diesel::delete(
+            author_bans::table.filter(
+                author_bans::guild_id
+                    .eq(&guild_id)
+                    .and(author_bans::author_id.eq(author_id)),
+            ),
+        )
+        .execute(conn)
+        {
+            Ok(deleted) => Ok(deleted > 0),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn is_author_banned(
+    pool: &DbPool,
+    guild_id: &String,
+    author_id: i32,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    run(pool, move |conn| {
+        match author_bans::table
+            .select(count_star())
+            .filter(
+                author_bans::guild_id
+                    .eq(&guild_id)
+                    .and(author_bans::author_id.eq(author_id))
+                    .and(
+                        author_bans::expires_at
+                            .is_null()
+                            .or(author_bans::expires_at.gt(Utc::now().naive_utc())),
+                    ),
+            )
+            .first::<i64>(conn)
+        {
+            Ok(bans) => Ok(bans > 0),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
@@ -0,0 +1,469 @@
+use std::error::Error;
+
+use chrono::NaiveDateTime;
+use confession_bot_rs::{crypto::AuthorKeys, run, ConfessionStatus, DbConnection, DbPool};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    SelectableHelper,
+};
+
+use crate::{
+    db_impl::{
+        sql::{authors::insert_author, returning, Lock},
+        AuthorEscrow,
+    },
+    models::{Confession, ConfessionDraft},
+    schema::{
+        attachments, confession, confession_revisions,
+        guild::{self},
+        outbox, reveal_shares,
+    },
+};
+
+/// Get a confession by its number within the guild.
+pub async fn get_confession_by_id_guild(
+    pool: &DbPool,
+    confession_id: u32,
+    guild_id: &String,
+) -> Result<Confession, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    run(pool, move |conn| {
+        match confession::table
+            .filter(
+                confession::guild_id
+                    .eq(guild_id)
+                    .and(confession::number.eq(confession_id as i32)),
+            )
+            .select(Confession::as_select())
+            .get_result(conn)
+        {
+            Ok(c) => Ok(c),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn get_confession_by_message_id(
+    pool: &DbPool,
+    message_id: &String,
+    _guild_id: &String,
+) -> Result<Confession, Box<dyn Error + Send + Sync>> {
+    let message_id = message_id.clone();
+    let _guild_id = _guild_id.clone();
+    run(pool, move |conn| {
+        match guild::table
+            .inner_join(confession::table)
+            .filter(
+                confession::message_id
+                    .eq(message_id)
+                    .and(guild::guild_id.eq(_guild_id)),
+            )
+            .select(Confession::as_select())
+            .get_result(conn)
+        {
+            Ok(c) => Ok(c),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Insert a confession with its escrowed author, keeping only the hashes of
+/// its reveal shares.
+///
+/// The confession is given the next number in its guild, unless it is held for
+/// `review` or queued in the outbox until `release_at`. It has no message
+/// until [`set_confession_message`] is called once it has been posted.
+pub async fn insert_confession(
+    pool: &DbPool,
+    keys: &AuthorKeys,
+    escrow: &AuthorEscrow,
+    _author_id: &String,
+    _guild_id: &String,
+    draft: &ConfessionDraft,
+    review: bool,
+    release_at: Option<NaiveDateTime>,
+) -> Result<Confession, Box<dyn Error + Send + Sync>> {
+    let release_at = release_at.filter(|_| !review);
+    let author_id = insert_author(pool, keys, _guild_id, _author_id).await?;
+    let escrow = escrow.clone();
+    let status: String = match (review, release_at) {
+        (true, _) => ConfessionStatus::PENDING,
+        (false, Some(_)) => ConfessionStatus::QUEUED,
+        (false, None) => ConfessionStatus::ACTIVE,
+    }
+    .into();
+
+    let draft = draft.clone();
+    let guild_id = _guild_id.clone();
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            // Lock the guild so two confessions cannot be given the same
+            // number
+            conn.lock_guild(&guild_id)?;
+            let number = match review || release_at.is_some() {
+                true => None,
+                false => Some(next_number(conn, &guild_id)?),
+            };
+            let inserted: Confession = diesel::insert_into(confession::table)
+                .values((
+                    confession::content.eq(draft.content),
+                    confession::title.eq(draft.title),
+                    confession::content_warning.eq(draft.content_warning),
+                    confession::skip_role_ping.eq(draft.skip_role_ping),
+                    confession::guild_id.eq(&guild_id),
+                    confession::message_id.eq(""),
+                    confession::author.eq(author_id),
+                    confession::author_escrow.eq(escrow.sealed),
+                    confession::reveal_threshold.eq(escrow.threshold.map(i32::from)),
+                    confession::number.eq(number),
+                    confession::status.eq(status),
+                    confession::review_notice.eq(escrow.review_notice),
+                ))
+                .returning(returning::<Confession>())
+                .get_result(conn)?;
+            for share in escrow.shares {
+                diesel::insert_into(reveal_shares::table)
+                    .values((
+                        reveal_shares::confession_id.eq(inserted.id),
+                        reveal_shares::share_index.eq(share.index as i32),
+                        reveal_shares::share_hash.eq(share.hash()),
+                        reveal_shares::moderator_id.eq(share.moderator_id),
+                    ))
+                    .execute(conn)?;
+            }
+            if let Some(attachment) = draft.attachment {
+                diesel::insert_into(attachments::table)
+                    .values((
+                        attachments::guild_id.eq(&guild_id),
+                        attachments::confession_id.eq(inserted.id),
+                        attachments::filename.eq(attachment.filename),
+                        attachments::content_type.eq(attachment.content_type),
+                        attachments::data.eq(attachment.data),
+                    ))
+                    .execute(conn)?;
+            }
+            if let Some(release_at) = release_at {
+                diesel::insert_into(outbox::table)
+                    .values((
+                        outbox::confession_id.eq(inserted.id),
+                        outbox::guild_id.eq(&guild_id),
+                        outbox::release_at.eq(release_at),
+                    ))
+                    .execute(conn)?;
+            }
+            Ok(inserted)
+        })
+    })
+    .await
+}
+
+/// Make a pending confession active and give it the next number in its guild.
+/// # Returns
+/// The approved confession, or `None` if it was not pending.
+pub async fn approve_confession(
+    pool: &DbPool,
+    guild_id: &String,
+    confession_id: i32,
+) -> Result<Option<Confession>, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    let pending: String = ConfessionStatus::PENDING.into();
+    let active: String = ConfessionStatus::ACTIVE.into();
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            // Lock the guild so two confessions cannot be given the same
+            // number
+            conn.lock_guild(&guild_id)?;
+            let number = next_number(conn, &guild_id)?;
+            diesel::update(
+                confession::table.filter(
+                    confession::id
+                        .eq(confession_id)
+                        .and(confession::guild_id.eq(&guild_id))
+                        .and(confession::status.eq(pending)),
+                ),
+            )
+            .set((
+                confession::status.eq(active),
+                confession::status_changed_at.eq(diesel::dsl::now),
+                confession::number.eq(number),
+            ))
+            .returning(returning::<Confession>())
+            .get_result(conn)
+            .optional()
+        })
+        .map_err(Box::from)
+    })
+    .await
+}
+
+/// Reject a pending confession.
+/// # Returns
+/// Whether the confession was pending.
+pub async fn reject_confession(
+    pool: &DbPool,
+    guild_id: &String,
+    confession_id: i32,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    let pending: String = ConfessionStatus::PENDING.into();
+    let rejected: String = ConfessionStatus::REJECTED.into();
+    run(pool, move |conn| {
+        match diesel::update(
+            confession::table.filter(
+                confession::id
+                    .eq(confession_id)
+                    .and(confession::guild_id.eq(guild_id))
+                    .and(confession::status.eq(pending)),
+            ),
+        )
+        .set((
+            confession::status.eq(rejected),
+            confession::status_changed_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)
+        {
+            Ok(updated) => Ok(updated > 0),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Put an approved confession which could not be posted back into review,
+/// freeing its number.
+pub async fn return_confession_to_review(
+    pool: &DbPool,
+    confession_id: i32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let pending: String = ConfessionStatus::PENDING.into();
+    run(pool, move |conn| {
+        match diesel::update(confession::table.filter(confession::id.eq(confession_id)))
+            .set((
+                confession::status.eq(pending),
+                confession::status_changed_at.eq(None::<NaiveDateTime>),
+                confession::number.eq(None::<i32>),
+            ))
+            .execute(conn)
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Clear and return the sealed review notice.
+pub async fn take_review_notice(
+    pool: &DbPool,
+    confession_id: i32,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            let notice = confession::table
+                .select(confession::review_notice)
+                .filter(confession::id.eq(confession_id))
+                .first::<Option<String>>(conn)
+                .optional()?
+                .flatten();
+            diesel::update(confession::table.filter(confession::id.eq(confession_id)))
+                .set(confession::review_notice.eq(None::<String>))
+                .execute(conn)?;
+            Ok(notice)
+        })
+    })
+    .await
+}
+
+/// Link a confession to the message it was posted as, and the channel it
+/// was posted in.
+pub async fn set_confession_message(
+    pool: &DbPool,
+    confession_id: i32,
+    channel_id: &String,
+    message_id: &String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let channel_id = channel_id.clone();
+    let message_id = message_id.clone();
+    run(pool, move |conn| {
+        match diesel::update(confession::table.filter(confession::id.eq(confession_id)))
+            .set((
+                confession::channel_id.eq(channel_id),
+                confession::message_id.eq(message_id),
+            ))
+            .execute(conn)
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Remove a confession which could not be posted, along with its reveal
+/// shares, attachment and any place in the outbox.
+pub async fn delete_confession(
+    pool: &DbPool,
+    confession_id: i32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            diesel::delete(
+                reveal_shares::table.filter(reveal_shares::confession_id.eq(confession_id)),
+            )
+            .execute(conn)?;
+            diesel::delete(outbox::table.filter(outbox::confession_id.eq(confession_id)))
+                .execute(conn)?;
+            diesel::delete(
+                attachments::table.filter(attachments::confession_id.eq(Some(confession_id))),
+            )
+            .execute(conn)?;
+            diesel::delete(confession::table.filter(confession::id.eq(confession_id)))
+                .execute(conn)?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+/// Take down an active confession at its author's request.
+/// # Returns
+/// Whether the confession was active.
+pub async fn retract_confession(
+    pool: &DbPool,
+    confession_id: i32,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let active: String = ConfessionStatus::ACTIVE.into();
+    let deleted: String = ConfessionStatus::DELETED.into();
+    run(pool, move |conn| {
+        match diesel::update(
+            confession::table.filter(
+                confession::id
+                    .eq(confession_id)
+                    .and(confession::status.eq(active)),
+            ),
+        )
+        .set((
+            confession::status.eq(deleted),
+            confession::status_changed_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)
+        {
+            Ok(updated) => Ok(updated > 0),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Replace the text of an active confession, keeping what it said before as a
+/// revision.
+/// # Returns
+/// The edited confession, or `None` if it is no longer active.
+pub async fn edit_confession(
+    pool: &DbPool,
+    confession_id: i32,
+    draft: &ConfessionDraft,
+) -> Result<Option<Confession>, Box<dyn Error + Send + Sync>> {
+    let active: String = ConfessionStatus::ACTIVE.into();
+    let draft = draft.clone();
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            conn.lock_confession(confession_id)?;
+            let previous = match confession::table
+                .find(confession_id)
+                .filter(confession::status.eq(active))
+                .select(Confession::as_select())
+                .first(conn)
+                .optional()?
+            {
+                Some(previous) => previous,
+                None => return Ok(None),
+            };
+            diesel::insert_into(confession_revisions::table)
+                .values((
+                    confession_revisions::confession_id.eq(confession_id),
+                    confession_revisions::title.eq(previous.title),
+                    confession_revisions::content_warning.eq(previous.content_warning),
+                    confession_revisions::content.eq(previous.content),
+                ))
+                .execute(conn)?;
+            diesel::update(confession::table.find(confession_id))
+                .set((
+                    confession::title.eq(draft.title),
+                    confession::content_warning.eq(draft.content_warning),
+                    confession::content.eq(draft.content),
+                ))
+                .returning(returning::<Confession>())
+                .get_result(conn)
+                .optional()
+        })
+        .map_err(Box::from)
+    })
+    .await
+}
+
+pub async fn undo_confession_edit(
+    pool: &DbPool,
+    edited: &Confession,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let edited = edited.clone();
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            conn.lock_confession(edited.id)?;
+            let current = confession::table
+                .find(edited.id)
+                .select(Confession::as_select())
+                .first(conn)?;
+            // A later edit is left in place
+            if (&current.title, &current.content_warning, &current.content)
+                != (&edited.title, &edited.content_warning, &edited.content)
+            {
+                return Ok(());
+            }
+            let (revision_id, title, content_warning, content) = match confession_revisions::table
+                .filter(confession_revisions::confession_id.eq(edited.id))
+                .order(confession_revisions::id.desc())
+                .select((
+                    confession_revisions::id,
+                    confession_revisions::title,
+                    confession_revisions::content_warning,
+                    confession_revisions::content,
+                ))
+                .first::<(i32, Option<String>, Option<String>, String)>(conn)
+                .optional()?
+            {
+                Some(revision) => revision,
+                None => return Ok(()),
+            };
+            diesel::update(confession::table.find(edited.id))
+                .set((
+                    confession::title.eq(title),
+                    confession::content_warning.eq(content_warning),
+                    confession::content.eq(content),
+                ))
+                .execute(conn)?;
+            diesel::delete(confession_revisions::table.find(revision_id)).execute(conn)?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+pub fn next_number(
+    conn: &mut DbConnection,
+    guild_id: &String,
+) -> Result<i32, diesel::result::Error> {
+    // The highest number, found through the unique (guild_id, number) index
+    Ok(confession::table
+        .select(confession::number)
+        .filter(confession::guild_id.eq(guild_id))
+        .filter(confession::number.is_not_null())
+        .order(confession::number.desc())
+        .first::<Option<i32>>(conn)
+        .optional()?
+        .flatten()
+        .unwrap_or(0)
+        + 1)
+}
@@ -0,0 +1,56 @@
+use std::error::Error;
+
+use chrono::{NaiveDateTime, Utc};
+use confession_bot_rs::{run, DbPool};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::{
+    db_impl::{post_horizon, post_retry_at, sql::Lock},
+    models::PostLimits,
+    schema::author_posts,
+};
+
+pub async fn try_record_post(
+    pool: &DbPool,
+    guild_id: &String,
+    author_id: i32,
+    source: &str,
+    limits: PostLimits,
+) -> Result<Option<NaiveDateTime>, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    let source = source.to_string();
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            // Lock the author so two quick posts cannot both pass
+            conn.lock_author(author_id)?;
+            let now = Utc::now().naive_utc();
+            let author_source = author_posts::author_id
+                .eq(author_id)
+                .and(author_posts::source.eq(&source));
+            diesel::delete(author_posts::table.filter(
+                author_source.and(author_posts::timestamp.le(now - post_horizon(&limits))),
+            ))
+            .execute(conn)?;
+
+            let recent = author_posts::table
+                .select(author_posts::timestamp)
+                .filter(author_source)
+                .order(author_posts::timestamp.asc())
+                .load::<NaiveDateTime>(conn)?;
+            if let Some(retry_at) = post_retry_at(&limits, now, &recent) {
+                return Ok(Some(retry_at));
+            }
+
+            diesel::insert_into(author_posts::table)
+                .values((
+                    author_posts::guild_id.eq(&guild_id),
+                    author_posts::author_id.eq(author_id),
+                    author_posts::source.eq(&source),
+                    author_posts::timestamp.eq(now),
+                ))
+                .execute(conn)?;
+            Ok(None)
+        })
+    })
+    .await
+}
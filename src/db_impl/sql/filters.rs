@@ -0,0 +1,49 @@
+use std::error::Error;
+
+use confession_bot_rs::{run, DbPool};
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::{
+    models::{FilterHit, NewFilterHit},
+    schema::filter_hits,
+};
+
+pub async fn insert_filter_hits(
+    pool: &DbPool,
+    hits: Vec<NewFilterHit>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        // One row at a time, as not every database takes a batch insert
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            for hit in &hits {
+                diesel::insert_into(filter_hits::table)
+                    .values(hit)
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+        .map_err(Box::from)
+    })
+    .await
+}
+
+pub async fn get_filter_hits(
+    pool: &DbPool,
+    guild_id: &String,
+    limit: i64,
+) -> Result<Vec<FilterHit>, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    run(pool, move |conn| {
+        match filter_hits::table
+            .filter(filter_hits::guild_id.eq(&guild_id))
+            .order(filter_hits::id.desc())
+            .limit(limit)
+            .select(FilterHit::as_select())
+            .load::<FilterHit>(conn)
+        {
+            Ok(hits) => Ok(hits),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
@@ -0,0 +1,184 @@
+use std::error::Error;
+
+use chrono::{NaiveDateTime, SubsecRound, TimeDelta, Utc};
+use confession_bot_rs::{run, DbPool};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+};
+use tracing::warn;
+
+use crate::{
+    db_impl::{
+        default_guild_config,
+        sql::{is_unique_violation, Lock},
+        RolePingClaim,
+    },
+    models::{Guild, GuildConfig},
+    schema::guild::{
+        self, confession_channel_id as guildConfessionChannel, config as guildConfig,
+        guild_id as guildId,
+    },
+};
+
+pub async fn get_guild(
+    pool: &DbPool,
+    guild_id: &String,
+) -> Result<Option<Guild>, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    run(pool, move |conn| {
+        Ok(guild::table
+            .filter(guild::guild_id.eq(guild_id))
+            .first(conn)
+            .optional()?)
+    })
+    .await
+}
+
+pub async fn get_guild_config(
+    pool: &DbPool,
+    guild_id: &String,
+) -> Result<GuildConfig, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    run(pool, move |conn| {
+        match guild::table
+            .select(guild::config)
+            .filter(guild::guild_id.eq(guild_id))
+            .first::<String>(conn)
+        {
+            Ok(cfg) => Ok(serde_json::from_str::<GuildConfig>(cfg.as_str())?),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn insert_guild(
+    pool: &DbPool,
+    guild_id: &String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    match serde_json::to_string(&default_guild_config()) {
+        Ok(default_config_string) => {
+            run(pool, move |conn| {
+                match diesel::insert_into(guild::table)
+                    .values((guildId.eq(guild_id), guildConfig.eq(default_config_string)))
+                    .execute(conn)
+                {
+                    Err(e) if !is_unique_violation(&e) => Err(Box::from(e)),
+                    _ => Ok(()),
+                }
+            })
+            .await?;
+        }
+        Err(e) => {
+            warn!("Default guild config not accessible: {}", e)
+        }
+    }
+    Ok(())
+}
+
+pub async fn update_guild(
+    pool: &DbPool,
+    guild_id: &String,
+    confession_channel_id: Option<String>,
+    config: GuildConfig,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    match serde_json::to_string(&config) {
+        Ok(config_string) => {
+            run(pool, move |conn| {
+                diesel::update(guild::table.filter(guildId.eq(guild_id)))
+                    .set((
+                        guildConfig.eq(config_string),
+                        guildConfessionChannel.eq(confession_channel_id),
+                    ))
+                    .execute(conn)?;
+                Ok(())
+            })
+            .await?;
+        }
+        Err(e) => {
+            warn!("Guild config not accessible: {}", e)
+        }
+    }
+    Ok(())
+}
+
+pub async fn set_blackout(
+    pool: &DbPool,
+    guild_id: &String,
+    enabled: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            // Lock the guild so a config change made at the same time is not
+            // written over
+            conn.lock_guild(&guild_id)?;
+            let config = guild::table
+                .select(guild::config)
+                .filter(guildId.eq(&guild_id))
+                .first::<String>(conn)?;
+            let mut config = serde_json::from_str::<GuildConfig>(&config)?;
+            config.blackout = enabled;
+            diesel::update(guild::table.filter(guildId.eq(&guild_id)))
+                .set(guildConfig.eq(serde_json::to_string(&config)?))
+                .execute(conn)?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+pub async fn claim_role_ping(
+    pool: &DbPool,
+    guild_id: &String,
+    interval: i64,
+) -> Result<Option<RolePingClaim>, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    // Kept to the microsecond, as Postgres stores it, so that a release can
+    // match it again
+    let now = Utc::now().naive_utc().trunc_subsecs(6);
+    let since = now - TimeDelta::seconds(interval.max(0));
+    run(pool, move |conn| {
+        // Lock the guild so two confessions posted at once cannot both ping
+        conn.transaction(|conn| {
+            conn.lock_guild(&guild_id)?;
+            let previous = match guild::table
+                .select(guild::last_role_ping)
+                .filter(guildId.eq(&guild_id))
+                .first::<Option<NaiveDateTime>>(conn)
+                .optional()?
+            {
+                Some(previous) => previous,
+                None => return Ok(None),
+            };
+            if previous.is_some_and(|last| last > since) {
+                return Ok(None);
+            }
+            diesel::update(guild::table.filter(guildId.eq(&guild_id)))
+                .set(guild::last_role_ping.eq(now))
+                .execute(conn)?;
+            Ok(Some(RolePingClaim { at: now, previous }))
+        })
+    })
+    .await
+}
+
+pub async fn release_role_ping(
+    pool: &DbPool,
+    guild_id: &String,
+    claim: RolePingClaim,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    run(pool, move |conn| {
+        // Only while our claim is still the last ping, so a later one stays
+        diesel::update(
+            guild::table.filter(guildId.eq(guild_id).and(guild::last_role_ping.eq(claim.at))),
+        )
+        .set(guild::last_role_ping.eq(claim.previous))
+        .execute(conn)?;
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,746 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use confession_bot_rs::{
+    crypto::AuthorKeys, DbConnection, DbError, DbPool, MultiBackend, VoteType,
+};
+use diesel::{
+    result::{DatabaseErrorKind, Error},
+    ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, Selectable,
+};
+
+use crate::{
+    db_impl::{
+        AttachmentStore, AuthorEscrow, AuthorStore, BanStore, ConfessionStore, CooldownStore,
+        FilterStore, GuildStore, OutboxStore, ReplyStore, RevealStore, RolePingClaim,
+        ScheduleStore, VoteOutcome, VoteStore,
+    },
+    models::{
+        Confession, ConfessionDraft, FilterHit, Guild, GuildConfig, InsertSchedule, NewAttachment,
+        NewFilterHit, OutboxEntry, PostLimits, Reply, RevealShare, Schedule, StoredAttachment,
+    },
+    schema,
+};
+
+pub mod attachments;
+pub mod authors;
+pub mod bans;
+pub mod confessions;
+pub mod cooldowns;
+pub mod filters;
+pub mod guilds;
+pub mod outbox;
+pub mod reply;
+pub mod reveal;
+pub mod schedules;
+pub mod votes;
+
+/// The SQLite and Postgres backend. Queries run on the blocking pool through
+/// [`confession_bot_rs::run`], and are the same for both databases apart from
+/// [`Lock`].
+pub struct SqlStore {
+    pool: DbPool,
+}
+
+impl SqlStore {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// The columns a `RETURNING` clause reads `T` from. `T::as_returning()` is
+/// built for a single database, so it cannot be handed on to each of them.
+pub fn returning<T: Selectable<MultiBackend>>() -> T::SelectExpression {
+    T::construct_selection()
+}
+
+/// Whether an insert failed because the row is already there. Upserts are
+/// not shared by every database, so inserts which can race check for this.
+pub fn is_unique_violation(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)
+    )
+}
+
+/// How each database stops two transactions racing on the same rows. A lock
+/// is held until the transaction ends, and the first must be taken before the
+/// transaction reads anything.
+pub trait Lock {
+    fn lock_guild(&mut self, guild_id: &String) -> QueryResult<()>;
+
+    fn lock_author(&mut self, author_id: i32) -> QueryResult<()>;
+
+    fn lock_confession(&mut self, confession_id: i32) -> QueryResult<()>;
+}
+
+// SQLite allows one writer at a time, so any write holds every row. A write
+// which changes nothing takes the write lock without touching the data, and
+// waits out the busy timeout instead of failing like a late upgrade from a
+// read would.
+impl Lock for DbConnection {
+    fn lock_guild(&mut self, guild_id: &String) -> QueryResult<()> {
+        match self {
+            DbConnection::Sqlite(conn) => diesel::update(schema::guild::table.find(guild_id))
+                .set(schema::guild::confession_channel_id.eq(schema::guild::confession_channel_id))
+                .execute(conn),
+            #[cfg(feature = "postgres")]
+            DbConnection::Postgres(conn) => schema::guild::table
+                .find(guild_id)
+                .select(schema::guild::guild_id)
+                .for_update()
+                .execute(conn),
+        }
+        .map(|_| ())
+    }
+
+    fn lock_author(&mut self, author_id: i32) -> QueryResult<()> {
+        match self {
+            DbConnection::Sqlite(conn) => diesel::update(schema::authors::table.find(author_id))
+                .set(schema::authors::key_id.eq(schema::authors::key_id))
+                .execute(conn),
+            #[cfg(feature = "postgres")]
+            DbConnection::Postgres(conn) => schema::authors::table
+                .find(author_id)
+                .select(schema::authors::id)
+                .for_update()
+                .execute(conn),
+        }
+        .map(|_| ())
+    }
+
+    fn lock_confession(&mut self, confession_id: i32) -> QueryResult<()> {
+        match self {
+            DbConnection::Sqlite(conn) => {
+                diesel::update(schema::confession::table.find(confession_id))
+                    .set(schema::confession::status.eq(schema::confession::status))
+                    .execute(conn)
+            }
+            #[cfg(feature = "postgres")]
+            DbConnection::Postgres(conn) => schema::confession::table
+                .find(confession_id)
+                .select(schema::confession::id)
+                .for_update()
+                .execute(conn),
+        }
+        .map(|_| ())
+    }
+}
+
+#[async_trait]
+impl AttachmentStore for SqlStore {
+    async fn get_confession_attachment(
+        &self,
+        confession_id: i32,
+    ) -> Result<Option<StoredAttachment>, DbError> {
+        attachments::get_confession_attachment(&self.pool, confession_id).await
+    }
+
+    async fn delete_attachments(&self, confession_id: i32) -> Result<(), DbError> {
+        attachments::delete_attachments(&self.pool, confession_id).await
+    }
+}
+
+#[async_trait]
+impl AuthorStore for SqlStore {
+    async fn insert_author(
+        &self,
+        keys: &AuthorKeys,
+        guild_id: &String,
+        user_id: &String,
+    ) -> Result<i32, DbError> {
+        authors::insert_author(&self.pool, keys, guild_id, user_id).await
+    }
+
+    async fn count_stale_authors(&self, key_id: &String) -> Result<i64, DbError> {
+        authors::count_stale_authors(&self.pool, key_id).await
+    }
+}
+
+#[async_trait]
+impl BanStore for SqlStore {
+    async fn ban_author(
+        &self,
+        guild_id: &String,
+        author_id: i32,
+        moderator_id: &String,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<(), DbError> {
+        bans::ban_author(&self.pool, guild_id, author_id, moderator_id, expires_at).await
+    }
+
+    async fn unban_author(&self, guild_id: &String, author_id: i32) -> Result<bool, DbError> {
+        bans::unban_author(&self.pool, guild_id, author_id).await
+    }
+
+    async fn is_author_banned(&self, guild_id: &String, author_id: i32) -> Result<bool, DbError> {
+        bans::is_author_banned(&self.pool, guild_id, author_id).await
+    }
+}
+
+#[async_trait]
+impl ConfessionStore for SqlStore {
+    async fn get_confession_by_id_guild(
+        &self,
+        confession_id: u32,
+        guild_id: &String,
+    ) -> Result<Confession, DbError> {
+        confessions::get_confession_by_id_guild(&self.pool, confession_id, guild_id).await
+    }
+
+    async fn get_confession_by_message_id(
+        &self,
+        message_id: &String,
+        guild_id: &String,
+    ) -> Result<Confession, DbError> {
+        confessions::get_confession_by_message_id(&self.pool, message_id, guild_id).await
+    }
+
+    async fn insert_confession(
+        &self,
+        keys: &AuthorKeys,
+        escrow: &AuthorEscrow,
+        author_id: &String,
+        guild_id: &String,
+        draft: &ConfessionDraft,
+        review: bool,
+        release_at: Option<NaiveDateTime>,
+    ) -> Result<Confession, DbError> {
+        confessions::insert_confession(
+            &self.pool, keys, escrow, author_id, guild_id, draft, review, release_at,
+        )
+        .await
+    }
+
+    async fn approve_confession(
+        &self,
+        guild_id: &String,
+        confession_id: i32,
+    ) -> Result<Option<Confession>, DbError> {
+        confessions::approve_confession(&self.pool, guild_id, confession_id).await
+    }
+
+    async fn reject_confession(
+        &self,
+        guild_id: &String,
+        confession_id: i32,
+    ) -> Result<bool, DbError> {
+        confessions::reject_confession(&self.pool, guild_id, confession_id).await
+    }
+
+    async fn return_confession_to_review(&self, confession_id: i32) -> Result<(), DbError> {
+        confessions::return_confession_to_review(&self.pool, confession_id).await
+    }
+
+    async fn take_review_notice(&self, confession_id: i32) -> Result<Option<String>, DbError> {
+        confessions::take_review_notice(&self.pool, confession_id).await
+    }
+
+    async fn set_confession_message(
+        &self,
+        confession_id: i32,
+        channel_id: &String,
+        message_id: &String,
+    ) -> Result<(), DbError> {
+        confessions::set_confession_message(&self.pool, confession_id, channel_id, message_id).await
+    }
+
+    async fn delete_confession(&self, confession_id: i32) -> Result<(), DbError> {
+        confessions::delete_confession(&self.pool, confession_id).await
+    }
+
+    async fn retract_confession(&self, confession_id: i32) -> Result<bool, DbError> {
+        confessions::retract_confession(&self.pool, confession_id).await
+    }
+
+    async fn edit_confession(
+        &self,
+        confession_id: i32,
+        draft: &ConfessionDraft,
+    ) -> Result<Option<Confession>, DbError> {
+        confessions::edit_confession(&self.pool, confession_id, draft).await
+    }
+
+    async fn undo_confession_edit(&self, edited: &Confession) -> Result<(), DbError> {
+        confessions::undo_confession_edit(&self.pool, edited).await
+    }
+}
+
+#[async_trait]
+impl CooldownStore for SqlStore {
+    async fn try_record_post(
+        &self,
+        guild_id: &String,
+        author_id: i32,
+        source: &str,
+        limits: PostLimits,
+    ) -> Result<Option<NaiveDateTime>, DbError> {
+        cooldowns::try_record_post(&self.pool, guild_id, author_id, source, limits).await
+    }
+}
+
+#[async_trait]
+impl FilterStore for SqlStore {
+    async fn insert_filter_hits(&self, hits: Vec<NewFilterHit>) -> Result<(), DbError> {
+        filters::insert_filter_hits(&self.pool, hits).await
+    }
+
+    async fn get_filter_hits(
+        &self,
+        guild_id: &String,
+        limit: i64,
+    ) -> Result<Vec<FilterHit>, DbError> {
+        filters::get_filter_hits(&self.pool, guild_id, limit).await
+    }
+}
+
+#[async_trait]
+impl GuildStore for SqlStore {
+    async fn get_guild(&self, guild_id: &String) -> Result<Option<Guild>, DbError> {
+        guilds::get_guild(&self.pool, guild_id).await
+    }
+
+    async fn get_guild_config(&self, guild_id: &String) -> Result<GuildConfig, DbError> {
+        guilds::get_guild_config(&self.pool, guild_id).await
+    }
+
+    async fn insert_guild(&self, guild_id: &String) -> Result<(), DbError> {
+        guilds::insert_guild(&self.pool, guild_id).await
+    }
+
+    async fn update_guild(
+        &self,
+        guild_id: &String,
+        confession_channel_id: Option<String>,
+        config: GuildConfig,
+    ) -> Result<(), DbError> {
+        guilds::update_guild(&self.pool, guild_id, confession_channel_id, config).await
+    }
+
+    async fn set_blackout(&self, guild_id: &String, enabled: bool) -> Result<(), DbError> {
+        guilds::set_blackout(&self.pool, guild_id, enabled).await
+    }
+
+    async fn claim_role_ping(
+        &self,
+        guild_id: &String,
+        interval: i64,
+    ) -> Result<Option<RolePingClaim>, DbError> {
+        guilds::claim_role_ping(&self.pool, guild_id, interval).await
+    }
+
+    async fn release_role_ping(
+        &self,
+        guild_id: &String,
+        claim: &RolePingClaim,
+    ) -> Result<(), DbError> {
+        guilds::release_role_ping(&self.pool, guild_id, *claim).await
+    }
+}
+
+#[async_trait]
+impl OutboxStore for SqlStore {
+    async fn get_due_outbox(&self, now: NaiveDateTime) -> Result<Vec<OutboxEntry>, DbError> {
+        outbox::get_due_outbox(&self.pool, now).await
+    }
+
+    async fn release_confession(&self, confession_id: i32) -> Result<Option<Confession>, DbError> {
+        outbox::release_confession(&self.pool, confession_id).await
+    }
+
+    async fn remove_from_outbox(&self, entry_id: i32) -> Result<(), DbError> {
+        outbox::remove_from_outbox(&self.pool, entry_id).await
+    }
+
+    async fn fail_outbox(
+        &self,
+        entry_id: i32,
+        error: &String,
+        retry_at: Option<NaiveDateTime>,
+    ) -> Result<(), DbError> {
+        outbox::fail_outbox(&self.pool, entry_id, error, retry_at).await
+    }
+}
+
+#[async_trait]
+impl ReplyStore for SqlStore {
+    async fn get_confession_replies(&self, confession_id: i32) -> Result<Vec<Reply>, DbError> {
+        reply::get_confession_replies(&self.pool, confession_id).await
+    }
+
+    async fn insert_reply(
+        &self,
+        keys: &AuthorKeys,
+        confession_id: i32,
+        guild_id: &String,
+        message_id: &String,
+        content: &String,
+        author_id: &String,
+        attachment: Option<&NewAttachment>,
+    ) -> Result<(), DbError> {
+        reply::insert_reply(
+            &self.pool,
+            keys,
+            confession_id,
+            guild_id,
+            message_id,
+            content,
+            author_id,
+            attachment,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl RevealStore for SqlStore {
+    async fn insert_reveal_approval(
+        &self,
+        confession_id: i32,
+        moderator_id: &String,
+        share: &String,
+    ) -> Result<Vec<String>, DbError> {
+        reveal::insert_reveal_approval(&self.pool, confession_id, moderator_id, share).await
+    }
+
+    async fn clear_reveal_approvals(&self, confession_id: i32) -> Result<(), DbError> {
+        reveal::clear_reveal_approvals(&self.pool, confession_id).await
+    }
+
+    async fn get_reveal_shares(
+        &self,
+        confession_id: i32,
+        moderator_ids: &[String],
+    ) -> Result<Vec<RevealShare>, DbError> {
+        reveal::get_reveal_shares(&self.pool, confession_id, moderator_ids).await
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for SqlStore {
+    async fn get_due_schedules(&self, now: i64) -> Result<Vec<Schedule>, DbError> {
+        schedules::get_due_schedules(&self.pool, now).await
+    }
+
+    async fn next_schedule_at(&self) -> Result<Option<i64>, DbError> {
+        schedules::next_schedule_at(&self.pool).await
+    }
+
+    async fn get_guild_schedules(
+        &self,
+        guild_id: &String,
+        now: i64,
+    ) -> Result<Vec<Schedule>, DbError> {
+        schedules::get_guild_schedules(&self.pool, guild_id, now).await
+    }
+
+    async fn get_enforced_schedule(
+        &self,
+        guild_id: &String,
+        victim_id: &String,
+        now: i64,
+    ) -> Result<Option<Schedule>, DbError> {
+        schedules::get_enforced_schedule(&self.pool, guild_id, victim_id, now).await
+    }
+
+    async fn insert_schedule(&self, insert_schedule: InsertSchedule) -> Result<Schedule, DbError> {
+        schedules::insert_schedule(&self.pool, insert_schedule).await
+    }
+
+    async fn update_schedule(
+        &self,
+        guild_id: &String,
+        schedule_id: i32,
+        start_at: i64,
+        ends_at: Option<i64>,
+    ) -> Result<Option<Schedule>, DbError> {
+        schedules::update_schedule(&self.pool, guild_id, schedule_id, start_at, ends_at).await
+    }
+
+    async fn cancel_schedule(
+        &self,
+        guild_id: &String,
+        schedule_id: i32,
+    ) -> Result<Option<Schedule>, DbError> {
+        schedules::cancel_schedule(&self.pool, guild_id, schedule_id).await
+    }
+
+    async fn complete_schedule(&self, schedule_id: i32) -> Result<(), DbError> {
+        schedules::complete_schedule(&self.pool, schedule_id).await
+    }
+
+    async fn reapply_schedule(&self, schedule_id: i32, now: i64) -> Result<(), DbError> {
+        schedules::reapply_schedule(&self.pool, schedule_id, now).await
+    }
+
+    async fn fail_schedule(
+        &self,
+        schedule_id: i32,
+        error: &String,
+        retry_at: Option<i64>,
+    ) -> Result<(), DbError> {
+        schedules::fail_schedule(&self.pool, schedule_id, error, retry_at).await
+    }
+}
+
+#[async_trait]
+impl VoteStore for SqlStore {
+    async fn get_vote(
+        &self,
+        message_id: &String,
+        guild_id: &String,
+        vote_type: VoteType,
+    ) -> Result<(u32, u32), DbError> {
+        votes::get_vote(&self.pool, message_id, guild_id, vote_type).await
+    }
+
+    async fn update_vote(
+        &self,
+        keys: &AuthorKeys,
+        author_id: &String,
+        message_id: &String,
+        guild_id: &String,
+        vote_type: VoteType,
+    ) -> Result<VoteOutcome, DbError> {
+        votes::update_vote(&self.pool, keys, author_id, message_id, guild_id, vote_type).await
+    }
+}
+
+// Each test runs on a fresh SQLite file, and on Postgres as well when
+// TEST_POSTGRES_URL names a server to use.
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use chrono::{TimeDelta, Utc};
+    use confession_bot_rs::{crypto::EscrowKey, establish_pool, run_migrations, ConfessionStatus};
+
+    use super::*;
+    use crate::db_impl::{default_guild_config, escrow_author};
+
+    /// Postgres tests share a database, so migrations are applied one at a
+    /// time.
+    static MIGRATING: Mutex<()> = Mutex::new(());
+
+    fn keys() -> AuthorKeys {
+        AuthorKeys::new(&[7; 32], None)
+    }
+
+    /// A store on each backend, with a guild using `config()`. Guild ids are
+    /// new on every run, as a Postgres database outlives the tests.
+    async fn stores(name: &str, config: fn() -> GuildConfig) -> Vec<(SqlStore, String)> {
+        let path = std::env::temp_dir().join(format!(
+            "confession-bot-{name}-{}.sqlite3",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let mut db_urls = vec![path.to_string_lossy().into_owned()];
+        db_urls.extend(std::env::var("TEST_POSTGRES_URL").ok());
+
+        let mut stores = vec![];
+        for db_url in db_urls {
+            let pool = establish_pool(&db_url).unwrap();
+            {
+                let _migrating = MIGRATING.lock().unwrap();
+                run_migrations(&mut pool.get().unwrap()).unwrap();
+            }
+            let store = SqlStore::new(pool);
+            let guild_id = Utc::now().timestamp_nanos_opt().unwrap().to_string();
+            store.insert_guild(&guild_id).await.unwrap();
+            store
+                .update_guild(&guild_id, Some("2".to_string()), config())
+                .await
+                .unwrap();
+            stores.push((store, guild_id));
+        }
+        stores
+    }
+
+    async fn confess(
+        store: &SqlStore,
+        guild_id: &String,
+        release_at: Option<NaiveDateTime>,
+    ) -> Confession {
+        let config = store.get_guild_config(guild_id).await.unwrap();
+        let escrow_key = EscrowKey::new(&[9; 32]).unwrap();
+        let author_id = "10".to_string();
+        let escrow = escrow_author(&escrow_key, &config, guild_id, &author_id, false).unwrap();
+        let draft = ConfessionDraft {
+            content: "A confession".to_string(),
+            ..Default::default()
+        };
+        store
+            .insert_confession(
+                &keys(),
+                &escrow,
+                &author_id,
+                guild_id,
+                &draft,
+                false,
+                release_at,
+            )
+            .await
+            .unwrap()
+    }
+
+    async fn vote(store: &SqlStore, guild_id: &String, voter: &str) -> VoteOutcome {
+        store
+            .update_vote(
+                &keys(),
+                &voter.to_string(),
+                &"100".to_string(),
+                guild_id,
+                VoteType::DELETE,
+            )
+            .await
+            .unwrap()
+    }
+
+    /// Two votes delete a confession.
+    fn vote_config() -> GuildConfig {
+        let mut config = default_guild_config();
+        config.delete_vote_min = 2;
+        config
+    }
+
+    #[tokio::test]
+    async fn votes_reach_the_minimum_once() {
+        for (store, guild_id) in stores("votes", vote_config).await {
+            let confession = confess(&store, &guild_id, None).await;
+            store
+                .set_confession_message(confession.id, &"2".to_string(), &"100".to_string())
+                .await
+                .unwrap();
+
+            assert!(matches!(
+                vote(&store, &guild_id, "20").await,
+                VoteOutcome::Counted(1, 2)
+            ));
+            assert!(matches!(
+                vote(&store, &guild_id, "20").await,
+                VoteOutcome::Counted(0, 2)
+            ));
+            assert!(matches!(
+                vote(&store, &guild_id, "20").await,
+                VoteOutcome::Counted(1, 2)
+            ));
+            assert!(matches!(
+                vote(&store, &guild_id, "21").await,
+                VoteOutcome::Reached(2)
+            ));
+            assert!(matches!(
+                vote(&store, &guild_id, "22").await,
+                VoteOutcome::Closed
+            ));
+
+            let confession = store
+                .get_confession_by_message_id(&"100".to_string(), &guild_id)
+                .await
+                .unwrap();
+            assert_eq!(
+                Into::<ConfessionStatus>::into(confession.status),
+                ConfessionStatus::DELETED
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_confessions_are_numbered_on_release() {
+        for (store, guild_id) in stores("release", default_guild_config).await {
+            let release_at = Utc::now().naive_utc() + TimeDelta::minutes(10);
+            let queued = confess(&store, &guild_id, Some(release_at)).await;
+            let posted = confess(&store, &guild_id, None).await;
+            assert_eq!(queued.number, None);
+            assert_eq!(posted.number, Some(1));
+
+            let due = store.get_due_outbox(release_at).await.unwrap();
+            assert!(due.iter().any(|entry| entry.confession_id == queued.id));
+
+            let released = store.release_confession(queued.id).await.unwrap().unwrap();
+            assert_eq!(released.number, Some(2));
+            // Released again before it was posted, it keeps its number
+            let again = store.release_confession(queued.id).await.unwrap().unwrap();
+            assert_eq!(again.number, Some(2));
+        }
+    }
+
+    #[tokio::test]
+    async fn authors_and_bans_are_written_once() {
+        for (store, guild_id) in stores("bans", default_guild_config).await {
+            let user_id = "10".to_string();
+            let author_id = store
+                .insert_author(&keys(), &guild_id, &user_id)
+                .await
+                .unwrap();
+            assert_eq!(
+                store
+                    .insert_author(&keys(), &guild_id, &user_id)
+                    .await
+                    .unwrap(),
+                author_id
+            );
+
+            let moderator_id = "30".to_string();
+            let expired = Utc::now().naive_utc() - TimeDelta::minutes(1);
+            store
+                .ban_author(&guild_id, author_id, &moderator_id, Some(expired))
+                .await
+                .unwrap();
+            assert!(!store.is_author_banned(&guild_id, author_id).await.unwrap());
+            // Banning again replaces the expired ban
+            store
+                .ban_author(&guild_id, author_id, &moderator_id, None)
+                .await
+                .unwrap();
+            assert!(store.is_author_banned(&guild_id, author_id).await.unwrap());
+            assert!(store.unban_author(&guild_id, author_id).await.unwrap());
+            assert!(!store.unban_author(&guild_id, author_id).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn role_pings_are_claimed_once() {
+        for (store, guild_id) in stores("pings", default_guild_config).await {
+            let first = store.claim_role_ping(&guild_id, 60).await.unwrap().unwrap();
+            assert!(store
+                .claim_role_ping(&guild_id, 60)
+                .await
+                .unwrap()
+                .is_none());
+            // A ping which was never sent hands the window back
+            store.release_role_ping(&guild_id, &first).await.unwrap();
+            let second = store.claim_role_ping(&guild_id, 60).await.unwrap().unwrap();
+            // A stale claim leaves a later one alone
+            store.release_role_ping(&guild_id, &first).await.unwrap();
+            assert!(store
+                .claim_role_ping(&guild_id, 60)
+                .await
+                .unwrap()
+                .is_none());
+            store.release_role_ping(&guild_id, &second).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn edits_which_cannot_be_shown_are_undone() {
+        for (store, guild_id) in stores("edits", default_guild_config).await {
+            let confession = confess(&store, &guild_id, None).await;
+            let draft = ConfessionDraft {
+                content: "An edit".to_string(),
+                ..Default::default()
+            };
+            let edited = store
+                .edit_confession(confession.id, &draft)
+                .await
+                .unwrap()
+                .unwrap();
+            store.undo_confession_edit(&edited).await.unwrap();
+            let undone = store
+                .get_confession_by_id_guild(1, &guild_id)
+                .await
+                .unwrap();
+            assert_eq!(undone.content, confession.content);
+            // Undoing again finds the text already changed
+            store.undo_confession_edit(&edited).await.unwrap();
+        }
+    }
+}
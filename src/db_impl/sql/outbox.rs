@@ -0,0 +1,127 @@
+use std::error::Error;
+
+use chrono::NaiveDateTime;
+use confession_bot_rs::{run, ConfessionStatus, DbPool};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+use crate::{
+    db_impl::sql::{confessions::next_number, returning, Lock},
+    models::{Confession, OutboxEntry},
+    schema::{confession, outbox},
+};
+
+pub async fn get_due_outbox(
+    pool: &DbPool,
+    now: NaiveDateTime,
+) -> Result<Vec<OutboxEntry>, Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        match outbox::table
+            .filter(outbox::dead.eq(false))
+            .filter(outbox::release_at.le(now))
+            .order(outbox::release_at.asc())
+            .select(OutboxEntry::as_select())
+            .load(conn)
+        {
+            Ok(entries) => Ok(entries),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Make a queued confession active and give it the next number in its guild.
+/// # Returns
+/// The released confession, or `None` if it can no longer be posted.
+pub async fn release_confession(
+    pool: &DbPool,
+    confession_id: i32,
+) -> Result<Option<Confession>, Box<dyn Error + Send + Sync>> {
+    let queued: String = ConfessionStatus::QUEUED.into();
+    let active: String = ConfessionStatus::ACTIVE.into();
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            conn.lock_confession(confession_id)?;
+            let released = match confession::table
+                .find(confession_id)
+                .select(Confession::as_select())
+                .first(conn)
+                .optional()?
+            {
+                Some(released) => released,
+                None => return Ok(None),
+            };
+            if released.status == active {
+                // Released before, but never posted
+                return Ok(released.message_id.is_empty().then_some(released));
+            }
+            if released.status != queued {
+                return Ok(None);
+            }
+            // Lock the guild so two confessions cannot be given the same
+            // number
+            conn.lock_guild(&released.guild_id)?;
+            let number = next_number(conn, &released.guild_id)?;
+            diesel::update(confession::table.find(confession_id))
+                .set((
+                    confession::status.eq(active),
+                    confession::status_changed_at.eq(diesel::dsl::now),
+                    confession::number.eq(number),
+                ))
+                .returning(returning::<Confession>())
+                .get_result(conn)
+                .optional()
+        })
+        .map_err(Box::from)
+    })
+    .await
+}
+
+pub async fn remove_from_outbox(
+    pool: &DbPool,
+    entry_id: i32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        match diesel::delete(outbox::table.find(entry_id)).execute(conn) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Record a failed attempt at posting a queued confession. It is tried again
+/// at `retry_at`, or without one is kept as a dead letter.
+pub async fn fail_outbox(
+    pool: &DbPool,
+    entry_id: i32,
+    error: &String,
+    retry_at: Option<NaiveDateTime>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let error = error.clone();
+    run(pool, move |conn| {
+        let failed = diesel::update(outbox::table.find(entry_id));
+        let result = match retry_at {
+            Some(retry_at) => failed
+                .set((
+                    outbox::attempts.eq(outbox::attempts + 1),
+                    outbox::last_error.eq(error),
+                    outbox::release_at.eq(retry_at),
+                ))
+                .execute(conn),
+            None => failed
+                .set((
+                    outbox::attempts.eq(outbox::attempts + 1),
+                    outbox::last_error.eq(error),
+                    outbox::dead.eq(true),
+                ))
+                .execute(conn),
+        };
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
@@ -0,0 +1,71 @@
+use std::error::Error;
+
+use confession_bot_rs::{crypto::AuthorKeys, run, DbPool};
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::{
+    db_impl::sql::authors::insert_author,
+    models::{NewAttachment, Reply},
+    schema::{attachments, replies},
+};
+
+pub async fn get_confession_replies(
+    pool: &DbPool,
+    confession_id: i32,
+) -> Result<Vec<Reply>, Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        match replies::table
+            .select(Reply::as_select())
+            .filter(replies::original_confession_id.eq(confession_id))
+            .load(conn)
+        {
+            Ok(replies) => Ok(replies),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn insert_reply(
+    pool: &DbPool,
+    keys: &AuthorKeys,
+    confession_id: i32,
+    guild_id: &String,
+    message_id: &String,
+    content: &String,
+    author_id: &String,
+    attachment: Option<&NewAttachment>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let author_id = insert_author(pool, keys, guild_id, author_id).await?;
+    let guild_id = guild_id.clone();
+    let message_id = message_id.clone();
+    let content = content.clone();
+    let attachment = attachment.cloned();
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            let reply_id = diesel::insert_into(replies::table)
+                .values((
+                    replies::original_confession_id.eq(confession_id),
+                    replies::content.eq(content),
+                    replies::guild_id.eq(&guild_id),
+                    replies::message_id.eq(message_id),
+                    replies::author.eq(author_id),
+                ))
+                .returning(replies::id)
+                .get_result::<i32>(conn)?;
+            if let Some(attachment) = attachment {
+                diesel::insert_into(attachments::table)
+                    .values((
+                        attachments::guild_id.eq(&guild_id),
+                        attachments::reply_id.eq(reply_id),
+                        attachments::filename.eq(attachment.filename),
+                        attachments::content_type.eq(attachment.content_type),
+                        attachments::data.eq(attachment.data),
+                    ))
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+    })
+    .await
+}
@@ -0,0 +1,92 @@
+use std::error::Error;
+
+use confession_bot_rs::{run, DbPool};
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::{
+    db_impl::sql::is_unique_violation,
+    models::RevealShare,
+    schema::{reveal_approvals, reveal_shares},
+};
+
+/// Record a moderator's approval to reveal a confession's author, with the
+/// share they were dealt. Approving twice has no effect.
+/// # Returns
+/// The shares given back so far, oldest first.
+pub async fn insert_reveal_approval(
+    pool: &DbPool,
+    confession_id: i32,
+    moderator_id: &String,
+    share: &String,
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    let moderator_id = moderator_id.clone();
+    let share = share.clone();
+    run(pool, move |conn| {
+        match diesel::insert_into(reveal_approvals::table)
+            .values((
+                reveal_approvals::confession_id.eq(confession_id),
+                reveal_approvals::moderator_id.eq(moderator_id),
+                reveal_approvals::share.eq(share),
+            ))
+            .execute(conn)
+        {
+            // Approving twice keeps the first approval
+            Err(e) if !is_unique_violation(&e) => return Err(Box::from(e)),
+            _ => {}
+        }
+
+        match reveal_approvals::table
+            .select(reveal_approvals::share)
+            .filter(reveal_approvals::confession_id.eq(confession_id))
+            .order(reveal_approvals::id)
+            .load::<Option<String>>(conn)
+        {
+            Ok(shares) => Ok(shares.into_iter().flatten().collect()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Forget the shares given back for a confession, once its author has been
+/// revealed.
+pub async fn clear_reveal_approvals(
+    pool: &DbPool,
+    confession_id: i32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        match diesel::update(
+            reveal_approvals::table.filter(reveal_approvals::confession_id.eq(confession_id)),
+        )
+        .set(reveal_approvals::share.eq(None::<String>))
+        .execute(conn)
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn get_reveal_shares(
+    pool: &DbPool,
+    confession_id: i32,
+    moderator_ids: &[String],
+) -> Result<Vec<RevealShare>, Box<dyn Error + Send + Sync>> {
+    let moderator_ids = moderator_ids.to_vec();
+    run(pool, move |conn| {
+        match reveal_shares::table
+            .select(RevealShare::as_select())
+            .filter(
+                reveal_shares::confession_id
+                    .eq(confession_id)
+                    .and(reveal_shares::moderator_id.eq_any(moderator_ids)),
+            )
+            .load(conn)
+        {
+            Ok(shares) => Ok(shares),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
@@ -0,0 +1,262 @@
+use std::error::Error;
+
+use confession_bot_rs::{run, DbPool, ScheduleStatus};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    SelectableHelper,
+};
+
+use crate::{
+    db_impl::sql::returning,
+    models::{InsertSchedule, Schedule},
+    schema::schedule,
+};
+
+/// Pending jobs due by `now`, across every guild, earliest first.
+pub async fn get_due_schedules(
+    pool: &DbPool,
+    now: i64,
+) -> Result<Vec<Schedule>, Box<dyn Error + Send + Sync>> {
+    let pending: String = ScheduleStatus::PENDING.into();
+    run(pool, move |conn| {
+        match schedule::table
+            .filter(schedule::status.eq(pending))
+            .filter(schedule::run_at.le(now))
+            .order(schedule::run_at.asc())
+            .select(Schedule::as_select())
+            .load::<Schedule>(conn)
+        {
+            Ok(schedules) => Ok(schedules),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn next_schedule_at(pool: &DbPool) -> Result<Option<i64>, Box<dyn Error + Send + Sync>> {
+    let pending: String = ScheduleStatus::PENDING.into();
+    run(pool, move |conn| {
+        match schedule::table
+            .filter(schedule::status.eq(pending))
+            .order(schedule::run_at.asc())
+            .select(schedule::run_at)
+            .first::<i64>(conn)
+            .optional()
+        {
+            Ok(run_at) => Ok(run_at),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// A guild's jobs which are waiting, applied or dead, soonest first. Applied
+/// jobs are left out once their timeout has ended.
+pub async fn get_guild_schedules(
+    pool: &DbPool,
+    guild_id: &String,
+    now: i64,
+) -> Result<Vec<Schedule>, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    let applied: String = ScheduleStatus::APPLIED.into();
+    run(pool, move |conn| {
+        match schedule::table
+            .filter(schedule::guild_id.eq(guild_id))
+            .filter(schedule::status.ne(applied).or(schedule::ends_at.gt(now)))
+            .order(schedule::start_at.asc())
+            .select(Schedule::as_select())
+            .load::<Schedule>(conn)
+        {
+            Ok(schedules) => Ok(schedules),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn get_enforced_schedule(
+    pool: &DbPool,
+    guild_id: &String,
+    victim_id: &String,
+    now: i64,
+) -> Result<Option<Schedule>, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    let victim_id = victim_id.clone();
+    let applied: String = ScheduleStatus::APPLIED.into();
+    run(pool, move |conn| {
+        match schedule::table
+            .filter(schedule::guild_id.eq(guild_id))
+            .filter(schedule::victim_id.eq(victim_id))
+            .filter(schedule::status.eq(applied))
+            .filter(schedule::enforce.eq(true))
+            .filter(schedule::ends_at.gt(now))
+            .order(schedule::ends_at.desc())
+            .select(Schedule::as_select())
+            .first::<Schedule>(conn)
+            .optional()
+        {
+            Ok(schedule) => Ok(schedule),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn insert_schedule(
+    pool: &DbPool,
+    insert_schedule: InsertSchedule,
+) -> Result<Schedule, Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        match diesel::insert_into(schedule::table)
+            .values((
+                &insert_schedule,
+                schedule::run_at.eq(insert_schedule.start_at),
+            ))
+            .returning(returning::<Schedule>())
+            .get_result(conn)
+        {
+            Ok(schedule) => Ok(schedule),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Move a job which has not been applied to new times. A dead job is given its
+/// attempts back.
+pub async fn update_schedule(
+    pool: &DbPool,
+    guild_id: &String,
+    schedule_id: i32,
+    start_at: i64,
+    ends_at: Option<i64>,
+) -> Result<Option<Schedule>, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    let pending: String = ScheduleStatus::PENDING.into();
+    let dead: String = ScheduleStatus::DEAD.into();
+    run(pool, move |conn| {
+        match diesel::update(
+            schedule::table
+                .filter(schedule::id.eq(schedule_id))
+                .filter(schedule::guild_id.eq(guild_id))
+                .filter(schedule::status.eq_any([pending.clone(), dead])),
+        )
+        .set((
+            schedule::start_at.eq(start_at),
+            schedule::run_at.eq(start_at),
+            schedule::ends_at.eq(ends_at),
+            schedule::status.eq(pending),
+            schedule::attempts.eq(0),
+            schedule::last_error.eq(None::<String>),
+        ))
+        .returning(returning::<Schedule>())
+        .get_result(conn)
+        .optional()
+        {
+            Ok(schedule) => Ok(schedule),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Remove a job from a guild, whatever its status.
+pub async fn cancel_schedule(
+    pool: &DbPool,
+    guild_id: &String,
+    schedule_id: i32,
+) -> Result<Option<Schedule>, Box<dyn Error + Send + Sync>> {
+    let guild_id = guild_id.clone();
+    run(pool, move |conn| {
+        match diesel::delete(
+            schedule::table
+                .filter(schedule::id.eq(schedule_id))
+                .filter(schedule::guild_id.eq(guild_id)),
+        )
+        .returning(returning::<Schedule>())
+        .get_result(conn)
+        .optional()
+        {
+            Ok(schedule) => Ok(schedule),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+pub async fn complete_schedule(
+    pool: &DbPool,
+    schedule_id: i32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let applied: String = ScheduleStatus::APPLIED.into();
+    run(pool, move |conn| {
+        match diesel::update(schedule::table.find(schedule_id))
+            .set(schedule::status.eq(applied))
+            .execute(conn)
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Record that an enforced timeout was put back at `now` after being lifted
+/// early.
+pub async fn reapply_schedule(
+    pool: &DbPool,
+    schedule_id: i32,
+    now: i64,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run(pool, move |conn| {
+        match diesel::update(schedule::table.find(schedule_id))
+            .set((
+                schedule::reapplied.eq(schedule::reapplied + 1),
+                schedule::reapplied_at.eq(now),
+            ))
+            .execute(conn)
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Record a failed attempt at a job. It is tried again at `retry_at`, or
+/// without one is kept as a dead letter.
+pub async fn fail_schedule(
+    pool: &DbPool,
+    schedule_id: i32,
+    error: &String,
+    retry_at: Option<i64>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let error = error.clone();
+    let pending: String = ScheduleStatus::PENDING.into();
+    let dead: String = ScheduleStatus::DEAD.into();
+    run(pool, move |conn| {
+        let failed = diesel::update(schedule::table.find(schedule_id));
+        let result = match retry_at {
+            Some(retry_at) => failed
+                .set((
+                    schedule::status.eq(pending),
+                    schedule::attempts.eq(schedule::attempts + 1),
+                    schedule::last_error.eq(error),
+                    schedule::run_at.eq(retry_at),
+                ))
+                .execute(conn),
+            None => failed
+                .set((
+                    schedule::status.eq(dead),
+                    schedule::attempts.eq(schedule::attempts + 1),
+                    schedule::last_error.eq(error),
+                ))
+                .execute(conn),
+        };
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
@@ -0,0 +1,165 @@
+use std::error::Error;
+
+use crate::{models::GuildConfig, schema::confession};
+use confession_bot_rs::{
+    crypto::AuthorKeys, run, schema::delete_votes, ConfessionStatus, DbPool, VoteType,
+    DELETE_VOTE_STR,
+};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, IntoSql, QueryDsl, RunQueryDsl,
+};
+use tracing::error;
+
+use crate::db_impl::{sql::Lock, vote_minimum, VoteOutcome};
+
+use super::{authors::insert_author, confessions::get_confession_by_message_id, guilds::get_guild};
+
+pub async fn get_vote(
+    pool: &DbPool,
+    message_id: &String,
+    guild_id: &String,
+    vote_type: VoteType,
+) -> Result<(u32, u32), Box<dyn Error + Send + Sync>> {
+    let guild = match get_guild(pool, guild_id).await? {
+        Some(guild) => guild,
+        None => {
+            return Err(Box::from(format!(
+                "Could not find a guild with Guild ID: {}",
+                &guild_id
+            )))
+        }
+    };
+
+    let config = match serde_json::from_str::<GuildConfig>(guild.config.as_str()) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            error!("Could not parse config {}. Reason: {:?}", guild.config, e);
+            return Err(Box::from(e));
+        }
+    };
+
+    let min_vote = vote_minimum(&config, &vote_type);
+
+    let vote_type_str: String = vote_type.into();
+
+    let confession = get_confession_by_message_id(pool, message_id, guild_id).await?;
+
+    run(pool, move |conn| {
+        match delete_votes::table
+            .filter(
+                delete_votes::confession_id
+                    .eq(confession.id)
+                    .and(delete_votes::vote_type.eq(&vote_type_str)),
+            )
+            .count()
+            .get_result::<i64>(conn)
+        {
+            Ok(count) => Ok((count as u32, min_vote)),
+            Err(e) => Err(Box::from(e)),
+        }
+    })
+    .await
+}
+
+/// Update the votes for the confession within the DB. If the user has already
+/// voted, their vote is removed.
+///
+/// The toggle and threshold check run in one transaction holding a lock on the
+/// confession, so concurrent votes are applied one at a time.
+pub async fn update_vote(
+    pool: &DbPool,
+    keys: &AuthorKeys,
+    author_id: &String,
+    message_id: &String,
+    guild_id: &String,
+    vote_type: VoteType,
+) -> Result<VoteOutcome, Box<dyn Error + Send + Sync>> {
+    let guild = match get_guild(pool, guild_id).await? {
+        Some(guild) => guild,
+        None => {
+            return Err(Box::from(format!(
+                "Could not find a guild with Guild ID: {}",
+                &guild_id
+            )))
+        }
+    };
+
+    let config = match serde_json::from_str::<GuildConfig>(guild.config.as_str()) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            error!("Could not parse config {}. Reason: {:?}", guild.config, e);
+            return Err(Box::from(e));
+        }
+    };
+
+    let min_vote = vote_minimum(&config, &vote_type);
+
+    let vote_type_str: String = vote_type.into();
+
+    let confession = get_confession_by_message_id(pool, message_id, guild_id).await?;
+
+    // Insert (or, if conflicting, get) the author for the keyed user ID
+    let author = insert_author(pool, keys, guild_id, author_id).await?;
+
+    run(pool, move |conn| {
+        conn.transaction(|conn| {
+            conn.lock_confession(confession.id)?;
+            let status: ConfessionStatus = confession::table
+                .select(confession::status)
+                .filter(confession::id.eq(confession.id))
+                .first::<String>(conn)?
+                .into();
+            if status != ConfessionStatus::ACTIVE {
+                return Ok(VoteOutcome::Closed);
+            }
+
+            let total_votes = delete_votes::table
+                .filter(
+                    delete_votes::confession_id
+                        .eq(confession.id)
+                        .and(delete_votes::vote_type.eq(&vote_type_str)),
+                )
+                .count()
+                .get_result::<i64>(conn)? as u32;
+
+            // User has already made a vote, so we will remove it
+            let removed = diesel::delete(delete_votes::table)
+                .filter(
+                    delete_votes::confession_id
+                        .eq(confession.id)
+                        .and(delete_votes::author_id.eq(author))
+                        .and(delete_votes::vote_type.eq(&vote_type_str)),
+                )
+                .execute(conn)?;
+            if removed > 0 {
+                return Ok(VoteOutcome::Counted(total_votes - 1, min_vote));
+            }
+
+            diesel::insert_into(delete_votes::table)
+                .values((
+                    delete_votes::confession_id.eq(confession.id),
+                    delete_votes::author_id.eq(author),
+                    delete_votes::vote_type.eq(&vote_type_str),
+                ))
+                .execute(conn)?;
+
+            if total_votes + 1 >= min_vote {
+                let status: String = match vote_type {
+                    VoteType::DELETE => ConfessionStatus::DELETED,
+                    VoteType::EXPOSE => ConfessionStatus::EXPOSED,
+                }
+                .into();
+                diesel::update(confession::table.filter(confession::id.eq(confession.id)))
+                    .set((
+                        confession::status.eq(status),
+                        confession::status_changed_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(conn)?;
+                return Ok(VoteOutcome::Reached(total_votes + 1));
+            }
+
+            Ok(VoteOutcome::Counted(total_votes + 1, min_vote))
+        })
+    })
+    .await
+}
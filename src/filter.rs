@@ -0,0 +1,140 @@
+use regex::{Regex, RegexBuilder};
+use tracing::warn;
+
+use crate::models::{FilterAction, FilterKind, FilterRule};
+
+/// Upper bound on the compiled size of a guild's regex rule, so a rule cannot
+/// make every confession expensive to check.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+const INVITE_PATTERN: &str = r"(discord\.gg|discord(app)?\.com/invite)/[\w-]+";
+const LINK_PATTERN: &str = r"https?://\S+";
+const MASK: char = '█';
+
+/// A rule which matched, with the first text it matched.
+pub struct FilterMatch<'a> {
+    pub rule: &'a FilterRule,
+    pub matched: String,
+}
+
+pub struct FilterVerdict<'a> {
+    /// The content with every masked match replaced.
+    pub content: String,
+    /// The most severe action of all matching rules, if any matched.
+    pub action: Option<FilterAction>,
+    pub matches: Vec<FilterMatch<'a>>,
+}
+
+/// Build the matcher for a rule. Fails for invalid or oversized regex rules.
+pub fn compile(kind: FilterKind, pattern: &str) -> Result<Regex, regex::Error> {
+    let pattern = match kind {
+        FilterKind::WORD => format!(r"\b{}\b", regex::escape(pattern.trim())),
+        FilterKind::REGEX => pattern.to_string(),
+        FilterKind::INVITE => INVITE_PATTERN.to_string(),
+        FilterKind::LINK => LINK_PATTERN.to_string(),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+/// Check content against a guild's rules.
+pub fn apply<'a>(rules: &'a [FilterRule], content: &str) -> FilterVerdict<'a> {
+    let mut verdict = FilterVerdict {
+        content: content.to_string(),
+        action: None,
+        matches: vec![],
+    };
+    for rule in rules {
+        let regex = match compile(rule.kind, &rule.pattern) {
+            Ok(regex) => regex,
+            Err(e) => {
+                // Rules are checked when added, so this only happens if the
+                // size limit has since changed
+                warn!("Skipping filter rule {}: {}", rule.id, e);
+                continue;
+            }
+        };
+        let matched = match regex.find(content) {
+            Some(m) => m.as_str().to_string(),
+            None => continue,
+        };
+        if rule.action == FilterAction::MASK {
+            verdict.content = regex
+                .replace_all(&verdict.content, |caps: &regex::Captures| {
+                    MASK.to_string().repeat(caps[0].chars().count())
+                })
+                .into_owned();
+        }
+        verdict.action = verdict.action.max(Some(rule.action));
+        verdict.matches.push(FilterMatch { rule, matched });
+    }
+    verdict
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i32, kind: FilterKind, pattern: &str, action: FilterAction) -> FilterRule {
+        FilterRule {
+            id,
+            kind,
+            pattern: pattern.to_string(),
+            action,
+        }
+    }
+
+    #[test]
+    fn words_match_whole_words_in_any_case() {
+        let rules = [rule(1, FilterKind::WORD, "cat", FilterAction::REVIEW)];
+        assert!(apply(&rules, "concatenate").action.is_none());
+
+        let verdict = apply(&rules, "Concatenate the CAT");
+        assert_eq!(verdict.action, Some(FilterAction::REVIEW));
+        assert_eq!(verdict.matches.len(), 1);
+        assert_eq!(verdict.matches[0].matched, "CAT");
+        // Only masking rules change the content
+        assert_eq!(verdict.content, "Concatenate the CAT");
+    }
+
+    #[test]
+    fn masks_keep_the_length() {
+        let rules = [rule(1, FilterKind::WORD, "über", FilterAction::MASK)];
+        let verdict = apply(&rules, "Über and über");
+        assert_eq!(verdict.action, Some(FilterAction::MASK));
+        assert_eq!(verdict.content, "████ and ████");
+    }
+
+    #[test]
+    fn the_most_severe_action_wins() {
+        let rules = [
+            rule(1, FilterKind::WORD, "cat", FilterAction::MASK),
+            rule(2, FilterKind::LINK, "", FilterAction::REJECT),
+            rule(3, FilterKind::INVITE, "", FilterAction::REVIEW),
+        ];
+        let verdict = apply(&rules, "cat at https://discord.gg/cats");
+        assert_eq!(verdict.action, Some(FilterAction::REJECT));
+        assert_eq!(
+            verdict
+                .matches
+                .iter()
+                .map(|m| m.rule.id)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(verdict.content, "███ at https://discord.gg/cats");
+    }
+
+    #[test]
+    fn broken_rules_are_skipped() {
+        let rules = [
+            rule(1, FilterKind::REGEX, "(", FilterAction::REJECT),
+            rule(2, FilterKind::REGEX, r"\d{3}", FilterAction::REVIEW),
+        ];
+        assert!(compile(FilterKind::REGEX, "(").is_err());
+        let verdict = apply(&rules, "call 555");
+        assert_eq!(verdict.action, Some(FilterAction::REVIEW));
+        assert_eq!(verdict.matches[0].matched, "555");
+    }
+}
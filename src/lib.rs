@@ -1,10 +1,160 @@
+pub mod crypto;
 pub mod models;
 pub mod schema;
 
-use diesel::{sqlite::SqliteConnection, Connection};
+use std::{collections::HashSet, error::Error, time::Duration};
 
-pub fn establish_connection(db_url: &String) -> SqliteConnection {
-    SqliteConnection::establish(db_url).unwrap_or_else(|_| panic!("Error connecting to {}", db_url))
+use diesel::{
+    backend::Backend,
+    connection::SimpleConnection,
+    migration::MigrationSource,
+    r2d2::{CustomizeConnection, ManageConnection, Pool, PoolError, R2D2Connection},
+    sqlite::{Sqlite, SqliteConnection},
+    Connection, QueryResult,
+};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+/// A connection to whichever database `DATABASE_URL` names. Queries are
+/// written once against it, and run on every backend.
+#[derive(diesel::MultiConnection)]
+pub enum DbConnection {
+    Sqlite(SqliteConnection),
+    #[cfg(feature = "postgres")]
+    Postgres(diesel::PgConnection),
+}
+
+pub type DbPool = Pool<DbConnectionManager>;
+pub type DbError = Box<dyn Error + Send + Sync>;
+
+/// How long a connection waits on a locked database before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens connections to the backend `DATABASE_URL` names. Diesel's own manager
+/// tries each backend in turn, so a Postgres server which cannot be reached
+/// would have its URL opened as a SQLite file instead.
+#[derive(Debug)]
+pub struct DbConnectionManager {
+    db_url: String,
+}
+
+impl ManageConnection for DbConnectionManager {
+    type Connection = DbConnection;
+    type Error = diesel::r2d2::Error;
+
+    fn connect(&self) -> Result<DbConnection, diesel::r2d2::Error> {
+        let conn = match is_postgres_url(&self.db_url) {
+            #[cfg(feature = "postgres")]
+            true => diesel::PgConnection::establish(&self.db_url).map(DbConnection::Postgres),
+            #[cfg(not(feature = "postgres"))]
+            true => Err(diesel::ConnectionError::BadConnection(
+                "This build lacks the postgres feature".to_string(),
+            )),
+            false => SqliteConnection::establish(&self.db_url).map(DbConnection::Sqlite),
+        };
+        conn.map_err(diesel::r2d2::Error::ConnectionError)
+    }
+
+    fn is_valid(&self, conn: &mut DbConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.ping().map_err(diesel::r2d2::Error::QueryError)
+    }
+
+    fn has_broken(&self, conn: &mut DbConnection) -> bool {
+        std::thread::panicking() || conn.is_broken()
+    }
+}
+
+#[derive(Debug)]
+struct ConnectionOptions;
+
+impl CustomizeConnection<DbConnection, diesel::r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, conn: &mut DbConnection) -> Result<(), diesel::r2d2::Error> {
+        match conn {
+            DbConnection::Sqlite(conn) => conn
+                .batch_execute(&format!(
+                    "PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL; PRAGMA busy_timeout = {};",
+                    BUSY_TIMEOUT.as_millis()
+                ))
+                .map_err(diesel::r2d2::Error::QueryError),
+            #[cfg(feature = "postgres")]
+            DbConnection::Postgres(_) => Ok(()),
+        }
+    }
+}
+
+pub fn establish_pool(db_url: &str) -> Result<DbPool, PoolError> {
+    Pool::builder()
+        .connection_customizer(Box::new(ConnectionOptions))
+        .build(DbConnectionManager {
+            db_url: db_url.to_string(),
+        })
+}
+
+/// Whether `db_url` points at a Postgres server rather than a SQLite file.
+pub fn is_postgres_url(db_url: &str) -> bool {
+    db_url.starts_with("postgres://") || db_url.starts_with("postgresql://")
+}
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+/// Postgres starts from the current schema, so it has its own history.
+#[cfg(feature = "postgres")]
+pub const PG_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_postgres");
+
+/// Apply any pending migrations. Refuses to touch a database which has had
+/// migrations applied that this binary does not know about, as the schema is
+/// then newer than the code.
+/// # Returns
+/// The versions of the migrations which were applied.
+pub fn run_migrations(conn: &mut DbConnection) -> Result<Vec<String>, DbError> {
+    match conn {
+        DbConnection::Sqlite(conn) => apply_migrations::<Sqlite>(conn, MIGRATIONS),
+        #[cfg(feature = "postgres")]
+        DbConnection::Postgres(conn) => apply_migrations::<diesel::pg::Pg>(conn, PG_MIGRATIONS),
+    }
+}
+
+fn apply_migrations<DB: Backend>(
+    conn: &mut impl MigrationHarness<DB>,
+    migrations: EmbeddedMigrations,
+) -> Result<Vec<String>, DbError>
+where
+    EmbeddedMigrations: MigrationSource<DB>,
+{
+    let known = MigrationSource::<DB>::migrations(&migrations)?
+        .iter()
+        .map(|m| m.name().version().as_owned())
+        .collect::<HashSet<_>>();
+    let unknown = conn
+        .applied_migrations()?
+        .into_iter()
+        .filter(|version| !known.contains(version))
+        .map(|version| version.to_string())
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        return Err(Box::from(format!(
+            "Database schema is newer than this binary. Unknown migrations: {}",
+            unknown.join(", ")
+        )));
+    }
+
+    Ok(conn
+        .run_pending_migrations(migrations)?
+        .into_iter()
+        .map(|version| version.to_string())
+        .collect())
+}
+
+/// Run blocking Diesel work on a pooled connection, off the async executor.
+pub async fn run<T, F>(pool: &DbPool, f: F) -> Result<T, DbError>
+where
+    F: FnOnce(&mut DbConnection) -> Result<T, DbError> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        f(&mut conn)
+    })
+    .await?
 }
 
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -15,6 +165,22 @@ pub enum VoteType {
 
 pub const DELETE_VOTE_STR: &str = "delete_vote";
 pub const EXPOSE_VOTE_STR: &str = "expose_vote";
+pub const REVEAL_APPROVE_STR: &str = "reveal_approve";
+/// Share modals carry the approving interaction ID after a `:`.
+pub const REVEAL_SHARE_MODAL_STR: &str = "reveal_share_modal";
+/// Review buttons carry the confession ID after a `:`, e.g. `review_approve:12`.
+pub const REVIEW_APPROVE_STR: &str = "review_approve";
+pub const REVIEW_REJECT_STR: &str = "review_reject";
+pub const REVIEW_REASON_STR: &str = "review_reason";
+/// Where content caught by the filter came from.
+pub const CONFESSION_SOURCE_STR: &str = "confession";
+pub const REPLY_SOURCE_STR: &str = "reply";
+/// Composer modals and buttons carry the command invocation ID after a `:`,
+/// e.g. `compose_post:1234`.
+pub const COMPOSE_MODAL_STR: &str = "compose_modal";
+pub const COMPOSE_POST_STR: &str = "compose_post";
+pub const COMPOSE_EDIT_STR: &str = "compose_edit";
+pub const COMPOSE_CANCEL_STR: &str = "compose_cancel";
 impl Into<String> for VoteType {
     fn into(self) -> String {
         match self {
@@ -33,3 +199,89 @@ impl Into<VoteType> for String {
         };
     }
 }
+
+/// Whether a scheduled job will still be run.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ScheduleStatus {
+    /// Waiting for its `run_at`, possibly after failed attempts.
+    PENDING,
+    /// Run successfully. Kept so the timeout can be lifted early.
+    APPLIED,
+    /// Out of attempts. Kept so the failure can be looked into.
+    DEAD,
+}
+
+pub const APPLIED_STATUS_STR: &str = "applied";
+pub const DEAD_STATUS_STR: &str = "dead";
+impl Into<String> for ScheduleStatus {
+    fn into(self) -> String {
+        match self {
+            ScheduleStatus::PENDING => PENDING_STATUS_STR.to_string(),
+            ScheduleStatus::APPLIED => APPLIED_STATUS_STR.to_string(),
+            ScheduleStatus::DEAD => DEAD_STATUS_STR.to_string(),
+        }
+    }
+}
+
+impl Into<ScheduleStatus> for String {
+    fn into(self) -> ScheduleStatus {
+        match self.to_lowercase().as_str() {
+            PENDING_STATUS_STR => ScheduleStatus::PENDING,
+            APPLIED_STATUS_STR => ScheduleStatus::APPLIED,
+            DEAD_STATUS_STR => ScheduleStatus::DEAD,
+            _ => panic!("Could not convert {} into a ScheduleStatus.", self),
+        }
+    }
+}
+
+/// Where a confession is in its lifecycle. Only active confessions take votes.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ConfessionStatus {
+    /// Waiting in the guild's review channel. Not yet numbered or posted.
+    PENDING,
+    /// Waiting out the guild's post delay in the outbox. Not yet numbered or
+    /// posted.
+    QUEUED,
+    ACTIVE,
+    /// Turned down during review. Never posted.
+    REJECTED,
+    DELETED,
+    EXPOSED,
+    REMOVED,
+}
+
+pub const PENDING_STATUS_STR: &str = "pending";
+pub const QUEUED_STATUS_STR: &str = "queued";
+pub const ACTIVE_STATUS_STR: &str = "active";
+pub const REJECTED_STATUS_STR: &str = "rejected";
+pub const DELETED_STATUS_STR: &str = "deleted";
+pub const EXPOSED_STATUS_STR: &str = "exposed";
+pub const REMOVED_STATUS_STR: &str = "removed";
+impl Into<String> for ConfessionStatus {
+    fn into(self) -> String {
+        match self {
+            ConfessionStatus::PENDING => PENDING_STATUS_STR.to_string(),
+            ConfessionStatus::QUEUED => QUEUED_STATUS_STR.to_string(),
+            ConfessionStatus::ACTIVE => ACTIVE_STATUS_STR.to_string(),
+            ConfessionStatus::REJECTED => REJECTED_STATUS_STR.to_string(),
+            ConfessionStatus::DELETED => DELETED_STATUS_STR.to_string(),
+            ConfessionStatus::EXPOSED => EXPOSED_STATUS_STR.to_string(),
+            ConfessionStatus::REMOVED => REMOVED_STATUS_STR.to_string(),
+        }
+    }
+}
+
+impl Into<ConfessionStatus> for String {
+    fn into(self) -> ConfessionStatus {
+        return match self.to_lowercase().as_str() {
+            PENDING_STATUS_STR => ConfessionStatus::PENDING,
+            QUEUED_STATUS_STR => ConfessionStatus::QUEUED,
+            ACTIVE_STATUS_STR => ConfessionStatus::ACTIVE,
+            REJECTED_STATUS_STR => ConfessionStatus::REJECTED,
+            DELETED_STATUS_STR => ConfessionStatus::DELETED,
+            EXPOSED_STATUS_STR => ConfessionStatus::EXPOSED,
+            REMOVED_STATUS_STR => ConfessionStatus::REMOVED,
+            _ => panic!("Could not convert {} into a ConfessionStatus.", self),
+        };
+    }
+}
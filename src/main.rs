@@ -1,19 +1,30 @@
 use anyhow::Context;
-use std::env;
+use confession_bot_rs::{
+    crypto::{from_hex, AuthorKeys, EscrowKey},
+    establish_pool, is_postgres_url, run, run_migrations,
+};
+use db_impl::{memory::MemoryStore, sql::SqlStore, AuthorStore, Db, MEMORY_URL};
+use std::{env, sync::Arc};
 use tokio::fs;
-use tracing::{error, subscriber};
+use tracing::{info, subscriber};
 use tracing_subscriber::FmtSubscriber;
 
+mod attachments;
 mod client;
 mod commands;
 mod db_impl;
+mod filter;
 mod models;
+mod sanitise;
 mod schema;
+mod time_expr;
 
 #[derive(Clone)]
 struct Config {
     db_url: String,
     bot_token: String,
+    author_keys: AuthorKeys,
+    escrow_key: EscrowKey,
 }
 
 #[tokio::main]
@@ -21,21 +32,68 @@ async fn main() -> anyhow::Result<()> {
     let subscriber = FmtSubscriber::new();
     subscriber::set_global_default(subscriber)?;
     let _ = dotenvy::dotenv();
+    // Only apply migrations, e.g. from a release step, then exit
+    let migrate_only = env::args().any(|arg| arg == "--migrate-only");
+
+    let db_url = env::var("DATABASE_URL").context("DATABASE_URL not set")?;
+    let db = open_database(&db_url).await?;
+    if migrate_only {
+        return Ok(());
+    }
+
+    let author_key = from_hex(&env::var("AUTHOR_KEY").context("AUTHOR_KEY not set")?)
+        .context("AUTHOR_KEY must be hex encoded")?;
+    let previous_author_key = match env::var("AUTHOR_KEY_PREVIOUS") {
+        Ok(key) => Some(from_hex(&key).context("AUTHOR_KEY_PREVIOUS must be hex encoded")?),
+        Err(_) => None,
+    };
+    let escrow_key = from_hex(&env::var("ESCROW_KEY").context("ESCROW_KEY not set")?)
+        .context("ESCROW_KEY must be hex encoded")?;
     let config = Config {
         bot_token: env::var("BOT_TOKEN").context("BOT_TOKEN not set")?,
-        db_url: env::var("DATABASE_URL").context("DATABASE_URL not set")?,
+        db_url,
+        author_keys: AuthorKeys::new(&author_key, previous_author_key.as_deref()),
+        escrow_key: EscrowKey::new(&escrow_key)
+            .map_err(|_| anyhow::anyhow!("ESCROW_KEY must be 32 bytes"))?,
     };
-    if let Ok(meta) = fs::metadata(&config.db_url).await {
+
+    // Authors are only re-keyed as they are seen, so say how far a rotation has got
+    let stale = db
+        .count_stale_authors(&config.author_keys.key_id().to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Could not count stale authors: {}", e))?;
+    if stale > 0 {
+        info!("{} authors are not yet keyed with AUTHOR_KEY", stale);
+    } else if previous_author_key.is_some() {
+        info!("Every author is keyed with AUTHOR_KEY, so AUTHOR_KEY_PREVIOUS can be removed");
+    }
+    client::start(config, db).await?;
+    Ok(())
+}
+
+/// Open the backend named by `DATABASE_URL` and bring its schema up to date.
+async fn open_database(db_url: &String) -> anyhow::Result<Db> {
+    if db_url == MEMORY_URL {
+        info!("Using the in-memory database. Nothing will be kept after shutdown");
+        return Ok(Arc::new(MemoryStore::default()));
+    }
+
+    if is_postgres_url(db_url) {
+        #[cfg(not(feature = "postgres"))]
+        anyhow::bail!("DATABASE_URL is a Postgres URL, but this build lacks the postgres feature");
+    } else if let Ok(meta) = fs::metadata(db_url).await {
         if !meta.is_file() && !meta.is_symlink() {
-            error!(
-                "Expected file at {}. Found {:?}",
-                &config.db_url,
-                meta.file_type()
-            )
+            anyhow::bail!("Expected file at {}. Found {:?}", db_url, meta.file_type());
         }
     } else {
-        error!("Cannot find file at {}", &config.db_url)
+        info!("No database found at {}. Creating a new one", db_url)
     }
-    client::start(config).await?;
-    Ok(())
+    let pool = establish_pool(db_url).context("Could not connect to the database")?;
+    let applied = run(&pool, run_migrations)
+        .await
+        .map_err(|e| anyhow::anyhow!("Could not migrate the database: {}", e))?;
+    for version in applied {
+        info!("Applied migration {}", version);
+    }
+    Ok(Arc::new(SqlStore::new(pool)))
 }
@@ -1,18 +1,24 @@
+use chrono_tz::Tz;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(Queryable, Identifiable, Selectable, PartialEq)]
 #[diesel(table_name = crate::schema::authors)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
 pub struct Author {
     pub id: i32,
     pub hash: String,
+    pub guild_id: Option<String>,
+    pub key_id: Option<String>,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = crate::schema::authors)]
 pub struct NewAuthor {
     pub hash: String,
+    pub guild_id: Option<String>,
+    pub key_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq)]
@@ -21,16 +27,204 @@ pub struct GuildConfig {
     pub expose_vote_min: i32,
     pub expose_vote_role: Option<String>,
     pub role_ping: Option<String>,
+    /// Seconds which must pass between pings of `role_ping`. Confessions posted
+    /// sooner are posted without a ping.
+    #[serde(default)]
+    pub role_ping_interval: i64,
+    /// Moderators who each hold a share of new confessions' author key.
+    #[serde(default)]
+    pub reveal_moderators: Vec<String>,
+    /// How many of `reveal_moderators` must approve a reveal. Unset means an
+    /// expose reveals the author directly.
+    #[serde(default)]
+    pub reveal_threshold: Option<u8>,
+    /// Private channel where moderators approve confessions before they are
+    /// posted. Unset means confessions are posted straight away.
+    #[serde(default)]
+    pub review_channel: Option<String>,
+    /// Content filter rules, checked against every confession and reply.
+    #[serde(default)]
+    pub filters: Vec<FilterRule>,
+    /// The last ID given to a filter rule.
+    #[serde(default)]
+    pub last_filter_id: i32,
+    /// How much markdown is stripped from anonymous content. Mentions are
+    /// always neutralised.
+    #[serde(default)]
+    pub sanitise_level: SanitiseLevel,
+    #[serde(default)]
+    pub post_limits: PostLimits,
+    #[serde(default)]
+    pub post_delay: PostDelay,
+    #[serde(default)]
+    pub attachments: AttachmentRules,
+    /// Seconds after posting in which authors may delete or edit their own
+    /// confession. Zero means they cannot.
+    #[serde(default)]
+    pub author_edit_window: i64,
+    /// IANA name of the timezone times are read in, such as `Europe/London`.
+    /// Unset means UTC.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// New confessions are refused while set.
+    #[serde(default)]
+    pub blackout: bool,
+}
+
+/// How often one author may post confessions, and separately replies. A zero
+/// turns that limit off.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct PostLimits {
+    /// Seconds an author must wait between posts.
+    pub cooldown: i64,
+    /// Posts allowed within `window`.
+    pub max_posts: i64,
+    /// Seconds over which `max_posts` is counted.
+    pub window: i64,
+}
+
+/// When confessions are posted after `/confess`, so the time a confession
+/// appears does not give away who was typing.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct PostDelay {
+    pub mode: DelayMode,
+    /// Seconds. The longest random delay, or the time between batches.
+    pub window: i64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DelayMode {
+    /// Post straight away.
+    #[default]
+    OFF,
+    /// Post after a random delay of up to the window.
+    RANDOM,
+    /// Post together at the end of each window.
+    BATCH,
+}
+
+/// Which attachments confessions and replies may carry. No kinds means
+/// attachments are turned off.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct AttachmentRules {
+    pub kinds: Vec<AttachmentKind>,
+    /// Bytes, checked both before and after metadata is stripped.
+    pub max_size: u32,
+}
+
+impl Default for AttachmentRules {
+    fn default() -> Self {
+        Self {
+            kinds: vec![],
+            max_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Only kinds the bot can strip of metadata are accepted.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentKind {
+    /// PNG, JPEG, GIF and WebP. Re-encoded, which drops EXIF and any other
+    /// metadata.
+    IMAGE,
+    /// UTF-8 plain text.
+    TEXT,
+}
+
+impl GuildConfig {
+    /// The number of approvals a reveal needs, if threshold reveals are on and
+    /// enough moderators are designated to meet it.
+    pub fn reveal_quorum(&self) -> Option<u8> {
+        match self.reveal_threshold {
+            Some(threshold)
+                if threshold > 0 && self.reveal_moderators.len() >= threshold as usize =>
+            {
+                Some(threshold)
+            }
+            _ => None,
+        }
+    }
+
+    /// The timezone times are read in. A name which is no longer known falls
+    /// back to UTC.
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+            .as_deref()
+            .and_then(|timezone| timezone.parse().ok())
+            .unwrap_or(Tz::UTC)
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SanitiseLevel {
+    /// Only neutralise mentions.
+    #[default]
+    BASIC,
+    /// Also unwrap masked links, so the real URL is shown.
+    STANDARD,
+    /// Also strip headings and subtext.
+    STRICT,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterKind {
+    /// A literal word or phrase, matched case-insensitively on word
+    /// boundaries.
+    WORD,
+    REGEX,
+    /// Discord server invites. The rule has no pattern.
+    INVITE,
+    /// Any http(s) link. The rule has no pattern.
+    LINK,
+}
+
+/// What happens to content which matches a rule. Ordered from least to most
+/// severe, so the most severe action of all matching rules wins.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// Replace the matched text before posting.
+    MASK,
+    /// Hold the confession in the review channel.
+    REVIEW,
+    REJECT,
+}
+
+impl Into<String> for FilterAction {
+    fn into(self) -> String {
+        match self {
+            FilterAction::MASK => "mask",
+            FilterAction::REVIEW => "review",
+            FilterAction::REJECT => "reject",
+        }
+        .to_string()
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct FilterRule {
+    /// Unique within the guild, and never reused after a rule is removed.
+    pub id: i32,
+    pub kind: FilterKind,
+    #[serde(default)]
+    pub pattern: String,
+    pub action: FilterAction,
 }
 
 #[derive(Queryable, Selectable, PartialEq, Clone)]
 #[diesel(table_name = crate::schema::guild)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
 pub struct Guild {
     pub guild_id: String,
     pub confession_channel_id: Option<String>,
     pub config: String,
     pub timestamp: chrono::NaiveDateTime,
+    pub last_role_ping: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Insertable)]
@@ -44,6 +238,7 @@ pub struct NewGuild {
 #[diesel(belongs_to(Author, foreign_key = author))]
 #[diesel(table_name = crate::schema::confession)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
 pub struct Confession {
     pub id: i32,
     pub guild_id: String,
@@ -51,7 +246,44 @@ pub struct Confession {
     pub content: String,
     pub author: i32,
     pub timestamp: chrono::NaiveDateTime,
-    pub deleted: i32,
+    pub author_escrow: Option<String>,
+    pub reveal_threshold: Option<i32>,
+    pub number: Option<i32>,
+    pub status: String,
+    pub status_changed_at: Option<chrono::NaiveDateTime>,
+    pub review_notice: Option<String>,
+    pub title: Option<String>,
+    /// Shown above the content, which is then hidden behind a spoiler.
+    pub content_warning: Option<String>,
+    /// The author asked for the guild's confession role not to be pinged.
+    pub skip_role_ping: bool,
+    /// The channel it was posted in. Unset until it is posted, and for
+    /// confessions posted before this was recorded.
+    pub channel_id: Option<String>,
+}
+
+/// A confession as written by its author, before it is stored.
+#[derive(Default, Clone)]
+pub struct ConfessionDraft {
+    pub title: Option<String>,
+    pub content_warning: Option<String>,
+    pub content: String,
+    pub attachment: Option<NewAttachment>,
+    pub skip_role_ping: bool,
+}
+
+/// An attachment once its original name and metadata have been stripped.
+#[derive(Clone)]
+pub struct NewAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl NewAttachment {
+    pub fn is_image(&self) -> bool {
+        self.content_type.starts_with("image/")
+    }
 }
 
 #[derive(Insertable)]
@@ -61,6 +293,7 @@ pub struct NewConfession<'a> {
     pub message_id: &'a String,
     pub content: &'a String,
     pub author: i32,
+    pub author_escrow: Option<&'a String>,
 }
 
 #[derive(Queryable, Selectable, Associations, PartialEq)]
@@ -68,6 +301,7 @@ pub struct NewConfession<'a> {
 #[diesel(belongs_to(Confession, foreign_key=confession_id))]
 #[diesel(table_name = crate::schema::delete_votes)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
 pub struct Vote {
     pub id: i32,
     pub confession_id: i32,
@@ -76,12 +310,13 @@ pub struct Vote {
     pub timestamp: chrono::NaiveDateTime,
 }
 
-#[derive(Queryable, Selectable, Associations, PartialEq)]
+#[derive(Queryable, Selectable, Associations, PartialEq, Clone)]
 #[diesel(belongs_to(Author, foreign_key=author))]
 #[diesel(belongs_to(Guild, foreign_key=guild_id))]
 #[diesel(belongs_to(Confession, foreign_key=original_confession_id))]
 #[diesel(table_name = crate::schema::replies)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
 pub struct Reply {
     pub id: i32,
     pub guild_id: String,
@@ -95,20 +330,156 @@ pub struct Reply {
 #[derive(Queryable, Selectable, Identifiable, PartialEq, Clone)]
 #[diesel(table_name = crate::schema::schedule)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
 pub struct Schedule {
     pub id: i32,
     pub guild_id: String,
-    pub victim_id: String,
-    pub ends_at: i32,
-    pub start_at: i32,
+    /// The member timed out. Only set for timeouts.
+    pub victim_id: Option<String>,
+    /// When the timeout ends. Only set for timeouts.
+    pub ends_at: Option<i64>,
+    pub start_at: i64,
+    /// When the job is next due. Later than `start_at` once it has failed.
+    pub run_at: i64,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    /// Reapply the timeout until `ends_at` if it is lifted early.
+    pub enforce: bool,
+    /// How often an enforced timeout has been put back after being lifted.
+    pub reapplied: i32,
+    /// When it was last put back.
+    pub reapplied_at: Option<i64>,
+    /// A [`ScheduleAction`] as JSON.
+    pub action: String,
+}
+
+impl Schedule {
+    pub fn action(&self) -> Result<ScheduleAction, serde_json::Error> {
+        serde_json::from_str(&self.action)
+    }
 }
 
 #[derive(Insertable, PartialEq)]
 #[diesel(table_name = crate::schema::schedule)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
 pub struct InsertSchedule {
     pub guild_id: String,
-    pub victim_id: String,
-    pub ends_at: i32,
-    pub start_at: i32,
+    pub victim_id: Option<String>,
+    pub ends_at: Option<i64>,
+    pub start_at: i64,
+    pub enforce: bool,
+    pub action: String,
+}
+
+/// What a scheduled job does when it runs.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ScheduleAction {
+    /// Time out the job's `victim_id` until its `ends_at`.
+    TIMEOUT,
+    /// Give a member a role.
+    GRANT { user_id: String, role_id: String },
+    /// Take a role from a member.
+    REVOKE { user_id: String, role_id: String },
+    /// Stop everyone sending messages in a channel.
+    LOCK { channel_id: String },
+    /// Undo a [`ScheduleAction::LOCK`].
+    UNLOCK { channel_id: String },
+    /// Post a message in a channel, without pinging anyone.
+    POST { channel_id: String, content: String },
+    /// Lock and archive the reply thread of a confession.
+    CLOSE {
+        confession_number: i32,
+        thread_id: String,
+    },
+    /// Turn the guild's confession blackout on or off.
+    BLACKOUT { enabled: bool },
+}
+
+#[derive(Queryable, Selectable, Associations, PartialEq, Clone)]
+#[diesel(belongs_to(Confession, foreign_key = confession_id))]
+#[diesel(table_name = crate::schema::reveal_shares)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct RevealShare {
+    pub id: i32,
+    pub confession_id: i32,
+    pub moderator_id: String,
+    pub share_index: i32,
+    /// The moderator holds the share itself. This only checks it when it is
+    /// given back.
+    pub share_hash: String,
+}
+
+#[derive(Queryable, Selectable, PartialEq, Clone)]
+#[diesel(table_name = crate::schema::filter_hits)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct FilterHit {
+    pub id: i32,
+    pub guild_id: String,
+    pub rule_id: i32,
+    /// The pseudonymous author row, so repeat hits can be told apart without
+    /// naming anyone.
+    pub author_id: i32,
+    pub source: String,
+    pub action: String,
+    pub matched: String,
+    pub timestamp: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::filter_hits)]
+pub struct NewFilterHit {
+    pub guild_id: String,
+    pub rule_id: i32,
+    pub author_id: i32,
+    pub source: String,
+    pub action: String,
+    pub matched: String,
+}
+
+#[derive(Queryable, Selectable, PartialEq, Clone, Debug)]
+#[diesel(table_name = crate::schema::outbox)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct OutboxEntry {
+    pub id: i32,
+    pub confession_id: i32,
+    pub guild_id: String,
+    /// When it is due, pushed back after each failed attempt.
+    pub release_at: chrono::NaiveDateTime,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    /// Out of attempts. Kept so the failure can be looked into.
+    pub dead: bool,
+}
+
+/// An attachment stored with the confession or reply it was posted with, so
+/// it can be posted again later and is removed along with it.
+#[derive(Queryable, Selectable, PartialEq, Clone)]
+#[diesel(table_name = crate::schema::attachments)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(feature = "postgres", diesel(check_for_backend(diesel::pg::Pg)))]
+pub struct StoredAttachment {
+    pub id: i32,
+    pub guild_id: String,
+    pub confession_id: Option<i32>,
+    pub reply_id: Option<i32>,
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+    pub timestamp: chrono::NaiveDateTime,
+}
+
+impl From<StoredAttachment> for NewAttachment {
+    fn from(value: StoredAttachment) -> Self {
+        Self {
+            filename: value.filename,
+            content_type: value.content_type,
+            data: value.data,
+        }
+    }
 }
@@ -0,0 +1,86 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::models::SanitiseLevel;
+
+/// Breaks mention syntax without changing how the text reads.
+const ZERO_WIDTH_SPACE: &str = "\u{200B}";
+
+static EVERYONE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"@(everyone|here)").unwrap());
+/// User and role mentions, e.g. `<@123>`, `<@!123>` and `<@&123>`.
+static MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<@([!&]?\d+)>").unwrap());
+static MASKED_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[([^\]\n]*)\]\(\s*<?(https?://[^)>\s]+)>?(?:\s+[^)]*)?\)").unwrap()
+});
+/// Headings (`#`, `##`, `###`) and subtext (`-#`) at the start of a line.
+static HEADING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^(\s*)(#{1,3}|-#)\s+").unwrap());
+
+/// Make anonymous content safe to post. Mentions are always neutralised, so
+/// nobody can be pinged through the bot, and `level` decides what else goes.
+///
+/// Messages with anonymous content should still be sent with no allowed
+/// mentions, as this only covers the syntax Discord currently knows.
+pub fn sanitise(content: &str, level: SanitiseLevel) -> String {
+    let mut content = EVERYONE
+        .replace_all(content, format!("@{}$1", ZERO_WIDTH_SPACE))
+        .into_owned();
+    content = MENTION
+        .replace_all(&content, format!("<@{}$1>", ZERO_WIDTH_SPACE))
+        .into_owned();
+    if level >= SanitiseLevel::STANDARD {
+        content = MASKED_LINK.replace_all(&content, "$1 (<$2>)").into_owned();
+    }
+    if level >= SanitiseLevel::STRICT {
+        content = HEADING.replace_all(&content, "$1").into_owned();
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_are_always_broken() {
+        for level in [
+            SanitiseLevel::BASIC,
+            SanitiseLevel::STANDARD,
+            SanitiseLevel::STRICT,
+        ] {
+            assert_eq!(
+                sanitise("@everyone and @here", level),
+                "@\u{200B}everyone and @\u{200B}here"
+            );
+            assert_eq!(
+                sanitise("<@123> <@!456> <@&789>", level),
+                "<@\u{200B}123> <@\u{200B}!456> <@\u{200B}&789>"
+            );
+        }
+    }
+
+    #[test]
+    fn masked_links_are_unwrapped_from_standard() {
+        let content = "[free nitro](https://example.com/a \"title\")";
+        assert_eq!(sanitise(content, SanitiseLevel::BASIC), content);
+        assert_eq!(
+            sanitise(content, SanitiseLevel::STANDARD),
+            "free nitro (<https://example.com/a>)"
+        );
+        assert_eq!(
+            sanitise("[x](<https://example.com>)", SanitiseLevel::STRICT),
+            "x (<https://example.com>)"
+        );
+    }
+
+    #[test]
+    fn headings_are_stripped_when_strict() {
+        let content = "# Big\n## Bigger\n-# small\nnot a #heading";
+        assert_eq!(sanitise(content, SanitiseLevel::STANDARD), content);
+        assert_eq!(
+            sanitise(content, SanitiseLevel::STRICT),
+            "Big\nBigger\nsmall\nnot a #heading"
+        );
+    }
+}
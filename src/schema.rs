@@ -1,9 +1,45 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    attachments (id) {
+        id -> Integer,
+        guild_id -> Text,
+        confession_id -> Nullable<Integer>,
+        reply_id -> Nullable<Integer>,
+        filename -> Text,
+        content_type -> Text,
+        data -> Binary,
+        timestamp -> Timestamp,
+    }
+}
+
+diesel::table! {
+    author_bans (id) {
+        id -> Integer,
+        guild_id -> Text,
+        author_id -> Integer,
+        moderator_id -> Text,
+        expires_at -> Nullable<Timestamp>,
+        timestamp -> Timestamp,
+    }
+}
+
+diesel::table! {
+    author_posts (id) {
+        id -> Integer,
+        guild_id -> Text,
+        author_id -> Integer,
+        source -> Text,
+        timestamp -> Timestamp,
+    }
+}
+
 diesel::table! {
     authors (id) {
         id -> Integer,
         hash -> Text,
+        guild_id -> Nullable<Text>,
+        key_id -> Nullable<Text>,
     }
 }
 
@@ -15,7 +51,27 @@ diesel::table! {
         content -> Text,
         author -> Integer,
         timestamp -> Timestamp,
-        deleted -> Integer,
+        author_escrow -> Nullable<Text>,
+        reveal_threshold -> Nullable<Integer>,
+        number -> Nullable<Integer>,
+        status -> Text,
+        status_changed_at -> Nullable<Timestamp>,
+        review_notice -> Nullable<Text>,
+        title -> Nullable<Text>,
+        content_warning -> Nullable<Text>,
+        skip_role_ping -> Bool,
+        channel_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    confession_revisions (id) {
+        id -> Integer,
+        confession_id -> Integer,
+        title -> Nullable<Text>,
+        content_warning -> Nullable<Text>,
+        content -> Text,
+        timestamp -> Timestamp,
     }
 }
 
@@ -29,12 +85,38 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    filter_hits (id) {
+        id -> Integer,
+        guild_id -> Text,
+        rule_id -> Integer,
+        author_id -> Integer,
+        source -> Text,
+        action -> Text,
+        matched -> Text,
+        timestamp -> Timestamp,
+    }
+}
+
 diesel::table! {
     guild (guild_id) {
         guild_id -> Text,
         confession_channel_id -> Nullable<Text>,
         config -> Text,
         timestamp -> Timestamp,
+        last_role_ping -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    outbox (id) {
+        id -> Integer,
+        confession_id -> Integer,
+        guild_id -> Text,
+        release_at -> Timestamp,
+        attempts -> Integer,
+        last_error -> Nullable<Text>,
+        dead -> Bool,
     }
 }
 
@@ -50,29 +132,77 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    reveal_approvals (id) {
+        id -> Integer,
+        confession_id -> Integer,
+        moderator_id -> Text,
+        share -> Nullable<Text>,
+        timestamp -> Timestamp,
+    }
+}
+
+diesel::table! {
+    reveal_shares (id) {
+        id -> Integer,
+        confession_id -> Integer,
+        moderator_id -> Text,
+        share_index -> Integer,
+        share_hash -> Text,
+    }
+}
+
 diesel::table! {
     schedule (id) {
         id -> Integer,
         guild_id -> Text,
-        victim_id -> Text,
-        ends_at -> Integer,
-        start_at -> Integer,
+        victim_id -> Nullable<Text>,
+        ends_at -> Nullable<BigInt>,
+        start_at -> BigInt,
+        run_at -> BigInt,
+        status -> Text,
+        attempts -> Integer,
+        last_error -> Nullable<Text>,
+        enforce -> Bool,
+        reapplied -> Integer,
+        reapplied_at -> Nullable<BigInt>,
+        action -> Text,
     }
 }
 
+diesel::joinable!(attachments -> confession (confession_id));
+diesel::joinable!(attachments -> guild (guild_id));
+diesel::joinable!(attachments -> replies (reply_id));
+diesel::joinable!(author_bans -> authors (author_id));
+diesel::joinable!(author_posts -> authors (author_id));
 diesel::joinable!(confession -> authors (author));
 diesel::joinable!(confession -> guild (guild_id));
+diesel::joinable!(confession_revisions -> confession (confession_id));
 diesel::joinable!(delete_votes -> authors (author_id));
 diesel::joinable!(delete_votes -> confession (confession_id));
+diesel::joinable!(filter_hits -> authors (author_id));
+diesel::joinable!(filter_hits -> guild (guild_id));
+diesel::joinable!(outbox -> confession (confession_id));
+diesel::joinable!(outbox -> guild (guild_id));
 diesel::joinable!(replies -> authors (author));
 diesel::joinable!(replies -> confession (original_confession_id));
 diesel::joinable!(replies -> guild (guild_id));
+diesel::joinable!(reveal_approvals -> confession (confession_id));
+diesel::joinable!(reveal_shares -> confession (confession_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    attachments,
+    author_bans,
+    author_posts,
     authors,
     confession,
+    confession_revisions,
     delete_votes,
+    filter_hits,
     guild,
+    outbox,
     replies,
+    reveal_approvals,
+    reveal_shares,
     schedule,
 );
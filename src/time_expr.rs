@@ -0,0 +1,330 @@
+use std::sync::LazyLock;
+
+use chrono::{
+    DateTime, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use regex::Regex;
+
+/// The longest Discord lets a member be timed out for.
+pub const MAX_TIMEOUT: TimeDelta = TimeDelta::days(28);
+
+/// A Discord timestamp token, such as `<t:1700000000:R>`.
+static DISCORD_TIMESTAMP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^<t:(-?\d+)(?::[tTdDfFR])?>$").unwrap());
+/// One part of a compound duration, such as the `30m` of `1h30m`.
+static DURATION_PART: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(\d+)\s*([a-zA-Z]+)").unwrap());
+/// An ISO-8601 duration. Years and months are matched only to be refused.
+static ISO_DURATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^P(?:(\d+)Y)?(?:(\d+)M)?(?:(\d+)W)?(?:(\d+)D)?(?:T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+)S)?)?$",
+    )
+    .unwrap()
+});
+
+const DATE_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// Parse a length of time: a compound duration such as `1h30m` or `2d 12h`, or
+/// an ISO-8601 duration such as `PT90M`.
+pub fn parse_duration(input: &str) -> Result<TimeDelta, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("No duration was given.".to_string());
+    }
+    let seconds = match input.strip_prefix(['P', 'p']) {
+        Some(_) => iso_duration_seconds(&input.to_uppercase())?,
+        None => compound_duration_seconds(input)?,
+    };
+    TimeDelta::try_seconds(seconds).ok_or_else(too_long)
+}
+
+/// Parse a point in time, read in `timezone` unless it says otherwise.
+///
+/// Accepts Discord `<t:...>` tokens, RFC 3339 timestamps, a local date and
+/// time such as `2024-12-25 18:00`, a local date, a local time (its next
+/// occurrence), or a duration from `now` (optionally after `in`).
+pub fn parse_time(input: &str, now: DateTime<Utc>, timezone: Tz) -> Result<DateTime<Utc>, String> {
+    let input = input.trim();
+
+    if let Some(captures) = DISCORD_TIMESTAMP.captures(input) {
+        let seconds: i64 = captures[1].parse().map_err(|_| too_long())?;
+        return DateTime::from_timestamp(seconds, 0).ok_or_else(too_long);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    for format in DATE_TIME_FORMATS {
+        if let Ok(local) = NaiveDateTime::parse_from_str(input, format) {
+            return from_local(local, timezone);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return from_local(date.and_time(NaiveTime::MIN), timezone);
+    }
+    if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M") {
+        let today = now.with_timezone(&timezone).date_naive();
+        let next = from_local(today.and_time(time), timezone)?;
+        if next > now {
+            return Ok(next);
+        }
+        let tomorrow = today.succ_opt().ok_or_else(too_long)?;
+        return from_local(tomorrow.and_time(time), timezone);
+    }
+
+    let duration = parse_duration(input.strip_prefix("in ").unwrap_or(input)).map_err(|e| {
+        format!(
+            "{} Times can be a duration like `1h30m` or `PT90M`, a date and time like `2024-12-25 18:00`, or a Discord timestamp.",
+            e
+        )
+    })?;
+    now.checked_add_signed(duration).ok_or_else(too_long)
+}
+
+/// Parse how long something lasts from `start`: a duration, or any time
+/// [`parse_time`] accepts as its end.
+pub fn parse_length(input: &str, start: DateTime<Utc>, timezone: Tz) -> Result<TimeDelta, String> {
+    match parse_duration(input) {
+        Ok(duration) => Ok(duration),
+        Err(_) => Ok(parse_time(input, start, timezone)? - start),
+    }
+}
+
+/// Check a timeout can be given by Discord: not starting in the past, and
+/// lasting no longer than [`MAX_TIMEOUT`].
+/// # Returns
+/// When the timeout starts and ends, as Unix timestamps.
+pub fn timeout_window(
+    start: DateTime<Utc>,
+    length: TimeDelta,
+    now: DateTime<Utc>,
+) -> Result<(i64, i64), String> {
+    check_start(start, now)?;
+    if length <= TimeDelta::zero() {
+        return Err("The timeout must end after it starts.".to_string());
+    }
+    if length > MAX_TIMEOUT {
+        return Err(format!(
+            "Discord timeouts can last at most {} days.",
+            MAX_TIMEOUT.num_days()
+        ));
+    }
+    let ends_at = start.checked_add_signed(length).ok_or_else(too_long)?;
+    Ok((start.timestamp(), ends_at.timestamp()))
+}
+
+/// Check something is not being scheduled in the past.
+/// # Returns
+/// `start` as a Unix timestamp.
+pub fn check_start(start: DateTime<Utc>, now: DateTime<Utc>) -> Result<i64, String> {
+    // A little leeway, so `now` or `0s` is not already late
+    if start < now - TimeDelta::minutes(1) {
+        return Err(format!(
+            "That would start <t:{}:R>, which has already passed.",
+            start.timestamp()
+        ));
+    }
+    Ok(start.timestamp())
+}
+
+fn compound_duration_seconds(input: &str) -> Result<i64, String> {
+    let mut rest = input;
+    let mut seconds: i64 = 0;
+    while !rest.trim().is_empty() {
+        let captures = DURATION_PART
+            .captures(rest)
+            .ok_or_else(|| format!("Could not read `{}` as a duration.", input))?;
+        let value: i64 = captures[1].parse().map_err(|_| too_long())?;
+        let unit = match captures[2].to_lowercase().as_str() {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+            "d" | "day" | "days" => 60 * 60 * 24,
+            "w" | "week" | "weeks" => 60 * 60 * 24 * 7,
+            unit => return Err(format!("`{}` is not a unit of time.", unit)),
+        };
+        seconds = value
+            .checked_mul(unit)
+            .and_then(|part| seconds.checked_add(part))
+            .ok_or_else(too_long)?;
+        rest = &rest[captures[0].len()..];
+    }
+    Ok(seconds)
+}
+
+fn iso_duration_seconds(input: &str) -> Result<i64, String> {
+    let captures = ISO_DURATION
+        .captures(input)
+        .filter(|captures| captures.iter().skip(1).any(|part| part.is_some()))
+        .ok_or_else(|| format!("Could not read `{}` as an ISO-8601 duration.", input))?;
+    if captures.get(1).is_some() || captures.get(2).is_some() {
+        return Err("Durations in years or months are not a fixed length.".to_string());
+    }
+    let mut seconds: i64 = 0;
+    for (index, unit) in [
+        (3, 60 * 60 * 24 * 7),
+        (4, 60 * 60 * 24),
+        (5, 60 * 60),
+        (6, 60),
+        (7, 1),
+    ] {
+        if let Some(part) = captures.get(index) {
+            let value: i64 = part.as_str().parse().map_err(|_| too_long())?;
+            seconds = value
+                .checked_mul(unit)
+                .and_then(|part| seconds.checked_add(part))
+                .ok_or_else(too_long)?;
+        }
+    }
+    Ok(seconds)
+}
+
+/// Read a wall-clock time in `timezone`. When clocks go back the earlier
+/// reading is used.
+fn from_local(local: NaiveDateTime, timezone: Tz) -> Result<DateTime<Utc>, String> {
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => Ok(time.with_timezone(&Utc)),
+        LocalResult::None => Err(format!(
+            "{} does not exist in {}, as the clocks go forward.",
+            local, timezone
+        )),
+    }
+}
+
+fn too_long() -> String {
+    "That time is too far away.".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::{Europe::London, UTC};
+
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn compound_durations_add_up() {
+        assert_eq!(parse_duration("1h30m"), Ok(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("2d 12h"), Ok(TimeDelta::hours(60)));
+        assert_eq!(parse_duration(" 1 week "), Ok(TimeDelta::weeks(1)));
+        assert_eq!(parse_duration("45 Seconds"), Ok(TimeDelta::seconds(45)));
+    }
+
+    #[test]
+    fn iso_durations_are_read() {
+        assert_eq!(parse_duration("PT90M"), Ok(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("P1W2D"), Ok(TimeDelta::days(9)));
+        assert_eq!(parse_duration("p1dt1h"), Ok(TimeDelta::hours(25)));
+    }
+
+    #[test]
+    fn bad_durations_are_refused() {
+        for input in [
+            "",
+            "1x",
+            "h",
+            "1h30",
+            "P",
+            "PT",
+            "P1M",
+            "P1Y",
+            "99999999999999999999w",
+        ] {
+            assert!(parse_duration(input).is_err(), "{:?} was accepted", input);
+        }
+    }
+
+    #[test]
+    fn absolute_times_are_read() {
+        let now = at("2024-07-01T12:00:00Z");
+        assert_eq!(
+            parse_time("<t:1700000000:R>", now, UTC),
+            Ok(DateTime::from_timestamp(1700000000, 0).unwrap())
+        );
+        assert_eq!(
+            parse_time("2024-06-01T12:00:00+02:00", now, London),
+            Ok(at("2024-06-01T10:00:00Z"))
+        );
+        assert_eq!(
+            parse_time("2024-07-01 18:00", now, London),
+            Ok(at("2024-07-01T17:00:00Z"))
+        );
+        assert_eq!(
+            parse_time("2024-12-25", now, London),
+            Ok(at("2024-12-25T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn times_of_day_are_the_next_occurrence() {
+        let now = at("2024-07-01T12:00:00Z");
+        assert_eq!(
+            parse_time("18:00", now, London),
+            Ok(at("2024-07-01T17:00:00Z"))
+        );
+        assert_eq!(
+            parse_time("09:00", now, London),
+            Ok(at("2024-07-02T08:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn relative_times_start_from_now() {
+        let now = at("2024-07-01T12:00:00Z");
+        assert_eq!(
+            parse_time("in 2h", now, UTC),
+            Ok(at("2024-07-01T14:00:00Z"))
+        );
+        assert_eq!(
+            parse_time("PT30M", now, UTC),
+            Ok(at("2024-07-01T12:30:00Z"))
+        );
+        assert!(parse_time("tomorrow", now, UTC).is_err());
+    }
+
+    #[test]
+    fn clock_changes_are_handled() {
+        let now = at("2024-01-01T00:00:00Z");
+        // Skipped when the clocks go forward
+        assert!(parse_time("2024-03-31 01:30", now, London).is_err());
+        // Repeated when they go back, so the first is used
+        assert_eq!(
+            parse_time("2024-10-27 01:30", now, London),
+            Ok(at("2024-10-27T00:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn lengths_can_be_an_end_time() {
+        let start = at("2024-07-01T12:00:00Z");
+        assert_eq!(parse_length("2h", start, UTC), Ok(TimeDelta::hours(2)));
+        assert_eq!(
+            parse_length("2024-07-01 14:30", start, UTC),
+            Ok(TimeDelta::minutes(150))
+        );
+    }
+
+    #[test]
+    fn timeout_windows_fit_discord() {
+        let now = at("2024-07-01T12:00:00Z");
+        assert_eq!(
+            timeout_window(now, TimeDelta::hours(1), now),
+            Ok((now.timestamp(), now.timestamp() + 3600))
+        );
+        assert!(timeout_window(now - TimeDelta::seconds(30), TimeDelta::hours(1), now).is_ok());
+        assert!(timeout_window(now - TimeDelta::minutes(5), TimeDelta::hours(1), now).is_err());
+        assert!(timeout_window(now, TimeDelta::zero(), now).is_err());
+        assert!(timeout_window(now, MAX_TIMEOUT, now).is_ok());
+        assert!(timeout_window(now, MAX_TIMEOUT + TimeDelta::seconds(1), now).is_err());
+    }
+}